@@ -85,7 +85,7 @@ def build_book():
                 
             f.write(struct.pack(">QHHI", key, move_int, min(weight, 0xFFFF), 0))
 
-    print("Success. Opening book generated.")
+    print(f"Success. Opening book generated, play with it using --book {BOOK_FILE}")
 
 if __name__ == "__main__":
     if os.path.exists(BOOK_FILE):
@@ -69,6 +69,9 @@ pub struct Bitboards {
     pub white_queenside: bool,
     pub black_kingside: bool,
     pub black_queenside: bool,
+    /// Neural network hidden layer, kept up to date by `apply_move`.
+    #[cfg(feature = "nnue")]
+    pub accumulator: crate::engine::nnue::Accumulator,
 }
 
 impl Bitboards {
@@ -80,6 +83,8 @@ impl Bitboards {
             white_queenside: true,
             black_kingside: true,
             black_queenside: true,
+            #[cfg(feature = "nnue")]
+            accumulator: Default::default(),
         }
     }
 
@@ -96,12 +101,12 @@ impl Bitboards {
 
         let mut squares: [Option<(char, u8)>; 64] = [None; 64];
 
-        for color in 0..2 {
-            for piece in 0..6 {
+        for (color, symbols) in PIECES.iter().enumerate() {
+            for (piece, &symbol) in symbols.iter().enumerate() {
                 let mut bb = board.boards[color][piece];
                 while bb != 0 {
                     let sq = bb.trailing_zeros() as usize;
-                    squares[sq] = Some((PIECES[color][piece], color as u8));
+                    squares[sq] = Some((symbol, color as u8));
                     bb &= bb - 1;
                 }
             }
@@ -191,6 +196,32 @@ impl Bitboards {
         *bb |= 1 << square;
     }
 
+    //returns a bitboard with every piece of the given color
+    pub fn color_occupancy(bitboards: &Bitboards, color: Color) -> u64 {
+        bitboards.boards[color as usize]
+            .iter()
+            .fold(0u64, |acc, &bb| acc | bb)
+    }
+
+    //returns a bitboard with every piece on the board
+    pub fn occupancy(bitboards: &Bitboards) -> u64 {
+        Self::color_occupancy(bitboards, Color::White)
+            | Self::color_occupancy(bitboards, Color::Black)
+    }
+
+    //returns color and type of the piece standing on the square, if any
+    pub fn piece_at(bitboards: &Bitboards, square: u8) -> Option<(Color, PieceType)> {
+        let mask = 1u64 << square;
+        for color in [Color::White, Color::Black] {
+            for piece in PieceType::pieces() {
+                if bitboards.boards[color as usize][piece as usize] & mask != 0 {
+                    return Some((color, piece));
+                }
+            }
+        }
+        None
+    }
+
     //count pieces on the bitboard
     pub fn count_pieces(bitboard: u64) -> i32 {
         let mut count: i32 = 0;
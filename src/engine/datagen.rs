@@ -0,0 +1,206 @@
+//! # Module: `datagen`
+//!
+//! Generates training data for tuning and network training by letting the engine
+//! play against itself.
+//!
+//! ## Games
+//!
+//! * Every game starts from the initial position followed by a few **random
+//!   moves**, so no two games are alike.
+//! * Both sides then search a fixed number of nodes per move with
+//!   [`search_nodes`], which keeps the games fast and their quality even.
+//! * A game ends by checkmate, stalemate, threefold repetition, the fifty-move
+//!   rule, insufficient material or a ply limit (scored as a draw).
+//!
+//! ## Recorded Positions
+//!
+//! Each position is stored with the search score and, once the game is over, its
+//! final result. "Noisy" positions are left out because their static evaluation
+//! is meaningless: the side to move is in check, the best move is a capture or a
+//! promotion, or the score is a forced mate.
+//!
+//! ## Output Format
+//!
+//! Plain text, one position per line: `<fen> | <score> | <result>`, with the score
+//! in centipawns and the result (`1.0`, `0.5`, `0.0`) both from White's point of
+//! view. The `tune` command reads these files directly.
+//!
+//! ## Threads
+//!
+//! Games are played on several threads at once. Each finished game is written in
+//! one piece, so lines of different games never interleave.
+
+use crate::board::{Bitboards, Color, PieceType};
+use crate::engine::make_move::{self, is_check};
+use crate::engine::parse_fen;
+use crate::engine::search::{MATE_BOUND, opposite, search_nodes};
+use crate::engine::zobrist;
+use rand::Rng;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// Games still running after this many plies are scored as draws
+const MAX_PLIES: u32 = 400;
+
+/// Settings of a data generation run.
+pub struct DatagenOptions {
+    /// Number of games to play in total.
+    pub games: usize,
+    /// Nodes searched for every move.
+    pub nodes: u64,
+    /// Number of random moves played before the engine takes over.
+    pub random_plies: u32,
+    /// Number of games played at the same time.
+    pub threads: usize,
+}
+
+// A recorded position waiting for the game result
+struct Record {
+    fen: String,
+    white_score: i32,
+}
+
+/// Plays `options.games` self-play games and writes the recorded positions to `out`.
+///
+/// # Returns
+///
+/// The number of positions written, or the I/O error that stopped the run.
+pub fn generate(out: &Path, options: &DatagenOptions) -> io::Result<usize> {
+    let writer = Mutex::new(BufWriter::new(File::create(out)?));
+    let next_game = AtomicUsize::new(0);
+    let positions = AtomicUsize::new(0);
+
+    thread::scope(|scope| -> io::Result<()> {
+        let workers: Vec<_> = (0..options.threads.max(1))
+            .map(|_| {
+                scope.spawn(|| -> io::Result<()> {
+                    while next_game.fetch_add(1, Ordering::Relaxed) < options.games {
+                        let (records, result) = play_game(options);
+                        let mut lines = String::new();
+                        for record in &records {
+                            lines.push_str(&format!(
+                                "{} | {} | {:.1}\n",
+                                record.fen, record.white_score, result
+                            ));
+                        }
+                        writer.lock().unwrap().write_all(lines.as_bytes())?;
+
+                        let total =
+                            positions.fetch_add(records.len(), Ordering::Relaxed) + records.len();
+                        let finished = next_game.load(Ordering::Relaxed).min(options.games);
+                        println!("Game {} / {}: {} positions", finished, options.games, total);
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap()?;
+        }
+        Ok(())
+    })?;
+
+    writer.into_inner().unwrap().flush()?;
+    Ok(positions.into_inner())
+}
+
+// Plays one game, returning the quiet positions and the result for White
+fn play_game(options: &DatagenOptions) -> (Vec<Record>, f64) {
+    let mut rng = rand::rng();
+    let (mut board, mut color) = random_opening(&mut rng, options.random_plies);
+
+    let mut records = Vec::new();
+    let mut history = vec![zobrist::hash(&board, color)];
+    let mut halfmove_clock = 0;
+    let mut fullmove_number = 1 + options.random_plies / 2;
+
+    for _ in 0..MAX_PLIES {
+        let Some((mv, score)) = search_nodes(&board, color, options.nodes) else {
+            // No legal move: checkmate or stalemate
+            let result = if is_check(&board, color) {
+                match color {
+                    Color::White => 0.0,
+                    Color::Black => 1.0,
+                }
+            } else {
+                0.5
+            };
+            return (records, result);
+        };
+
+        let noisy = is_check(&board, color)
+            || mv.is_capture
+            || mv.promotion_rights
+            || board.en_passant_square == Some(mv.to) && mv.piece == PieceType::Pawn
+            || score.abs() >= MATE_BOUND;
+        if !noisy {
+            records.push(Record {
+                fen: parse_fen::board_to_fen(&board, color, halfmove_clock, fullmove_number),
+                white_score: match color {
+                    Color::White => score,
+                    Color::Black => -score,
+                },
+            });
+        }
+
+        let irreversible = mv.is_capture || mv.piece == PieceType::Pawn;
+        make_move::apply_move(&mut board, &mv, color);
+        if color == Color::Black {
+            fullmove_number += 1;
+        }
+        color = opposite(color);
+
+        if irreversible {
+            halfmove_clock = 0;
+            history.clear();
+        } else {
+            halfmove_clock += 1;
+        }
+        let key = zobrist::hash(&board, color);
+        history.push(key);
+
+        let repetitions = history.iter().filter(|&&k| k == key).count();
+        if repetitions >= 3 || halfmove_clock >= 100 || insufficient_material(&board) {
+            return (records, 0.5);
+        }
+    }
+
+    (records, 0.5)
+}
+
+// Plays random legal moves from the initial position, retrying if the game ends
+// on the way
+fn random_opening(rng: &mut impl Rng, plies: u32) -> (Bitboards, Color) {
+    'retry: loop {
+        let mut board = parse_fen::board_from_fen(START_FEN).expect("valid start position");
+        let mut color = Color::White;
+        for _ in 0..plies {
+            let moves = make_move::generate_legal_moves(&board, color);
+            if moves.is_empty() {
+                continue 'retry;
+            }
+            let mv = &moves[rng.random_range(0..moves.len())];
+            make_move::apply_move(&mut board, mv, color);
+            color = opposite(color);
+        }
+        if !make_move::generate_legal_moves(&board, color).is_empty() {
+            return (board, color);
+        }
+    }
+}
+
+// No pawns or heavy pieces and at most one minor piece: nobody can mate
+fn insufficient_material(board: &Bitboards) -> bool {
+    let count = |piece: PieceType| {
+        Bitboards::count_pieces(board.boards[0][piece as usize])
+            + Bitboards::count_pieces(board.boards[1][piece as usize])
+    };
+    count(PieceType::Pawn) + count(PieceType::Rook) + count(PieceType::Queen) == 0
+        && count(PieceType::Knight) + count(PieceType::Bishop) <= 1
+}
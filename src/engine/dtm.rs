@@ -0,0 +1,747 @@
+//! # Module: `dtm`
+//!
+//! **Distance-to-mate tables** for endings with few pieces, generated by the
+//! engine itself. Unlike the [`syzygy`](crate::engine::syzygy) tables they need
+//! no external files, and they tell exactly how many plies it takes to mate
+//! instead of only who wins.
+//!
+//! ## Material Signatures
+//!
+//! A table is named after its material, the side listed first being White in
+//! the table: `KQK`, `KRK`, `KPK`, `KRKP`, ... Probing also uses a table for the
+//! position with the colors swapped. A table holds at most [`MAX_PIECES`]
+//! pieces, Kings included.
+//!
+//! ## Generation
+//!
+//! [`generate`] works by **retrograde analysis**:
+//!
+//! 1. **Forward pass:** every position of the table is set up and its moves are
+//!    generated with [`generate_legal_moves`](make_move::generate_legal_moves).
+//!    Checkmates and stalemates are decided right away. Captures and promotions
+//!    leave the table; they are played with [`apply_move`](make_move::apply_move)
+//!    and looked up in the smaller tables, which are generated first.
+//! 2. **Retrograde pass:** starting from the mates, results are propagated
+//!    backwards one ply at a time by taking moves back. A position is won in
+//!    `n + 1` plies once some move reaches a position lost in `n`, and lost once
+//!    every move reaches a won position. Since the plies are handled in order,
+//!    the first result found is the fastest mate (or the slowest defence).
+//! 3. Whatever is still undecided is a **draw**.
+//!
+//! ## Indexing
+//!
+//! One entry per side to move and placement of the pieces. The board is
+//! mirrored so the White King is on files a-d, which halves the table: a
+//! 4-piece table has `2 x 32 x 64^3` entries.
+//!
+//! ## File Format
+//!
+//! One file `<signature>.dtm` per table: the magic bytes `RDTM`, the signature
+//! as a length-prefixed string, the number of entries (`u32`, little endian) and
+//! the entries as `(value, run length)` pairs, the run length being a LEB128
+//! varint. A value is `0` for a draw and otherwise the number of plies to mate
+//! plus one: odd values are losses for the side to move, even values wins.
+//!
+//! ## Limits
+//!
+//! * Positions with castling rights are not probed.
+//! * En passant is ignored by the generator, so positions where it is possible
+//!   are not probed.
+//! * Promotions are always to a Queen, like everywhere else in the engine.
+//! * The fifty-move rule is not taken into account.
+
+use crate::board::{Bitboards, Color, PieceType};
+use crate::engine::make_move::{
+    self, bishop_attacks, is_check, king_attacks, knight_attacks, rook_attacks,
+};
+use crate::engine::search::{MATE_SCORE, opposite};
+use crate::movegen::Move;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Most pieces, Kings included, a table can hold.
+pub const MAX_PIECES: usize = 4;
+
+/// Longest mate, in plies, a table can store.
+pub const MAX_PLIES: usize = 252;
+
+const MAGIC: [u8; 4] = *b"RDTM";
+
+// Generation states besides the number of plies to mate
+const DRAW: u8 = 253;
+const INVALID: u8 = 254;
+const UNKNOWN: u8 = 255;
+
+// Pieces besides the King in signature order
+const LETTERS: [(char, PieceType); 5] = [
+    ('Q', PieceType::Queen),
+    ('R', PieceType::Rook),
+    ('B', PieceType::Bishop),
+    ('N', PieceType::Knight),
+    ('P', PieceType::Pawn),
+];
+
+/// Result of a position from the point of view of the side to move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dtm {
+    Draw,
+    /// Mates in this many plies.
+    Win(u32),
+    /// Is mated in this many plies.
+    Loss(u32),
+}
+
+impl Dtm {
+    fn from_value(value: u8) -> Self {
+        match value {
+            0 => Dtm::Draw,
+            v if v % 2 == 1 => Dtm::Loss(u32::from(v - 1)),
+            v => Dtm::Win(u32::from(v - 1)),
+        }
+    }
+
+    /// The search score of the result, probed `ply` plies below the root: a
+    /// mate score that prefers fast mates and slow defeats.
+    pub fn score(self, ply: u32) -> i32 {
+        match self {
+            Dtm::Draw => 0,
+            Dtm::Win(plies) => MATE_SCORE - (ply + plies) as i32,
+            Dtm::Loss(plies) => -(MATE_SCORE - (ply + plies) as i32),
+        }
+    }
+
+    // The result for the side that moved into this position
+    fn before_move(self) -> Self {
+        match self {
+            Dtm::Draw => Dtm::Draw,
+            Dtm::Win(plies) => Dtm::Loss(plies + 1),
+            Dtm::Loss(plies) => Dtm::Win(plies + 1),
+        }
+    }
+}
+
+/// The pieces of a table in index order: White King, Black King, then the other
+/// White and Black pieces in signature order.
+#[derive(Clone, Debug)]
+struct Signature {
+    pieces: Vec<(Color, PieceType)>,
+}
+
+impl Signature {
+    fn new(mut white: Vec<PieceType>, mut black: Vec<PieceType>) -> Self {
+        let order = |piece: &PieceType| LETTERS.iter().position(|(_, p)| p == piece);
+        white.sort_by_key(order);
+        black.sort_by_key(order);
+        let mut pieces = vec![
+            (Color::White, PieceType::King),
+            (Color::Black, PieceType::King),
+        ];
+        pieces.extend(white.into_iter().map(|piece| (Color::White, piece)));
+        pieces.extend(black.into_iter().map(|piece| (Color::Black, piece)));
+        Self { pieces }
+    }
+
+    /// Parses a signature like `KRKP`.
+    fn parse(name: &str) -> io::Result<Self> {
+        let invalid = |reason: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid material signature '{}': {}", name, reason),
+            )
+        };
+        let name = name.to_ascii_uppercase();
+        let Some(rest) = name.strip_prefix('K') else {
+            return Err(invalid("it must start with a King"));
+        };
+        let Some((white, black)) = rest.split_once('K') else {
+            return Err(invalid("it needs a King for each side"));
+        };
+        let pieces = |letters: &str| {
+            letters
+                .chars()
+                .map(|letter| {
+                    LETTERS
+                        .iter()
+                        .find(|(l, _)| *l == letter)
+                        .map(|&(_, piece)| piece)
+                        .ok_or_else(|| invalid("unknown piece letter"))
+                })
+                .collect::<io::Result<Vec<_>>>()
+        };
+
+        let signature = Self::new(pieces(white)?, pieces(black)?);
+        if signature.pieces.len() > MAX_PIECES {
+            return Err(invalid("too many pieces"));
+        }
+        if signature.pieces.len() == 2 {
+            return Err(invalid("two bare Kings need no table"));
+        }
+        Ok(signature)
+    }
+
+    fn name(&self) -> String {
+        let letters = |color: Color| -> String {
+            let mut side = String::from("K");
+            side.extend(
+                self.pieces
+                    .iter()
+                    .filter(|&&(c, piece)| c == color && piece != PieceType::King)
+                    .map(|&(_, piece)| letter(piece)),
+            );
+            side
+        };
+        letters(Color::White) + &letters(Color::Black)
+    }
+
+    fn side(&self, color: Color) -> Vec<PieceType> {
+        self.pieces[2..]
+            .iter()
+            .filter(|&&(c, _)| c == color)
+            .map(|&(_, piece)| piece)
+            .collect()
+    }
+
+    fn swapped(&self) -> Self {
+        Self::new(self.side(Color::Black), self.side(Color::White))
+    }
+
+    // Material reached by capturing a piece or promoting a pawn
+    fn successors(&self) -> Vec<Signature> {
+        let mut successors = Vec::new();
+        for color in [Color::White, Color::Black] {
+            let side = self.side(color);
+            let other = self.side(opposite(color));
+            for (i, &piece) in side.iter().enumerate() {
+                let mut changed = side.clone();
+                changed.remove(i);
+                successors.push(Self::with_side(color, changed.clone(), other.clone()));
+                if piece == PieceType::Pawn {
+                    changed.push(PieceType::Queen);
+                    successors.push(Self::with_side(color, changed, other.clone()));
+                }
+            }
+        }
+        successors
+    }
+
+    fn with_side(color: Color, side: Vec<PieceType>, other: Vec<PieceType>) -> Self {
+        match color {
+            Color::White => Self::new(side, other),
+            Color::Black => Self::new(other, side),
+        }
+    }
+
+    // The board with the pieces on `squares`, or `None` if they overlap or a
+    // pawn stands on the first or last rank
+    fn setup(&self, squares: &[u8]) -> Option<Bitboards> {
+        let mut board = Bitboards::default();
+        let mut occupied = 0u64;
+        for (&(color, piece), &sq) in self.pieces.iter().zip(squares) {
+            if occupied & (1 << sq) != 0 || piece == PieceType::Pawn && !(8..56).contains(&sq) {
+                return None;
+            }
+            occupied |= 1 << sq;
+            Bitboards::add_piece(&mut board, color, piece, sq);
+        }
+        Some(board)
+    }
+
+    // Indices of the positions, with `mover` to move, from which `mover` can
+    // reach the position on `squares` without capturing or promoting
+    fn unmoves(&self, squares: &[u8], mover: Color) -> Vec<usize> {
+        let occupied = squares.iter().fold(0u64, |occ, &sq| occ | (1 << sq));
+        let mut predecessors = Vec::new();
+
+        for (slot, &(color, piece)) in self.pieces.iter().enumerate() {
+            if color != mover {
+                continue;
+            }
+            let sq = squares[slot];
+            let origins = match piece {
+                PieceType::King => king_attacks(sq),
+                PieceType::Knight => knight_attacks(sq),
+                PieceType::Bishop => bishop_attacks(sq, occupied),
+                PieceType::Rook => rook_attacks(sq, occupied),
+                PieceType::Queen => bishop_attacks(sq, occupied) | rook_attacks(sq, occupied),
+                PieceType::Pawn => pawn_origins(sq, color, occupied),
+            } & !occupied;
+
+            let mut bits = origins;
+            while bits != 0 {
+                let from = bits.trailing_zeros() as u8;
+                bits &= bits - 1;
+                let mut before = squares.to_vec();
+                before[slot] = from;
+                predecessors.push(index(mover == Color::White, &mut before));
+            }
+        }
+
+        predecessors
+    }
+}
+
+// Squares a pawn on `sq` can have been pushed from
+fn pawn_origins(sq: u8, color: Color, occupied: u64) -> u64 {
+    let (single, double, double_rank) = match color {
+        Color::White => (sq.checked_sub(8), sq.checked_sub(16), 3),
+        Color::Black => (Some(sq + 8), Some(sq + 16), 4),
+    };
+    let mut origins = 0;
+    if let Some(from) = single.filter(|&from| (8..56).contains(&from)) {
+        origins |= 1 << from;
+        if let Some(double) = double.filter(|_| sq / 8 == double_rank)
+            && occupied & (1 << from) == 0
+        {
+            origins |= 1 << double;
+        }
+    }
+    origins
+}
+
+fn letter(piece: PieceType) -> char {
+    LETTERS
+        .iter()
+        .find(|&&(_, p)| p == piece)
+        .map_or('K', |&(letter, _)| letter)
+}
+
+fn table_size(pieces: usize) -> usize {
+    2 * 32 * 64usize.pow(pieces as u32 - 1)
+}
+
+// Mirrors the squares so the White King is on files a-d, then packs them with
+// the side to move
+fn index(white_to_move: bool, squares: &mut [u8]) -> usize {
+    if squares[0] % 8 > 3 {
+        for sq in squares.iter_mut() {
+            *sq ^= 7;
+        }
+    }
+    let king = squares[0];
+    let mut idx = usize::from(!white_to_move) * 32 + (king / 8 * 4 + king % 8) as usize;
+    for &sq in &squares[1..] {
+        idx = idx * 64 + sq as usize;
+    }
+    idx
+}
+
+// Unpacks an index into `squares`, returning whether White is to move
+fn decode(mut idx: usize, squares: &mut [u8]) -> bool {
+    for sq in squares[1..].iter_mut().rev() {
+        *sq = (idx % 64) as u8;
+        idx /= 64;
+    }
+    let king = idx % 32;
+    squares[0] = (king / 4 * 8 + king % 4) as u8;
+    idx / 32 == 0
+}
+
+/// A generated table.
+pub struct Table {
+    signature: Signature,
+    values: Vec<u8>,
+}
+
+impl Table {
+    fn probe(&self, white_to_move: bool, squares: &mut [u8]) -> Dtm {
+        Dtm::from_value(self.values[index(white_to_move, squares)])
+    }
+
+    fn save(&self, dir: &Path) -> io::Result<()> {
+        let name = self.signature.name();
+        let mut out = BufWriter::new(File::create(dir.join(format!("{}.dtm", name)))?);
+        out.write_all(&MAGIC)?;
+        out.write_all(&[name.len() as u8])?;
+        out.write_all(name.as_bytes())?;
+        out.write_all(&(self.values.len() as u32).to_le_bytes())?;
+        for run in self.values.chunk_by(|a, b| a == b) {
+            out.write_all(&[run[0]])?;
+            let mut len = run.len();
+            while len >= 0x80 {
+                out.write_all(&[(len & 0x7F) as u8 | 0x80])?;
+                len >>= 7;
+            }
+            out.write_all(&[len as u8])?;
+        }
+        out.flush()
+    }
+
+    fn load(path: &Path) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        let corrupt = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not a valid DTM table", path.display()),
+            )
+        };
+        let mut pos = 0;
+        let mut take = |len: usize| {
+            let bytes = data.get(pos..pos + len).ok_or_else(corrupt)?;
+            pos += len;
+            Ok::<_, io::Error>(bytes)
+        };
+
+        if take(4)? != MAGIC {
+            return Err(corrupt());
+        }
+        let name_len = take(1)?[0] as usize;
+        let name = std::str::from_utf8(take(name_len)?).map_err(|_| corrupt())?;
+        let signature = Signature::parse(name)?;
+        let count = u32::from_le_bytes(take(4)?.try_into().unwrap()) as usize;
+        if count != table_size(signature.pieces.len()) {
+            return Err(corrupt());
+        }
+
+        let mut values = Vec::with_capacity(count);
+        while values.len() < count {
+            let value = take(1)?[0];
+            let mut len = 0;
+            let mut shift = 0;
+            loop {
+                let byte = take(1)?[0];
+                len |= ((byte & 0x7F) as usize) << shift;
+                shift += 7;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            values.resize(values.len() + len, value);
+        }
+        if values.len() != count {
+            return Err(corrupt());
+        }
+
+        Ok(Self { signature, values })
+    }
+}
+
+static TABLES: OnceLock<HashMap<String, Table>> = OnceLock::new();
+
+/// Loads every table in `dir` for probing.
+///
+/// # Returns
+///
+/// The number of tables loaded, or the error of a table that could not be read.
+pub fn init(dir: &Path) -> io::Result<usize> {
+    let tables = load_dir(dir)?;
+    let count = tables.len();
+    TABLES
+        .set(tables)
+        .map_err(|_| io::Error::other("DTM tables are already loaded"))?;
+    Ok(count)
+}
+
+fn load_dir(dir: &Path) -> io::Result<HashMap<String, Table>> {
+    let mut tables = HashMap::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) == Some("dtm") {
+            let table = Table::load(&path)?;
+            tables.insert(table.signature.name(), table);
+        }
+    }
+    Ok(tables)
+}
+
+/// Probes the loaded tables.
+///
+/// # Returns
+///
+/// The result for `color` to move, or `None` if no table covers the position.
+pub fn probe(board: &Bitboards, color: Color) -> Option<Dtm> {
+    let tables = TABLES.get()?;
+    if Bitboards::occupancy(board).count_ones() as usize > MAX_PIECES
+        || board.white_kingside
+        || board.white_queenside
+        || board.black_kingside
+        || board.black_queenside
+    {
+        return None;
+    }
+    // The tables know nothing about en passant
+    if board.en_passant_square.is_some_and(|ep| {
+        make_move::pawn_attacks(ep, opposite(color))
+            & board.boards[color as usize][PieceType::Pawn as usize]
+            != 0
+    }) {
+        return None;
+    }
+    lookup(tables, board, color)
+}
+
+/// Picks the root move with the best result: the fastest mate, a draw, or the
+/// slowest defeat.
+///
+/// # Returns
+///
+/// The move and its result for `color`, or `None` if the tables do not cover the
+/// position and every position it leads to.
+pub fn root_move(board: &Bitboards, color: Color) -> Option<(Move, Dtm)> {
+    probe(board, color)?;
+
+    let mut best: Option<(Move, Dtm)> = None;
+    for mv in make_move::generate_legal_moves(board, color) {
+        let mut next = board.clone();
+        make_move::apply_move(&mut next, &mv, color);
+        let result = probe(&next, opposite(color))?.before_move();
+        if best
+            .as_ref()
+            .is_none_or(|(_, best)| result.score(0) > best.score(0))
+        {
+            best = Some((mv, result));
+        }
+    }
+    best
+}
+
+// Looks the position up in the table for its material, or the one with the
+// colors swapped. Two bare Kings are a draw without a table.
+fn lookup(tables: &HashMap<String, Table>, board: &Bitboards, color: Color) -> Option<Dtm> {
+    let side = |color: Color| -> Vec<PieceType> {
+        LETTERS
+            .iter()
+            .flat_map(|&(_, piece)| {
+                let count = board.boards[color as usize][piece as usize].count_ones();
+                std::iter::repeat_n(piece, count as usize)
+            })
+            .collect()
+    };
+    let signature = Signature::new(side(Color::White), side(Color::Black));
+    if signature.pieces.len() == 2 {
+        return Some(Dtm::Draw);
+    }
+
+    let (table, swap) = match tables.get(&signature.name()) {
+        Some(table) => (table, false),
+        None => (tables.get(&signature.swapped().name())?, true),
+    };
+
+    // Hand out the squares of every piece type in turn; swapping the colors
+    // also flips the board so pawns keep moving up for White
+    let mut remaining = board.boards;
+    let mut squares = [0u8; MAX_PIECES];
+    for (slot, &(c, piece)) in table.signature.pieces.iter().enumerate() {
+        let c = if swap { opposite(c) } else { c };
+        let bits = &mut remaining[c as usize][piece as usize];
+        let sq = bits.trailing_zeros() as u8;
+        *bits &= *bits - 1;
+        squares[slot] = if swap { sq ^ 56 } else { sq };
+    }
+    let white_to_move = (color == Color::White) != swap;
+    Some(table.probe(white_to_move, &mut squares[..table.signature.pieces.len()]))
+}
+
+/// Generates the tables for `signatures` and every smaller table they lead to
+/// by captures and promotions, and writes them to `dir`. Tables already in `dir`
+/// are loaded instead of generated again.
+///
+/// # Returns
+///
+/// The number of tables generated, or the error of an invalid signature or a
+/// table that could not be read or written.
+pub fn generate(signatures: &[String], dir: &Path) -> io::Result<usize> {
+    fs::create_dir_all(dir)?;
+    let mut tables = load_dir(dir)?;
+    let mut generated = 0;
+    for name in signatures {
+        let signature = Signature::parse(name)?;
+        generated += generate_with_successors(&signature, &mut tables, dir)?;
+    }
+    Ok(generated)
+}
+
+fn generate_with_successors(
+    signature: &Signature,
+    tables: &mut HashMap<String, Table>,
+    dir: &Path,
+) -> io::Result<usize> {
+    if signature.pieces.len() == 2
+        || tables.contains_key(&signature.name())
+        || tables.contains_key(&signature.swapped().name())
+    {
+        return Ok(0);
+    }
+
+    let mut generated = 0;
+    for successor in signature.successors() {
+        generated += generate_with_successors(&successor, tables, dir)?;
+    }
+    let table = build(signature, tables);
+    table.save(dir)?;
+    tables.insert(signature.name(), table);
+    Ok(generated + 1)
+}
+
+fn build(signature: &Signature, tables: &HashMap<String, Table>) -> Table {
+    let n = signature.pieces.len();
+    let size = table_size(n);
+    let mut state = vec![UNKNOWN; size];
+    // Moves whose result is not known yet, plus one for each capture or
+    // promotion that does not lose
+    let mut pending = vec![0u8; size];
+    // Slowest loss among the captures and promotions
+    let mut exit_loss = vec![0u8; size];
+    // Positions decided at every ply, and those won by a capture or promotion
+    let mut by_plies: Vec<Vec<u32>> = vec![Vec::new(); MAX_PLIES + 2];
+    let mut exit_wins: Vec<Vec<u32>> = vec![Vec::new(); MAX_PLIES + 2];
+    let mut squares = [0u8; MAX_PIECES];
+
+    let pb = ProgressBar::new(size as u64);
+    pb.set_style(ProgressStyle::with_template("{msg} [{bar:40}] {percent}%").unwrap());
+    pb.set_message(format!("Generating {}", signature.name()));
+
+    // Forward pass
+    for idx in 0..size {
+        if idx % (1 << 16) == 0 {
+            pb.set_position(idx as u64);
+        }
+        let white_to_move = decode(idx, &mut squares[..n]);
+        let color = if white_to_move {
+            Color::White
+        } else {
+            Color::Black
+        };
+        let Some(board) = signature.setup(&squares[..n]) else {
+            state[idx] = INVALID;
+            continue;
+        };
+        if is_check(&board, opposite(color)) {
+            state[idx] = INVALID;
+            continue;
+        }
+
+        let moves = make_move::generate_legal_moves(&board, color);
+        if moves.is_empty() {
+            if is_check(&board, color) {
+                state[idx] = 0;
+                by_plies[0].push(idx as u32);
+            } else {
+                state[idx] = DRAW;
+            }
+            continue;
+        }
+
+        let mut fastest_win = None;
+        for mv in &moves {
+            if !mv.is_capture && !mv.promotion_rights {
+                pending[idx] += 1;
+                continue;
+            }
+            let mut next = board.clone();
+            make_move::apply_move(&mut next, mv, color);
+            let result = lookup(tables, &next, opposite(color))
+                .expect("smaller tables are generated first")
+                .before_move();
+            match result {
+                Dtm::Win(plies) => {
+                    pending[idx] += 1;
+                    fastest_win =
+                        Some(fastest_win.map_or(plies, |fastest: u32| fastest.min(plies)));
+                }
+                Dtm::Loss(plies) => exit_loss[idx] = exit_loss[idx].max(plies as u8),
+                Dtm::Draw => pending[idx] += 1,
+            }
+        }
+
+        if let Some(plies) = fastest_win {
+            exit_wins[checked_plies(plies as usize)].push(idx as u32);
+        } else if pending[idx] == 0 {
+            state[idx] = exit_loss[idx];
+            by_plies[checked_plies(exit_loss[idx] as usize)].push(idx as u32);
+        }
+    }
+
+    // Retrograde pass
+    for plies in 0..=MAX_PLIES {
+        for idx in std::mem::take(&mut exit_wins[plies]) {
+            if state[idx as usize] == UNKNOWN {
+                state[idx as usize] = plies as u8;
+                by_plies[plies].push(idx);
+            }
+        }
+
+        for idx in std::mem::take(&mut by_plies[plies]) {
+            let white_to_move = decode(idx as usize, &mut squares[..n]);
+            let mover = if white_to_move {
+                Color::Black
+            } else {
+                Color::White
+            };
+            for before in signature.unmoves(&squares[..n], mover) {
+                if state[before] != UNKNOWN {
+                    continue;
+                }
+                if plies % 2 == 0 {
+                    // Moving here mates one ply later
+                    state[before] = plies as u8 + 1;
+                    by_plies[checked_plies(plies + 1)].push(before as u32);
+                } else {
+                    pending[before] -= 1;
+                    if pending[before] == 0 {
+                        let loss = (plies + 1).max(exit_loss[before] as usize);
+                        state[before] = loss as u8;
+                        by_plies[checked_plies(loss)].push(before as u32);
+                    }
+                }
+            }
+        }
+    }
+    pb.finish_with_message(format!("Generated {}", signature.name()));
+
+    let values = state
+        .into_iter()
+        .map(|state| match state {
+            DRAW | INVALID | UNKNOWN => 0,
+            plies => plies + 1,
+        })
+        .collect();
+    Table {
+        signature: signature.clone(),
+        values,
+    }
+}
+
+fn checked_plies(plies: usize) -> usize {
+    assert!(plies <= MAX_PLIES, "mate too long for a DTM table");
+    plies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Longest win of a table, in plies
+    fn longest_win(name: &str) -> u32 {
+        let table = build(&Signature::parse(name).unwrap(), &HashMap::new());
+        table
+            .values
+            .iter()
+            .filter_map(|&value| match Dtm::from_value(value) {
+                Dtm::Win(plies) => Some(plies),
+                _ => None,
+            })
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn queen_mates_in_ten_moves() {
+        assert_eq!(longest_win("KQK"), 19);
+    }
+
+    #[test]
+    fn rook_mates_in_sixteen_moves() {
+        assert_eq!(longest_win("KRK"), 31);
+    }
+
+    #[test]
+    fn scores_count_plies_from_the_root() {
+        assert_eq!(Dtm::Win(3).score(2), MATE_SCORE - 5);
+        assert_eq!(Dtm::Loss(2).score(1), -(MATE_SCORE - 3));
+        assert_eq!(Dtm::Draw.score(7), 0);
+    }
+}
@@ -0,0 +1,357 @@
+//! # Module: `endgame`
+//!
+//! Knowledge about specific endgames that the general evaluation terms cannot
+//! express. Once few pieces are left, *which* pieces are on the board often
+//! matters more than where they stand: King and Rook always beat a lone King,
+//! while a Bishop and a Rook pawn of the wrong color cannot.
+//!
+//! ## Material Signatures
+//!
+//! A position is classified by its [`Material`]: the number of pieces of every
+//! type per side, written like `KRPKR`. The strong side comes first in the
+//! evaluators below.
+//!
+//! ## Specialised Evaluators
+//!
+//! These replace the normal evaluation entirely (see [`evaluate`]):
+//!
+//! * **Mop-up (`KXK`):** A lone King against enough material to mate. The score is
+//!   a known win plus a gradient that drives the lone King to the edge and brings
+//!   the strong King closer, so the search finds the mate instead of shuffling.
+//! * **`KBNK`:** Bishop and Knight can only mate in a corner of the Bishop's
+//!   color, so the lone King is driven to one of those two corners.
+//! * **`KPK`:** Decided exactly by the [`kpk`] bitbase: won positions score as a
+//!   known win that grows as the pawn advances, drawn ones as `0`.
+//!
+//! ## Scale Factors
+//!
+//! Other endings keep the normal evaluation, but it is scaled towards a draw by [`scale_factor`] (out of [`SCALE_NORMAL`]):
+//!
+//! * **Insufficient material:** The strong side has no pawns and at most a minor
+//!   piece, or only two Knights: it cannot win.
+//! * **Small advantage without pawns:** e.g. `KRKB` or `KRNKR`; usually drawn.
+//! * **Wrong-colored Bishop:** Bishop and pawns all on one Rook file whose
+//!   promotion square the Bishop does not cover, with the defending King in the
+//!   corner: a dead draw.
+//! * **Opposite-colored Bishops:** Even a pawn or two up, these are hard to win,
+//!   especially without other pieces.
+
+use crate::board::{Bitboards, Color, PieceType};
+use crate::engine::evaluation::LIGHT_SQUARES;
+use crate::engine::kpk;
+use crate::engine::params::EvalParams;
+use crate::search::opposite;
+use std::fmt;
+
+/// Scale factor that leaves the evaluation unchanged.
+pub const SCALE_NORMAL: i32 = 64;
+
+// Base score of a position known to be won, well below mate scores
+const KNOWN_WIN: i32 = 2_000;
+
+// Rough piece values used to compare material independently of the parameters
+// pawn, knight, bishop, rook, queen, king
+const SIMPLE_VALUES: [i32; 6] = [1, 3, 3, 5, 9, 0];
+
+/// Number of pieces of every type, per side.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Material {
+    counts: [[i32; 6]; 2],
+}
+
+impl Material {
+    pub fn of(board: &Bitboards) -> Self {
+        let mut counts = [[0; 6]; 2];
+        for (color, side) in counts.iter_mut().enumerate() {
+            for (piece, count) in side.iter_mut().enumerate() {
+                *count = Bitboards::count_pieces(board.boards[color][piece]);
+            }
+        }
+        Self { counts }
+    }
+
+    pub fn count(&self, color: Color, piece: PieceType) -> i32 {
+        self.counts[color as usize][piece as usize]
+    }
+
+    /// Material without pawns and King, in pawn units.
+    pub fn non_pawn(&self, color: Color) -> i32 {
+        (1..5)
+            .map(|piece| self.counts[color as usize][piece] * SIMPLE_VALUES[piece])
+            .sum()
+    }
+
+    // True if the side has nothing but its King
+    fn is_bare(&self, color: Color) -> bool {
+        self.counts[color as usize][..5]
+            .iter()
+            .all(|&count| count == 0)
+    }
+
+    // True if the side has exactly the listed pieces besides its King
+    fn is_exactly(&self, color: Color, pieces: [i32; 5]) -> bool {
+        self.counts[color as usize][..5] == pieces
+    }
+}
+
+impl fmt::Display for Material {
+    /// Writes the signature with the side ahead in material first, e.g. `KRPKR`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = |color: Color| {
+            self.non_pawn(color) + self.count(color, PieceType::Pawn) * SIMPLE_VALUES[0]
+        };
+        let first = if value(Color::Black) > value(Color::White) {
+            Color::Black
+        } else {
+            Color::White
+        };
+        for color in [first, opposite(first)] {
+            write!(f, "K")?;
+            for (piece, letter) in [(4, 'Q'), (3, 'R'), (2, 'B'), (1, 'N'), (0, 'P')] {
+                for _ in 0..self.counts[color as usize][piece] {
+                    write!(f, "{}", letter)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Evaluates the position with a specialised evaluator, if one applies.
+///
+/// # Arguments
+///
+/// * `board` - A reference to the current [`Bitboards`] state.
+/// * `color` - The side to move; the score is from its point of view.
+/// * `params` - The weights providing the material values.
+///
+/// # Returns
+///
+/// The score replacing the normal evaluation, or `None` if the material on the
+/// board has no specialised evaluator.
+pub fn evaluate(board: &Bitboards, color: Color, params: &EvalParams) -> Option<i32> {
+    if let Some(score) = kpk_score(board, color) {
+        return Some(score);
+    }
+
+    let material = Material::of(board);
+    for strong in [Color::White, Color::Black] {
+        let weak = opposite(strong);
+        if !material.is_bare(weak) {
+            continue;
+        }
+
+        let score = if material.is_exactly(strong, [0, 1, 1, 0, 0]) {
+            kbnk(board, strong, params)
+        } else if has_mating_material(&material, board, strong) {
+            mop_up(board, strong, params)
+        } else {
+            None
+        };
+
+        return score.map(|score| if color == strong { score } else { -score });
+    }
+    None
+}
+
+/// How much of the endgame evaluation the strong side can expect to convert.
+///
+/// # Arguments
+///
+/// * `board` - A reference to the current [`Bitboards`] state.
+/// * `strong` - The side the evaluation favors.
+///
+/// # Returns
+///
+/// A factor between `0` (dead draw) and [`SCALE_NORMAL`] (no scaling) for the
+/// tapered evaluation.
+pub fn scale_factor(board: &Bitboards, strong: Color) -> i32 {
+    let material = Material::of(board);
+    let weak = opposite(strong);
+    let strong_pawns = material.count(strong, PieceType::Pawn);
+
+    if strong_pawns == 0 {
+        let strong_pieces = material.non_pawn(strong);
+        let only_knights = material.is_exactly(strong, [0, 2, 0, 0, 0]);
+        if strong_pieces <= SIMPLE_VALUES[PieceType::Bishop as usize] || only_knights {
+            return 0;
+        }
+        if strong_pieces - material.non_pawn(weak) <= SIMPLE_VALUES[PieceType::Bishop as usize] {
+            return 16;
+        }
+    }
+
+    if wrong_bishop(board, &material, strong) {
+        return 0;
+    }
+
+    if opposite_bishops(board, &material) {
+        let pure = material.non_pawn(strong) == SIMPLE_VALUES[PieceType::Bishop as usize]
+            && material.non_pawn(weak) == SIMPLE_VALUES[PieceType::Bishop as usize];
+        return if pure { 24 } else { 48 };
+    }
+
+    SCALE_NORMAL
+}
+
+// Queen, Rook, Bishop and Knight or Bishops on both colors can force mate
+fn has_mating_material(material: &Material, board: &Bitboards, strong: Color) -> bool {
+    let bishops = board.boards[strong as usize][PieceType::Bishop as usize];
+    material.count(strong, PieceType::Queen) > 0
+        || material.count(strong, PieceType::Rook) > 0
+        || material.count(strong, PieceType::Bishop) > 0
+            && material.count(strong, PieceType::Knight) > 0
+        || bishops & LIGHT_SQUARES != 0 && bishops & !LIGHT_SQUARES != 0
+}
+
+// Lone King: drive it to the edge and bring the strong King closer
+fn mop_up(board: &Bitboards, strong: Color, params: &EvalParams) -> Option<i32> {
+    let (strong_king, weak_king) = kings(board, strong)?;
+    Some(
+        KNOWN_WIN
+            + material_eg(board, strong, params)
+            + 20 * center_distance(weak_king)
+            + 10 * (7 - distance(strong_king, weak_king)),
+    )
+}
+
+// Bishop and Knight: drive the lone King to a corner of the Bishop's color
+fn kbnk(board: &Bitboards, strong: Color, params: &EvalParams) -> Option<i32> {
+    let (strong_king, weak_king) = kings(board, strong)?;
+    let bishop = board.boards[strong as usize][PieceType::Bishop as usize];
+    // a1 and h8 are dark, h1 and a8 light
+    let corners: [u8; 2] = if bishop & LIGHT_SQUARES != 0 {
+        [7, 56]
+    } else {
+        [0, 63]
+    };
+    let corner_distance = corners
+        .iter()
+        .map(|&corner| manhattan_distance(weak_king, corner))
+        .min()
+        .unwrap_or(0);
+
+    Some(
+        KNOWN_WIN + material_eg(board, strong, params) + 10 * center_distance(weak_king)
+            - 20 * corner_distance
+            + 10 * (7 - distance(strong_king, weak_king)),
+    )
+}
+
+/// Scores a King and pawn against King position from the bitbase.
+///
+/// # Arguments
+///
+/// * `board` - A reference to the current [`Bitboards`] state.
+/// * `color` - The side to move; the score is from its point of view.
+///
+/// # Returns
+///
+/// `None` if the position is not a valid `KPK` position. Otherwise `0` for a draw, or a known win
+/// that grows as the pawn advances.
+pub fn kpk_score(board: &Bitboards, color: Color) -> Option<i32> {
+    let material = Material::of(board);
+    let strong = [Color::White, Color::Black].into_iter().find(|&strong| {
+        material.is_exactly(strong, [1, 0, 0, 0, 0]) && material.is_bare(opposite(strong))
+    })?;
+
+    let (strong_king, weak_king) = kings(board, strong)?;
+    let pawn = board.boards[strong as usize][PieceType::Pawn as usize].trailing_zeros() as u8;
+    // A pawn on the first or last rank is not a real position
+    if !(8..56).contains(&pawn) {
+        return None;
+    }
+    // Look at the position as if the strong side were White
+    let relative = |sq: u8| match strong {
+        Color::White => sq,
+        Color::Black => sq ^ 56,
+    };
+    let pawn = relative(pawn);
+    if !kpk::probe(
+        relative(strong_king),
+        pawn,
+        relative(weak_king),
+        color == strong,
+    ) {
+        return Some(0);
+    }
+
+    let score = KNOWN_WIN + 20 * (pawn / 8) as i32;
+    Some(if color == strong { score } else { -score })
+}
+
+// A Bishop that does not cover the promotion square of its Rook pawns
+fn wrong_bishop(board: &Bitboards, material: &Material, strong: Color) -> bool {
+    let weak = opposite(strong);
+    if !material.is_exactly(
+        strong,
+        [material.count(strong, PieceType::Pawn), 0, 1, 0, 0],
+    ) || material.non_pawn(weak) > 0
+    {
+        return false;
+    }
+
+    let pawns = board.boards[strong as usize][PieceType::Pawn as usize];
+    let file = pawns.trailing_zeros() as u8 % 8;
+    let file_bits = 0x0101_0101_0101_0101u64 << file;
+    if (file != 0 && file != 7) || pawns & !file_bits != 0 {
+        return false;
+    }
+
+    let promotion = match strong {
+        Color::White => 56 + file,
+        Color::Black => file,
+    };
+    let bishop = board.boards[strong as usize][PieceType::Bishop as usize];
+    let bishop_is_light = bishop & LIGHT_SQUARES != 0;
+    let promotion_is_light = (1u64 << promotion) & LIGHT_SQUARES != 0;
+    let Some((_, weak_king)) = kings(board, strong) else {
+        return false;
+    };
+
+    bishop_is_light != promotion_is_light && distance(weak_king, promotion) <= 1
+}
+
+// One Bishop each, on squares of different colors
+fn opposite_bishops(board: &Bitboards, material: &Material) -> bool {
+    if material.count(Color::White, PieceType::Bishop) != 1
+        || material.count(Color::Black, PieceType::Bishop) != 1
+    {
+        return false;
+    }
+    let white = board.boards[Color::White as usize][PieceType::Bishop as usize];
+    let black = board.boards[Color::Black as usize][PieceType::Bishop as usize];
+    (white & LIGHT_SQUARES != 0) != (black & LIGHT_SQUARES != 0)
+}
+
+fn material_eg(board: &Bitboards, color: Color, params: &EvalParams) -> i32 {
+    (0..5)
+        .map(|piece| {
+            params.material[piece].eg * Bitboards::count_pieces(board.boards[color as usize][piece])
+        })
+        .sum()
+}
+
+// Squares of the strong and the weak King, `None` if either King is missing
+fn kings(board: &Bitboards, strong: Color) -> Option<(u8, u8)> {
+    let square = |color: Color| {
+        let king = board.boards[color as usize][PieceType::King as usize];
+        (king != 0).then(|| king.trailing_zeros() as u8)
+    };
+    Some((square(strong)?, square(opposite(strong))?))
+}
+
+// King moves between two squares
+fn distance(a: u8, b: u8) -> i32 {
+    ((a % 8).abs_diff(b % 8)).max((a / 8).abs_diff(b / 8)) as i32
+}
+
+fn manhattan_distance(a: u8, b: u8) -> i32 {
+    ((a % 8).abs_diff(b % 8) + (a / 8).abs_diff(b / 8)) as i32
+}
+
+// 0 on the four center squares, up to 6 in the corners
+fn center_distance(sq: u8) -> i32 {
+    let from_center = |x: u8| if x < 4 { 3 - x } else { x - 4 };
+    (from_center(sq % 8) + from_center(sq / 8)) as i32
+}
@@ -9,19 +9,24 @@
 //!
 //! ## Evaluation Components
 //!
-//! The final score is a weighted sum of several distinct heuristics:
+//! The final score is a weighted sum of several distinct heuristics. Every term is a
+//! [`Score`]: a pair of **middlegame** and **endgame** values.
 //!
 //! ### 1. Material Balance
-//! The most fundamental metric. Each piece is assigned a static value:
-//! * **Pawn:** 100 | **Knight:** 320 | **Bishop:** 330 | **Rook:** 500 | **Queen:** 900.
+//! The most fundamental metric. Each piece is assigned a static value per phase
+//! (default values):
+//! * **Middlegame:** Pawn 100 | Knight 320 | Bishop 330 | Rook 500 | Queen 900.
+//! * **Endgame:** Pawn 120 | Knight 300 | Bishop 320 | Rook 530 | Queen 950.
 //!
 //! ### 2. Piece-Square Tables (PST)
 //! Positional value based on where pieces are placed. For example, Knights are
 //! rewarded for being in the center and penalized for being on the "rim,"
 //! while Pawns are rewarded for advancing toward promotion.
-//! * **King Safety:** The King uses different PSTs depending on the game phase.
-//!   In the **Middlegame**, it seeks safety in the corners; in the **Endgame**,
-//!   it is encouraged to move to the center to become an active attacker.
+//! * **King Safety:** In the **Middlegame** table the King seeks safety in the
+//!   corners; in the **Endgame** table it is encouraged to move to the center to
+//!   become an active attacker.
+//! * **Orientation:** Tables are written the way a board is printed, a8 first, from
+//!   White's point of view. Black reads the same table vertically mirrored.
 //!
 //! [Image of piece-square tables in computer chess]
 //!
@@ -38,350 +43,720 @@
 //!   board ("A Knight on the rim is dim").
 //! * **Early Flank Pawn Penalty:** Discourages pushing edge pawns too early
 //!   in the opening, which can weaken the King's future home.
+//! * **Hanging Pieces:** Uses Static Exchange Evaluation to find pieces of the side
+//!   to move that the opponent wins by exchange. Only one of them can be rescued
+//!   with the next move, so the others are counted as (half) lost.
 //!
 //! [Image of center control and pawn structure in chess evaluation]
 //!
-//! ## Game Phase Detection
-//! The engine uses [`is_endgame`] to dynamically shift its priorities.
-//! If the Queens are off the board (or very few pieces remain), the engine
-//! transitions into "Endgame Mode," fundamentally changing how it values
-//! King placement.
+//! ### 5. Pawn Structure
+//! Doubled, isolated, backward, connected and passed pawns are scored by the
+//! `pawns` module, which caches its results in a pawn hash table.
+//!
+//! ### 6. King Safety
+//! The `king_safety` module scores the pawn shield and pawn storms in front of each
+//! King, open files next to it, and the enemy pieces attacking the squares around
+//! it, converted into a penalty through an attack-unit table.
+//!
+//! ### 7. Piece Placement
+//! Coordination terms for the pieces themselves:
+//! * **Bishop Pair:** Two bishops covering both square colors.
+//! * **Open Files:** Rooks (and, less so, Queens) on files without friendly pawns.
+//! * **Seventh Rank:** Rooks and Queens on the 7th rank attacking pawns or
+//!   cutting off the enemy King.
+//! * **Outposts:** Knights and Bishops on the 4th-6th ranks, defended by a pawn
+//!   and out of reach of enemy pawns.
+//! * **Bad Bishops:** Bishops blocked in by their own pawns on their square color.
+//! * **Trapped Pieces:** A Bishop caught on a7/h7 by a pawn on b6/g6, or a Rook
+//!   locked in the corner by its own uncastled King.
+//!
+//! ## Tapered Evaluation
+//! Instead of switching between "Middlegame Mode" and "Endgame Mode", the engine
+//! measures how much material is left with [`game_phase`] (from `MAX_PHASE` with
+//! all pieces on the board down to `0` with bare Kings and Pawns) and blends the
+//! two values of every term:
+//!
+//! $eval = \frac{mg \cdot phase + eg \cdot (MAX\_PHASE - phase)}{MAX\_PHASE}$
+//!
+//! Trading a single piece therefore moves the score a little instead of flipping
+//! whole tables at once.
+//!
+//! ## Endgames
+//! The `endgame` module recognizes material signatures. Endings with a
+//! specialised evaluator (a lone King against mating material, `KBNK`, `KPK`
+//! from a bitbase) skip the terms above; drawish ones (insufficient material,
+//! wrong-colored Bishop, opposite-colored Bishops) have the tapered score scaled
+//! towards zero.
+//!
+//! ## Parameters
+//! None of the weights above are hard-coded here: every term reads them from an
+//! [`EvalParams`] value (see the `params` module), so they can be loaded from a
+//! file and tuned without recompiling.
 
 use crate::board::{Bitboards, Color, PieceType};
+use crate::engine::endgame::{self, Material};
+use crate::engine::king_safety;
+use crate::engine::make_move;
+use crate::engine::params::{self, DevelopmentParams, EvalParams, PieceParams};
+use crate::engine::pawns;
+use crate::engine::see;
 use crate::movegen::Move;
+use crate::search::opposite;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+/// A middlegame/endgame pair of values for a single evaluation term.
+///
+/// Terms are accumulated as pairs and only blended into one number at the very
+/// end, by [`Score::taper`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Score {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+impl Score {
+    pub const ZERO: Score = Score { mg: 0, eg: 0 };
 
-// mobility weight gives mobility a proper weigth in final eval
+    pub const fn new(mg: i32, eg: i32) -> Self {
+        Self { mg, eg }
+    }
 
-const PST_WEIGHT: i32 = 5;
+    /// Blends the middlegame and endgame values according to the game phase.
+    pub fn taper(self, phase: i32) -> i32 {
+        (self.mg * phase + self.eg * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+impl Add for Score {
+    type Output = Score;
+    fn add(self, rhs: Score) -> Score {
+        Score::new(self.mg + rhs.mg, self.eg + rhs.eg)
+    }
+}
+
+impl Sub for Score {
+    type Output = Score;
+    fn sub(self, rhs: Score) -> Score {
+        Score::new(self.mg - rhs.mg, self.eg - rhs.eg)
+    }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, rhs: Score) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign for Score {
+    fn sub_assign(&mut self, rhs: Score) {
+        *self = *self - rhs;
+    }
+}
+
+impl Mul<i32> for Score {
+    type Output = Score;
+    fn mul(self, rhs: i32) -> Score {
+        Score::new(self.mg * rhs, self.eg * rhs)
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+    fn neg(self) -> Score {
+        Score::new(-self.mg, -self.eg)
+    }
+}
+
+// Phase value of all pieces on the starting position (4 minors, 4 rooks, 2 queens)
+pub const MAX_PHASE: i32 = 24;
+
+// How much each piece type contributes to the game phase
+// pawn, knight, bishop, rook, queen, king
+const PHASE_WEIGHTS: [i32; 6] = [0, 1, 1, 2, 4, 0];
 
 const CENTRAL_SQUARES: [u8; 4] = [27, 28, 35, 36]; // d4, e4, d5, e5
 
 const RIM_SQUARES: [u8; 16] = [0, 8, 16, 24, 32, 40, 48, 56, 7, 15, 23, 31, 39, 47, 55, 63];
 
-// Piece-square table for pawn
-// White Pawn PST (Encourages advancement and slight center control)
-const WHITE_PAWN_PST: [i32; 64] = [
-    0, 0, 0, 0, 0, 0, 0, 0, // Rank 8 (Promotion handled elsewhere)
-    50, 50, 50, 50, 50, 50, 50, 50, // Rank 7 (Almost promoted!)
-    10, 10, 20, 30, 30, 20, 10, 10, // Rank 6
-    5, 5, 10, 25, 25, 10, 5, 5, // Rank 5
-    0, 0, 0, 20, 20, 0, 0, 0, // Rank 4
-    5, -5, -10, 0, 0, -10, -5, 5, // Rank 3
-    5, 10, 10, -20, -20, 10, 10, 5, // Rank 2 (Defending the King)
-    0, 0, 0, 0, 0, 0, 0, 0, // Rank 1
-];
+// b1, d1, ... a2, c2, ...: every square of the same color as h1
+pub(crate) const LIGHT_SQUARES: u64 = 0x55AA55AA55AA55AA;
 
-// Black Pawn PST (Flipped version of White)
-const BLACK_PAWN_PST: [i32; 64] = [
-    0, 0, 0, 0, 0, 0, 0, 0, // Rank 1
-    5, 10, 10, -20, -20, 10, 10, 5, // Rank 2
-    5, -5, -10, 0, 0, -10, -5, 5, // Rank 3
-    0, 0, 0, 20, 20, 0, 0, 0, // Rank 4
-    5, 5, 10, 25, 25, 10, 5, 5, // Rank 5
-    10, 10, 20, 30, 30, 20, 10, 10, // Rank 6
-    50, 50, 50, 50, 50, 50, 50, 50, // Rank 7
-    0, 0, 0, 0, 0, 0, 0, 0, // Rank 8
-];
+/// Evaluates a position with the active parameters (see [`params::active`]).
+///
+/// With the `nnue` feature and a loaded network, the network evaluates instead.
+pub fn evaluation(board: &Bitboards, color: Color) -> i32 {
+    #[cfg(feature = "nnue")]
+    if let Some(score) = crate::engine::nnue::evaluate(board, color) {
+        return score;
+    }
+    evaluate_with(board, color, &params::active())
+}
 
-// White Knight PST (Index 0 = a8)
-const WHITE_KNIGHT_PST: [i32; 64] = [
-    -30, -15, -10, -10, -10, -10, -15, -30, // Rank 8
-    -15, 0, 0, 5, 5, 0, 0, -15, // Rank 7
-    -10, 5, 10, 15, 15, 10, 5, -10, // Rank 6
-    -10, 10, 15, 20, 20, 15, 10, -10, // Rank 5
-    -10, 5, 15, 20, 20, 15, 5, -10, // Rank 4
-    -10, 0, 10, 15, 15, 10, 0, -10, // Rank 3
-    -15, -5, 0, 0, 0, 0, -5, -15, // Rank 2
-    -30, -15, -10, -10, -10, -10, -15, -30, // Rank 1
-];
+/// Evaluates a position with an explicit set of parameters.
+///
+/// # Arguments
+///
+/// * `board` - A reference to the current [`Bitboards`] state.
+/// * `color` - The side to move; the score is from its point of view.
+/// * `params` - The weights to use instead of the active ones.
+pub fn evaluate_with(board: &Bitboards, color: Color, params: &EvalParams) -> i32 {
+    // Decide which side is "friendly" and which is "enemy"
+    let (friendly_color, enemy_color) = match color {
+        Color::White => (Color::White, Color::Black),
+        Color::Black => (Color::Black, Color::White),
+    };
 
-// Black Knight PST (Vertical Flip)
-const BLACK_KNIGHT_PST: [i32; 64] = [
-    -30, -15, -10, -10, -10, -10, -15, -30, -15, -5, 0, 0, 0, 0, -5, -15, -10, 0, 10, 15, 15, 10,
-    0, -10, -10, 5, 15, 20, 20, 15, 5, -10, -10, 10, 15, 20, 20, 15, 10, -10, -10, 5, 10, 15, 15,
-    10, 5, -10, -15, 0, 0, 5, 5, 0, 0, -15, -30, -15, -10, -10, -10, -10, -15, -30,
-];
+    /* SPECIALISED ENDGAMES */
+    if let Some(score) = endgame::evaluate(board, color, params) {
+        return score;
+    }
 
-// White Bishop (Index 0 = a8)
-const WHITE_BISHOP_PST: [i32; 64] = [
-    -10, -10, -10, -10, -10, -10, -10, -10, -10, 5, 0, 0, 0, 0, 5, -10, -10, 0, 5, 10, 10, 5, 0,
-    -10, -10, 5, 10, 15, 15, 10, 5, -10, -10, 0, 10, 15, 15, 10, 0, -10, -10, 5, 5, 10, 10, 5, 5,
-    -10, -10, 0, 0, 0, 0, 0, 0, -10, -10, -10, -10, -10, -10, -10, -10, -10,
-];
+    let phase = game_phase(board);
+    let score = side_score(board, friendly_color, params) - side_score(board, enemy_color, params);
 
-const BLACK_BISHOP_PST: [i32; 64] = [
-    -10, -10, -10, -10, -10, -10, -10, -10, // Rank 8 (Home)
-    -10, 0, 0, 0, 0, 0, 0, -10, // Rank 7
-    -10, 5, 5, 10, 10, 5, 5, -10, // Rank 6
-    -10, 0, 10, 15, 15, 10, 0, -10, // Rank 5
-    -10, 5, 10, 15, 15, 10, 5, -10, // Rank 4
-    -10, 0, 5, 10, 10, 5, 0, -10, // Rank 3
-    -10, 5, 0, 0, 0, 0, 5, -10, // Rank 2
-    -10, -10, -10, -10, -10, -10, -10, -10, // Rank 1 (Enemy)
-];
+    /* ENDGAME SCALING */
+    let tapered = score.taper(phase);
+    let strong = if tapered >= 0 {
+        friendly_color
+    } else {
+        enemy_color
+    };
+    let scaled = tapered * endgame::scale_factor(board, strong) / endgame::SCALE_NORMAL;
 
-// Piece-square table for rook
-const WHITE_ROOK_PST: [i32; 64] = [
-    0, 0, 0, 5, 5, 0, 0, 0, 10, 15, 15, 15, 15, 15, 15, 10, // Reward for 7th rank!
-    -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0,
-    0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, 0, 0, 0, 5, 5, 0, 0, 0, // Centralize on start
-];
+    /* HANGING PIECES */
+    let hanging = hanging_penalty(board, friendly_color, enemy_color);
 
-const BLACK_ROOK_PST: [i32; 64] = [
-    0, 0, 0, 5, 5, 0, 0, 0, // Rank 8 (Home)
-    -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0,
-    0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, 10, 15, 15, 15, 15, 15, 15,
-    10, // Rank 2 (Attacking White pawns)
-    0, 0, 0, 5, 5, 0, 0, 0, // Rank 1
-];
+    /* FINAL SCORE */
+    scaled - hanging
+}
 
-//Piece-square table for queen
-const WHITE_QUEEN_PST: [i32; 64] = [
-    -20, -10, -10, -5, -5, -10, -10, -20, -10, 0, 5, 0, 0, 0, 0, -10, -10, 5, 5, 5, 5, 5, 0, -10,
-    -5, 0, 5, 5, 5, 5, 0, -5, 0, 0, 5, 5, 5, 5, 0, -5, -10, 0, 5, 5, 5, 5, 0, -10, -10, 0, 0, 0, 0,
-    0, 0, -10, -20, -10, -10, -5, -5, -10, -10, -20,
-];
+/// Measures how much non-pawn material is left on the board.
+///
+/// # Returns
+///
+/// A value between `0` (only Kings and Pawns left, pure endgame) and [`MAX_PHASE`]
+/// (all pieces on the board, pure middlegame). Extra material from promotions is
+/// clamped to [`MAX_PHASE`].
+pub fn game_phase(board: &Bitboards) -> i32 {
+    let mut phase = 0;
+    for piece in PieceType::pieces() {
+        let idx = piece_type_index(piece);
+        let count = Bitboards::count_pieces(board.boards[0][idx])
+            + Bitboards::count_pieces(board.boards[1][idx]);
+        phase += count * PHASE_WEIGHTS[idx];
+    }
+    phase.min(MAX_PHASE)
+}
 
-const BLACK_QUEEN_PST: [i32; 64] = [
-    -20, -10, -10, -5, -5, -10, -10, -20, // Rank 8
-    -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 5, 5, 5, 0, -10, 0, 0, 5, 5, 5, 5, 0, -5, -5, 0, 5, 5,
-    5, 5, 0, -5, -10, 5, 5, 5, 5, 5, 0, -10, -10, 0, 5, 0, 0, 0, 0, -10, -20, -10, -10, -5, -5,
-    -10, -10, -20, // Rank 1
+/// Names of the per-side evaluation terms, in the order [`side_terms`] returns them.
+pub const TERM_NAMES: [&str; 7] = [
+    "Material",
+    "Piece-square",
+    "Mobility",
+    "Development",
+    "Pawns",
+    "King safety",
+    "Pieces",
 ];
 
-//Piece-square table for king
-const WHITE_KING_MIDDLEGAME_PST: [i32; 64] = [
-    -30, -40, -40, -50, -50, -40, -40, -30, -30, -40, -40, -50, -50, -40, -40, -30, -30, -40, -40,
-    -50, -50, -40, -40, -30, -30, -40, -40, -50, -50, -40, -40, -30, -20, -30, -30, -40, -40, -30,
-    -30, -20, -10, -20, -20, -20, -20, -20, -20, -10, 10, 10, -5, -10, -10, -5, 10, 10, 20, 30, 10,
-    0, 0, 10, 30, 20, // Values are lower (max 30)
-];
+// Every term for one side, from that side's point of view
+fn side_terms(board: &Bitboards, color: Color, params: &EvalParams) -> [Score; TERM_NAMES.len()] {
+    [
+        material(board, color, params),
+        piece_square(board, color, params) * params.pst_weight,
+        mobility(board, color, params),
+        development(board, color, params),
+        pawns::pawn_structure(board, color, &params.pawns),
+        king_safety::king_safety(board, color, &params.king_safety),
+        piece_placement(board, color, params),
+    ]
+}
 
-const BLACK_KING_MIDDLEGAME_PST: [i32; 64] = [
-    20, 30, 10, 0, 0, 10, 30, 20, // Rank 8 (Safe Home)
-    10, 10, -5, -10, -10, -5, 10, 10, // Rank 7
-    -10, -20, -20, -20, -20, -20, -20, -10, -20, -30, -30, -40, -40, -30, -30, -20, -30, -40, -40,
-    -50, -50, -40, -40, -30, -30, -40, -40, -50, -50, -40, -40, -30, -30, -40, -40, -50, -50, -40,
-    -40, -30, -30, -40, -40, -50, -50, -40, -40, -30, // Rank 1 (Danger Zone)
-];
+// Sum of all terms for one side, from that side's point of view
+fn side_score(board: &Bitboards, color: Color, params: &EvalParams) -> Score {
+    side_terms(board, color, params)
+        .into_iter()
+        .fold(Score::ZERO, |total, term| total + term)
+}
 
-const WHITE_KING_ENDGAME_PST: [i32; 64] = [
-    -50, -40, -30, -20, -20, -30, -40, -50, -30, -20, -10, 0, 0, -10, -20, -30, -30, -10, 20, 30,
-    30, 20, -10, -30, -30, -10, 30, 40, 40, 30, -10, -30, -30, -10, 30, 40, 40, 30, -10, -30, -30,
-    -10, 20, 30, 30, 20, -10, -30, -30, -30, 0, 0, 0, 0, -30, -30, -50, -30, -30, -30, -30, -30,
-    -30, -50,
-];
+/// One row of an [`EvalTrace`]: a single term for both sides.
+#[derive(Clone, Copy, Debug)]
+pub struct TermTrace {
+    pub name: &'static str,
+    pub white: Score,
+    pub black: Score,
+}
 
-const BLACK_KING_ENDGAME_PST: [i32; 64] = [
-    -50, -30, -30, -30, -30, -30, -30, -50, -30, -30, 0, 0, 0, 0, -30, -30, -30, -10, 20, 30, 30,
-    20, -10, -30, -30, -10, 30, 40, 40, 30, -10, -30, -30, -10, 30, 40, 40, 30, -10, -30, -30, -10,
-    20, 30, 30, 20, -10, -30, -30, -20, -10, 0, 0, -10, -20, -30, -50, -40, -30, -20, -20, -30,
-    -40, -50,
-];
+/// Breakdown of [`evaluation`] into its individual terms.
+///
+/// Every term is reported for both sides, each from its own point of view, so
+/// `white - black` is the contribution of that term from White's perspective.
+#[derive(Clone, Debug)]
+pub struct EvalTrace {
+    /// The side the final score is computed for.
+    pub color: Color,
+    /// Game phase used for tapering, between `0` and [`MAX_PHASE`].
+    pub phase: i32,
+    pub terms: Vec<TermTrace>,
+    /// Hanging piece penalty of the side to move (already tapered).
+    pub hanging: i32,
+    /// Material signature, e.g. `KRPKR`.
+    pub material: Material,
+    /// Score of the specialised endgame evaluator, which replaces the terms,
+    /// the scaling and the hanging penalty.
+    pub specialised: Option<i32>,
+    /// Scale factor applied to the tapered score, out of [`endgame::SCALE_NORMAL`].
+    pub scale: i32,
+    /// Tapered total of the terms for the side to move, after scaling.
+    pub scaled: i32,
+    /// Score of the loaded network, which replaces all of the above.
+    pub nnue: Option<i32>,
+    /// The final score, identical to [`evaluation`] for the same position.
+    pub total: i32,
+}
 
-pub fn evaluation(board: &Bitboards, color: Color) -> i32 {
-    // Decide which side is "friendly" and which is "enemy"
-    let (friendly_idx, enemy_idx, friendly_color, enemy_color) = match color {
-        Color::White => (0, 1, Color::White, Color::Black),
-        Color::Black => (1, 0, Color::Black, Color::White),
-    };
+/// Evaluates a position like [`evaluation`] (with the active parameters), keeping
+/// every term.
+///
+/// # Arguments
+///
+/// * `board` - A reference to the current [`Bitboards`] state.
+/// * `color` - The side to move; the final score is from its point of view.
+///
+/// # Returns
+///
+/// An [`EvalTrace`] whose `total` equals `evaluation(board, color)`.
+pub fn evaluation_trace(board: &Bitboards, color: Color) -> EvalTrace {
+    let params = params::active();
+    let white = side_terms(board, Color::White, &params);
+    let black = side_terms(board, Color::Black, &params);
+    let terms: Vec<TermTrace> = TERM_NAMES
+        .iter()
+        .zip(white.iter().zip(black.iter()))
+        .map(|(&name, (&white, &black))| TermTrace { name, white, black })
+        .collect();
+    let net = terms
+        .iter()
+        .fold(Score::ZERO, |total, term| total + term.white - term.black);
+
+    // Scaled from the side to move's point of view, exactly like `evaluate_with`
+    let phase = game_phase(board);
+    let tapered = match color {
+        Color::White => net,
+        Color::Black => -net,
+    }
+    .taper(phase);
+    let strong = if tapered >= 0 { color } else { opposite(color) };
+    let scale = endgame::scale_factor(board, strong);
+
+    #[cfg(feature = "nnue")]
+    let nnue = crate::engine::nnue::evaluate(board, color);
+    #[cfg(not(feature = "nnue"))]
+    let nnue = None;
+
+    EvalTrace {
+        color,
+        phase,
+        terms,
+        hanging: hanging_penalty(board, color, opposite(color)),
+        material: Material::of(board),
+        specialised: endgame::evaluate(board, color, &params),
+        scale,
+        scaled: tapered * scale / endgame::SCALE_NORMAL,
+        nnue,
+        total: evaluation(board, color),
+    }
+}
 
-    /* MATERIAL SCORE */
-    let mut friendly_score = 0i32;
-    let mut enemy_score = 0i32;
-
-    // pawn, rook, knight, bishop, queen
-    let piece_values: [i32; 5] = [100, 500, 320, 330, 900];
-    for i in 0..piece_values.len() {
-        let friendly_pieces = Bitboards::count_pieces(board.boards[friendly_idx][i]);
-        let enemy_pieces = Bitboards::count_pieces(board.boards[enemy_idx][i]);
-        friendly_score += friendly_pieces * piece_values[i];
-        enemy_score += enemy_pieces * piece_values[i];
-    }
-
-    let material_score = friendly_score - enemy_score;
-
-    /* MOBILITY SCORE */
-    let mut friendly_moves = 0i32;
-    let mut enemy_moves = 0i32;
-    let piece_mobility_weights = [
-        1, // Pawn
-        4, // Knight
-        4, // Bishop
-        5, // Rook
-        9, // Queen
-        0, // King
-    ];
-
-    for i in 0..PieceType::pieces().len() {
-        let piece = PieceType::pieces()[i];
-
-        // Friendly
-        let friendly_squares = Bitboards::return_squares(board.boards[friendly_idx][i]);
-        for sq in friendly_squares {
-            let possible_moves = Move::generate_moves_for_piece(sq, piece, friendly_color, board);
-            friendly_moves += piece_mobility_weights[i] * possible_moves.len() as i32;
+impl fmt::Display for EvalTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<14} | {:>6} {:>6} | {:>6} {:>6} | {:>6} {:>6} | {:>7}",
+            "Term", "W mg", "W eg", "B mg", "B eg", "mg", "eg", "Tapered"
+        )?;
+        writeln!(f, "{}", "-".repeat(71))?;
+
+        let mut white_total = Score::ZERO;
+        let mut black_total = Score::ZERO;
+        for term in &self.terms {
+            let net = term.white - term.black;
+            writeln!(
+                f,
+                "{:<14} | {:>6} {:>6} | {:>6} {:>6} | {:>6} {:>6} | {:>7}",
+                term.name,
+                term.white.mg,
+                term.white.eg,
+                term.black.mg,
+                term.black.eg,
+                net.mg,
+                net.eg,
+                net.taper(self.phase)
+            )?;
+            white_total += term.white;
+            black_total += term.black;
         }
 
-        // Enemy
-        let enemy_squares = Bitboards::return_squares(board.boards[enemy_idx][i]);
-        for sq in enemy_squares {
-            let possible_moves = Move::generate_moves_for_piece(sq, piece, enemy_color, board);
-            enemy_moves += piece_mobility_weights[i] * possible_moves.len() as i32;
+        let net = white_total - black_total;
+        writeln!(f, "{}", "-".repeat(71))?;
+        writeln!(
+            f,
+            "{:<14} | {:>6} {:>6} | {:>6} {:>6} | {:>6} {:>6} | {:>7}",
+            "Total",
+            white_total.mg,
+            white_total.eg,
+            black_total.mg,
+            black_total.eg,
+            net.mg,
+            net.eg,
+            net.taper(self.phase)
+        )?;
+        writeln!(f, "(mg, eg and Tapered columns are White minus Black)")?;
+        writeln!(f)?;
+        writeln!(f, "Phase:    {} / {}", self.phase, MAX_PHASE)?;
+        if let Some(score) = self.specialised {
+            writeln!(
+                f,
+                "Endgame:  {} {} for {:?} (specialised evaluator, replaces the terms above)",
+                self.material, score, self.color
+            )?;
+        } else {
+            writeln!(
+                f,
+                "Endgame:  {} {} for {:?} (Tapered total scaled by {} / {})",
+                self.material,
+                self.scaled,
+                self.color,
+                self.scale,
+                endgame::SCALE_NORMAL
+            )?;
+            writeln!(f, "Hanging:  {} for {:?}", -self.hanging, self.color)?;
         }
+        if let Some(score) = self.nnue {
+            writeln!(f, "NNUE:     {} (replaces the evaluation above)", score)?;
+        }
+        write!(f, "Eval:     {} ({:?} to move)", self.total, self.color)
     }
+}
 
-    let mobility_score = friendly_moves - enemy_moves;
-
-    /* PIECE-SQUARE TABLE SCORE */
-    let mut pst_score = 0;
-    let endgame = is_endgame(board);
+/* MATERIAL SCORE */
+fn material(board: &Bitboards, color: Color, params: &EvalParams) -> Score {
+    let mut score = Score::ZERO;
+    for piece in PieceType::pieces() {
+        let idx = piece_type_index(piece);
+        score += params.material[idx] * Bitboards::count_pieces(board.boards[color as usize][idx]);
+    }
+    score
+}
 
-    // Friendly PST
+/* PIECE-SQUARE TABLE SCORE */
+fn piece_square(board: &Bitboards, color: Color, params: &EvalParams) -> Score {
+    let mut score = Score::ZERO;
     for piece in PieceType::pieces() {
-        let pst = pst_for(piece, friendly_color, endgame);
-        for sq in Bitboards::return_squares(board.boards[friendly_idx][piece_type_index(piece)]) {
-            pst_score += pst[sq as usize];
+        let table = params.pst.get(piece);
+        for sq in Bitboards::return_squares(board.boards[color as usize][piece_type_index(piece)]) {
+            score += table.score(sq, color);
         }
     }
+    score
+}
 
-    // Enemy PST
+/* MOBILITY SCORE */
+fn mobility(board: &Bitboards, color: Color, params: &EvalParams) -> Score {
+    let mut score = Score::ZERO;
     for piece in PieceType::pieces() {
-        let pst = pst_for(piece, enemy_color, endgame);
-        for sq in Bitboards::return_squares(board.boards[enemy_idx][piece_type_index(piece)]) {
-            pst_score -= pst[sq as usize];
+        let idx = piece_type_index(piece);
+        for sq in Bitboards::return_squares(board.boards[color as usize][idx]) {
+            let possible_moves = Move::generate_moves_for_piece(sq, piece, color, board);
+            score += params.mobility[idx] * possible_moves.len() as i32;
         }
     }
+    score
+}
 
-    /* DEVELOPMENT BONUS */
-    let mut dev_bonus = 0;
-    for sq in
-        Bitboards::return_squares(board.boards[friendly_idx][piece_type_index(PieceType::Pawn)])
-    {
-        dev_bonus += center_pawns(sq);
-        dev_bonus += pawn_development(sq, color);
-        dev_bonus += early_flank_pawn_penalty(sq, enemy_color)
+/* DEVELOPMENT BONUS */
+fn development(board: &Bitboards, color: Color, params: &EvalParams) -> Score {
+    let weights = &params.development;
+    let mut score = Score::ZERO;
+    let side = color as usize;
+    for sq in Bitboards::return_squares(board.boards[side][piece_type_index(PieceType::Pawn)]) {
+        score += center_pawns(sq, weights);
+        score += pawn_development(sq, color, weights);
+        score += early_flank_pawn_penalty(sq, color, weights);
     }
-    for sq in Bitboards::return_squares(board.boards[enemy_idx][piece_type_index(PieceType::Pawn)])
-    {
-        dev_bonus -= center_pawns(sq);
-        dev_bonus -= pawn_development(sq, enemy_color);
-        dev_bonus -= early_flank_pawn_penalty(sq, enemy_color)
+    for sq in Bitboards::return_squares(board.boards[side][piece_type_index(PieceType::Knight)]) {
+        score += knight_penalty(sq, weights);
     }
-    for sq in
-        Bitboards::return_squares(board.boards[enemy_idx][piece_type_index(PieceType::Knight)])
-    {
-        dev_bonus -= knight_penalty(sq);
+    for sq in Bitboards::return_squares(board.boards[side][piece_type_index(PieceType::King)]) {
+        score += castle_bonus(sq, color, weights);
     }
-    for sq in
-        Bitboards::return_squares(board.boards[friendly_idx][piece_type_index(PieceType::Knight)])
-    {
-        dev_bonus += knight_penalty(sq);
+    score
+}
+
+/* PIECE PLACEMENT */
+fn piece_placement(board: &Bitboards, color: Color, params: &EvalParams) -> Score {
+    let weights = &params.pieces;
+    bishop_pair(board, color, weights)
+        + heavy_pieces_on_open_files(board, color, weights)
+        + heavy_pieces_on_seventh(board, color, weights)
+        + outposts(board, color, weights)
+        + bad_bishops(board, color, weights)
+        + trapped_pieces(board, color, weights)
+}
+
+fn bishop_pair(board: &Bitboards, color: Color, weights: &PieceParams) -> Score {
+    let bishops = board.boards[color as usize][piece_type_index(PieceType::Bishop)];
+    if bishops & LIGHT_SQUARES != 0 && bishops & !LIGHT_SQUARES != 0 {
+        weights.bishop_pair
+    } else {
+        Score::ZERO
     }
-    for sq in
-        Bitboards::return_squares(board.boards[friendly_idx][piece_type_index(PieceType::King)])
-    {
-        dev_bonus += castle_bonus(sq, color) * 2;
+}
+
+fn heavy_pieces_on_open_files(board: &Bitboards, color: Color, weights: &PieceParams) -> Score {
+    let side = color as usize;
+    let own_pawns = board.boards[side][piece_type_index(PieceType::Pawn)];
+    let enemy_pawns = board.boards[opposite(color) as usize][piece_type_index(PieceType::Pawn)];
+    let mut score = Score::ZERO;
+
+    for (piece, open, semi_open) in [
+        (
+            PieceType::Rook,
+            weights.rook_open_file,
+            weights.rook_semi_open_file,
+        ),
+        (
+            PieceType::Queen,
+            weights.queen_open_file,
+            weights.queen_semi_open_file,
+        ),
+    ] {
+        for sq in Bitboards::return_squares(board.boards[side][piece_type_index(piece)]) {
+            let file = pawns::file_mask(sq % 8);
+            if own_pawns & file == 0 {
+                score += if enemy_pawns & file == 0 {
+                    open
+                } else {
+                    semi_open
+                };
+            }
+        }
     }
-    for sq in Bitboards::return_squares(board.boards[enemy_idx][piece_type_index(PieceType::King)])
-    {
-        dev_bonus -= castle_bonus(sq, enemy_color) * 2;
+    score
+}
+
+// The 7th rank only matters if there is something there: enemy pawns, or the
+// enemy King cut off on its back rank
+fn heavy_pieces_on_seventh(board: &Bitboards, color: Color, weights: &PieceParams) -> Score {
+    let side = color as usize;
+    let enemy = opposite(color) as usize;
+    let seventh: u64 = match color {
+        Color::White => 0xFF << 48,
+        Color::Black => 0xFF << 8,
+    };
+    let eighth: u64 = match color {
+        Color::White => 0xFF << 56,
+        Color::Black => 0xFF,
+    };
+    let enemy_pawns = board.boards[enemy][piece_type_index(PieceType::Pawn)];
+    let enemy_king = board.boards[enemy][piece_type_index(PieceType::King)];
+    if enemy_pawns & seventh == 0 && enemy_king & eighth == 0 {
+        return Score::ZERO;
     }
-    // println!("{}", dev_bonus);
-    /* FINAL SCORE */
-    let eval = material_score + pst_score * PST_WEIGHT + mobility_score + dev_bonus;
-    eval
-}
-
-fn pst_for(piece: PieceType, color: Color, endgame: bool) -> &'static [i32; 64] {
-    match (piece, color) {
-        (PieceType::Pawn, Color::White) => &WHITE_PAWN_PST,
-        (PieceType::Pawn, Color::Black) => &BLACK_PAWN_PST,
-        (PieceType::Knight, Color::White) => &WHITE_KNIGHT_PST,
-        (PieceType::Knight, Color::Black) => &BLACK_KNIGHT_PST,
-        (PieceType::Bishop, Color::White) => &WHITE_BISHOP_PST,
-        (PieceType::Bishop, Color::Black) => &BLACK_BISHOP_PST,
-        (PieceType::Rook, Color::White) => &WHITE_ROOK_PST,
-        (PieceType::Rook, Color::Black) => &BLACK_ROOK_PST,
-        (PieceType::Queen, Color::White) => &WHITE_QUEEN_PST,
-        (PieceType::Queen, Color::Black) => &BLACK_QUEEN_PST,
-        (PieceType::King, Color::White) => {
-            if endgame {
-                &WHITE_KING_ENDGAME_PST
-            } else {
-                &WHITE_KING_MIDDLEGAME_PST
+
+    let rooks = board.boards[side][piece_type_index(PieceType::Rook)] & seventh;
+    let queens = board.boards[side][piece_type_index(PieceType::Queen)] & seventh;
+    weights.rook_on_seventh * Bitboards::count_pieces(rooks)
+        + weights.queen_on_seventh * Bitboards::count_pieces(queens)
+}
+
+fn outposts(board: &Bitboards, color: Color, weights: &PieceParams) -> Score {
+    let side = color as usize;
+    let enemy_color = opposite(color);
+    let own_pawns = board.boards[side][piece_type_index(PieceType::Pawn)];
+    let enemy_pawns = board.boards[enemy_color as usize][piece_type_index(PieceType::Pawn)];
+    let mut score = Score::ZERO;
+
+    for (piece, bonus) in [
+        (PieceType::Knight, weights.knight_outpost),
+        (PieceType::Bishop, weights.bishop_outpost),
+    ] {
+        for sq in Bitboards::return_squares(board.boards[side][piece_type_index(piece)]) {
+            if !(3..=5).contains(&pawns::relative_rank(sq, color)) {
+                continue;
             }
-        }
-        (PieceType::King, Color::Black) => {
-            if endgame {
-                &BLACK_KING_ENDGAME_PST
-            } else {
-                &BLACK_KING_MIDDLEGAME_PST
+            // A friendly pawn defends the square exactly when an enemy pawn on it would attack that pawn
+            let supported = make_move::pawn_attacks(sq, enemy_color) & own_pawns != 0;
+            let challengers = pawns::adjacent_files(sq % 8) & pawns::ranks_in_front(sq / 8, color);
+            if supported && enemy_pawns & challengers == 0 {
+                score += bonus;
             }
         }
     }
+    score
 }
 
-fn center_pawns(square: u8) -> i32 {
+// A bishop hemmed in by its own pawns on its square color. Central pawns that
+// are blocked cannot move off that color, so they count twice.
+fn bad_bishops(board: &Bitboards, color: Color, weights: &PieceParams) -> Score {
+    let side = color as usize;
+    let own_pawns = board.boards[side][piece_type_index(PieceType::Pawn)];
+    let occupancy = Bitboards::occupancy(board);
+    let central_files =
+        pawns::file_mask(2) | pawns::file_mask(3) | pawns::file_mask(4) | pawns::file_mask(5);
+    let pushed = match color {
+        Color::White => own_pawns << 8,
+        Color::Black => own_pawns >> 8,
+    };
+    let blocked_squares = pushed & occupancy;
+    let blocked_central = match color {
+        Color::White => blocked_squares >> 8,
+        Color::Black => blocked_squares << 8,
+    } & central_files;
+
+    let mut score = Score::ZERO;
+    for sq in Bitboards::return_squares(board.boards[side][piece_type_index(PieceType::Bishop)]) {
+        let same_color = if LIGHT_SQUARES & (1u64 << sq) != 0 {
+            LIGHT_SQUARES
+        } else {
+            !LIGHT_SQUARES
+        };
+        let count = Bitboards::count_pieces(own_pawns & same_color)
+            + Bitboards::count_pieces(blocked_central & same_color);
+        score += weights.bad_bishop_pawn * count;
+    }
+    score
+}
+
+fn trapped_pieces(board: &Bitboards, color: Color, weights: &PieceParams) -> Score {
+    let side = color as usize;
+    let enemy_pawns = board.boards[opposite(color) as usize][piece_type_index(PieceType::Pawn)];
+    let bishops = board.boards[side][piece_type_index(PieceType::Bishop)];
+    let rooks = board.boards[side][piece_type_index(PieceType::Rook)];
+    let king = board.boards[side][piece_type_index(PieceType::King)];
+    // Patterns are written for White and mirrored vertically for Black
+    let at = |sq: u8| -> u64 {
+        match color {
+            Color::White => 1u64 << sq,
+            Color::Black => 1u64 << (sq ^ 56),
+        }
+    };
+    let mut score = Score::ZERO;
+
+    // Bxa7 ... b6 or Bxh7 ... g6: the bishop cannot get out without being lost
+    if bishops & at(48) != 0 && enemy_pawns & at(41) != 0 {
+        score += weights.trapped_bishop;
+    }
+    if bishops & at(55) != 0 && enemy_pawns & at(46) != 0 {
+        score += weights.trapped_bishop;
+    }
+
+    // King walked to f1/g1 (or b1/c1) without castling, locking the Rook in the corner
+    let (can_castle_kingside, can_castle_queenside) = match color {
+        Color::White => (board.white_kingside, board.white_queenside),
+        Color::Black => (board.black_kingside, board.black_queenside),
+    };
+    let king_file = king.trailing_zeros() as u8 % 8;
+    let corner_rooks = |mask: u64| Bitboards::return_squares(rooks & mask);
+    if !can_castle_kingside
+        && king & (at(5) | at(6)) != 0
+        && corner_rooks(at(6) | at(7) | at(15))
+            .iter()
+            .any(|sq| sq % 8 > king_file)
+    {
+        score += weights.trapped_rook;
+    }
+    if !can_castle_queenside
+        && king & (at(1) | at(2)) != 0
+        && corner_rooks(at(0) | at(1) | at(8))
+            .iter()
+            .any(|sq| sq % 8 < king_file)
+    {
+        score += weights.trapped_rook;
+    }
+    score
+}
+
+fn center_pawns(square: u8, weights: &DevelopmentParams) -> Score {
     if CENTRAL_SQUARES.contains(&square) {
-        40
+        weights.center_pawn
     } else {
-        0
+        Score::ZERO
     }
 }
 
-fn pawn_development(square: u8, color: Color) -> i32 {
+fn pawn_development(square: u8, color: Color, weights: &DevelopmentParams) -> Score {
     let rank = square / 8;
     match color {
-        Color::White if rank == 3 || rank == 4 => 40, // encourage 2nd → 3rd/4th rank
-        Color::Black if rank == 4 || rank == 3 => 40,
-        _ => 0,
+        Color::White if rank == 3 || rank == 4 => weights.advanced_pawn, // encourage 2nd → 3rd/4th rank
+        Color::Black if rank == 4 || rank == 3 => weights.advanced_pawn,
+        _ => Score::ZERO,
     }
 }
 
-fn early_flank_pawn_penalty(square: u8, color: Color) -> i32 {
+fn early_flank_pawn_penalty(square: u8, color: Color, weights: &DevelopmentParams) -> Score {
     let file = square % 8;
     let rank = square / 8;
+    let on_flank = file == 6 || file == 7;
     match color {
-        Color::White => {
-            if (file == 6 || file == 7) && rank <= 3 {
-                -15
-            } else {
-                0
-            }
-        }
-        Color::Black => {
-            if (file == 1 || file == 0) && rank >= 4 {
-                -15
-            } else {
-                0
-            }
-        }
+        Color::White if on_flank && rank <= 3 => weights.early_flank_pawn,
+        Color::Black if on_flank && rank >= 4 => weights.early_flank_pawn,
+        _ => Score::ZERO,
     }
 }
 
-fn knight_penalty(sq: u8) -> i32 {
-    if RIM_SQUARES.contains(&sq) { -20 } else { 5 }
+fn knight_penalty(sq: u8, weights: &DevelopmentParams) -> Score {
+    if RIM_SQUARES.contains(&sq) {
+        weights.knight_on_rim
+    } else {
+        weights.knight_off_rim
+    }
 }
 
-fn castle_bonus(sq: u8, color: Color) -> i32 {
-    if color == Color::White {
-        if sq == 2 {
-            40
-        } else if sq == 6 {
-            50
-        } else {
-            0
+fn castle_bonus(sq: u8, color: Color, weights: &DevelopmentParams) -> Score {
+    match (color, sq) {
+        (Color::White, 6) | (Color::Black, 62) => weights.castled_kingside,
+        (Color::White, 2) | (Color::Black, 58) => weights.castled_queenside,
+        _ => Score::ZERO,
+    }
+}
+
+// Material the side to move is about to lose: every piece the enemy wins by
+// exchange, except the most valuable one, which can still be saved this turn.
+fn hanging_penalty(board: &Bitboards, color: Color, enemy_color: Color) -> i32 {
+    let occupancy = Bitboards::occupancy(board);
+    let mut losses = Vec::new();
+
+    for piece in PieceType::pieces() {
+        if piece == PieceType::King {
+            continue;
         }
-    } else {
-        if sq == 62 {
-            40
-        } else if sq == 58 {
-            50
-        } else {
-            0
+        for sq in Bitboards::return_squares(board.boards[color as usize][piece_type_index(piece)]) {
+            let Some((from, attacker)) =
+                see::least_valuable_attacker(board, sq, enemy_color, occupancy)
+            else {
+                continue;
+            };
+            let capture = Move {
+                from,
+                to: sq,
+                piece: attacker,
+                promotion_rights: attacker == PieceType::Pawn && (sq / 8 == 0 || sq / 8 == 7),
+                is_castling: false,
+                is_capture: true,
+            };
+            let loss = see::see_value(board, &capture, enemy_color);
+            if loss > 0 {
+                losses.push(loss);
+            }
         }
     }
+
+    let total: i32 = losses.iter().sum();
+    let saved = losses.iter().copied().max().unwrap_or(0);
+    (total - saved) / 2
 }
 
 fn piece_type_index(piece: PieceType) -> usize {
@@ -394,16 +769,3 @@ fn piece_type_index(piece: PieceType) -> usize {
         PieceType::King => 5,
     }
 }
-
-fn is_endgame(board: &Bitboards) -> bool {
-    let queens = Bitboards::count_pieces(board.boards[0][piece_type_index(PieceType::Queen)])
-        + Bitboards::count_pieces(board.boards[1][piece_type_index(PieceType::Queen)]);
-    let other_pieces = Bitboards::count_pieces(board.boards[0][piece_type_index(PieceType::Rook)])
-        + Bitboards::count_pieces(board.boards[1][piece_type_index(PieceType::Rook)])
-        + Bitboards::count_pieces(board.boards[0][piece_type_index(PieceType::Bishop)])
-        + Bitboards::count_pieces(board.boards[1][piece_type_index(PieceType::Bishop)])
-        + Bitboards::count_pieces(board.boards[0][piece_type_index(PieceType::Knight)])
-        + Bitboards::count_pieces(board.boards[1][piece_type_index(PieceType::Knight)]);
-
-    queens == 0 || (queens == 1 && other_pieces <= 1)
-}
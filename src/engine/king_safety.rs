@@ -0,0 +1,164 @@
+//! # Module: `king_safety`
+//!
+//! Scores how exposed each King is. Castling and the King piece-square tables only
+//! say *where* the King stands; this module looks at what surrounds it:
+//!
+//! * **Pawn Shield:** Friendly pawns on the King's file and the two neighbouring
+//!   files, close in front of the King, keep attackers out.
+//! * **Pawn Storm:** Enemy pawns advancing on those files pry the shield open.
+//!   A storming pawn that is blocked by a shield pawn is much less dangerous.
+//! * **Open Files:** A file near the King without friendly pawns is a highway for
+//!   enemy Rooks and Queens, even more so when it has no pawns at all.
+//! * **Attack Units:** Every enemy piece attacking the **King zone** (the King's
+//!   square, its neighbours and the squares one rank further in front) adds units
+//!   according to its type and the number of zone squares it hits. The total is
+//!   looked up in the safety table, which grows slowly at first and steeply later:
+//!   a single attacker is rarely a threat, a coordinated attack often decides the
+//!   game.
+//!
+//! Shelter terms only matter in the middlegame, so they carry no endgame value.
+//! Attack units keep a small endgame part, since a Queen and Rook can still mate.
+
+use crate::board::{Bitboards, Color, PieceType};
+use crate::engine::evaluation::Score;
+use crate::engine::make_move::{bishop_attacks, king_attacks, knight_attacks, rook_attacks};
+use crate::engine::params::KingSafetyParams;
+use crate::engine::pawns::{file_mask, ranks_in_front};
+
+/// Scores the safety of one side's King, from that side's point of view.
+///
+/// # Arguments
+///
+/// * `board` - A reference to the current [`Bitboards`] state.
+/// * `color` - The side whose King is evaluated.
+/// * `weights` - The king safety weights.
+///
+/// # Returns
+///
+/// A middlegame/endgame [`Score`]; shelter bonuses are positive, exposure and
+/// enemy attacks are negative.
+pub fn king_safety(board: &Bitboards, color: Color, weights: &KingSafetyParams) -> Score {
+    let king = board.boards[color as usize][PieceType::King as usize];
+    if king == 0 {
+        return Score::ZERO;
+    }
+    let king_sq = king.trailing_zeros() as u8;
+
+    shelter(board, king_sq, color, weights) + attack_units(board, king_sq, color, weights)
+}
+
+// Pawn shield, pawn storm and open files on the King's file and its neighbours
+fn shelter(board: &Bitboards, king_sq: u8, color: Color, weights: &KingSafetyParams) -> Score {
+    let enemy_color = match color {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    };
+    let own_pawns = board.boards[color as usize][PieceType::Pawn as usize];
+    let enemy_pawns = board.boards[enemy_color as usize][PieceType::Pawn as usize];
+    let king_file = king_sq % 8;
+    let king_rank = king_sq / 8;
+    let in_front = ranks_in_front(king_rank, color);
+
+    let mut mg = 0;
+    for file in king_file.saturating_sub(1)..=(king_file + 1).min(7) {
+        let file_bits = file_mask(file);
+
+        let shield = nearest(own_pawns & file_bits & in_front, color);
+        match shield {
+            Some(sq) => {
+                mg += weights
+                    .shield
+                    .get(rank_distance(sq, king_rank))
+                    .unwrap_or(&0)
+            }
+            None => mg += weights.shield_missing,
+        }
+
+        if let Some(sq) = nearest(enemy_pawns & file_bits & in_front, color) {
+            let blocked = shield.is_some_and(|own| rank_distance(own, sq / 8) == 1);
+            if blocked {
+                mg += weights.storm_blocked;
+            } else {
+                mg += weights
+                    .storm
+                    .get(rank_distance(sq, king_rank))
+                    .unwrap_or(&0);
+            }
+        }
+
+        if own_pawns & file_bits == 0 {
+            mg += weights.semi_open_file;
+            if enemy_pawns & file_bits == 0 {
+                mg += weights.open_file;
+            }
+        }
+    }
+
+    Score::new(mg, 0)
+}
+
+// Enemy pieces hitting the King zone, weighted by type and number of squares hit
+fn attack_units(board: &Bitboards, king_sq: u8, color: Color, weights: &KingSafetyParams) -> Score {
+    let enemy_color = match color {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    };
+    let occupancy = Bitboards::occupancy(board);
+
+    let mut zone = king_attacks(king_sq) | (1u64 << king_sq);
+    zone |= match color {
+        Color::White => zone << 8,
+        Color::Black => zone >> 8,
+    };
+
+    let mut attackers = 0;
+    let mut units = 0;
+    for piece in [
+        PieceType::Knight,
+        PieceType::Bishop,
+        PieceType::Rook,
+        PieceType::Queen,
+    ] {
+        for sq in Bitboards::return_squares(board.boards[enemy_color as usize][piece as usize]) {
+            let attacks = match piece {
+                PieceType::Knight => knight_attacks(sq),
+                PieceType::Bishop => bishop_attacks(sq, occupancy),
+                PieceType::Rook => rook_attacks(sq, occupancy),
+                _ => bishop_attacks(sq, occupancy) | rook_attacks(sq, occupancy),
+            };
+            let hits = Bitboards::count_pieces(attacks & zone);
+            if hits > 0 {
+                attackers += 1;
+                units += weights.attack_weights[piece as usize] * hits;
+            }
+        }
+    }
+
+    // A lone attacker cannot break through a defended King position
+    if attackers < 2 {
+        return Score::ZERO;
+    }
+
+    let table = &weights.safety_table;
+    let danger = table
+        .get(units as usize)
+        .or(table.last())
+        .copied()
+        .unwrap_or(0);
+    Score::new(-danger, -danger / 4)
+}
+
+// The pawn of `pawns` closest to the King, i.e. the least advanced one
+fn nearest(pawns: u64, color: Color) -> Option<u8> {
+    if pawns == 0 {
+        return None;
+    }
+    match color {
+        Color::White => Some(pawns.trailing_zeros() as u8),
+        Color::Black => Some(63 - pawns.leading_zeros() as u8),
+    }
+}
+
+fn rank_distance(sq: u8, rank: u8) -> usize {
+    (sq / 8).abs_diff(rank) as usize
+}
@@ -0,0 +1,208 @@
+//! # Module: `kpk`
+//!
+//! A **bitbase** for King and pawn against King: one bit per position telling
+//! whether the side with the pawn wins. It is generated once, on the first
+//! probe, by retrograde analysis and then answers every KPK position exactly.
+//!
+//! ## Indexing
+//!
+//! Positions are stored from the point of view of the side with the pawn
+//! ("White"). The board is mirrored so the pawn is always on files a-d, which
+//! leaves `2 (side to move) x 64 (White King) x 64 (Black King) x 24 (pawn)`
+//! entries.
+//!
+//! ## Generation
+//!
+//! 1. **Classify** every position that is decided without looking ahead:
+//!    impossible positions, a pawn that promotes safely, a Black King that
+//!    captures the undefended pawn, stalemate.
+//! 2. **Iterate** over the undecided positions until nothing changes: with
+//!    White to move, a position is won if *some* move reaches a won position;
+//!    with Black to move, if *every* move does. Likewise a position is drawn if
+//!    every White move, or some Black move, reaches a draw.
+//! 3. Whatever is still undecided cannot be forced by White and is a **draw**.
+
+use crate::engine::make_move::king_attacks;
+use std::sync::LazyLock;
+
+// Pawn on files a-d, ranks 2-7
+const PAWN_SQUARES: usize = 24;
+const SIZE: usize = 2 * 64 * 64 * PAWN_SQUARES;
+
+const UNKNOWN: u8 = 0;
+const INVALID: u8 = 1;
+const DRAW: u8 = 2;
+const WIN: u8 = 3;
+
+static BITBASE: LazyLock<Vec<u64>> = LazyLock::new(generate);
+
+/// Looks up whether the side with the pawn wins.
+///
+/// # Arguments
+///
+/// * `strong_king` - Square of the King of the side with the pawn.
+/// * `pawn` - Square of the pawn.
+/// * `weak_king` - Square of the lone King.
+/// * `strong_to_move` - Whether the side with the pawn is to move.
+///
+/// All squares are seen from the side with the pawn, i.e. the pawn moves
+/// towards the 8th rank, and the pawn must stand on ranks 2-7.
+pub fn probe(strong_king: u8, pawn: u8, weak_king: u8, strong_to_move: bool) -> bool {
+    debug_assert!((8..56).contains(&pawn), "pawn on the first or last rank");
+    // Mirror the board so the pawn is on files a-d
+    let (strong_king, pawn, weak_king) = if pawn % 8 > 3 {
+        (strong_king ^ 7, pawn ^ 7, weak_king ^ 7)
+    } else {
+        (strong_king, pawn, weak_king)
+    };
+    let idx = index(strong_to_move, strong_king, weak_king, pawn);
+    BITBASE[idx / 64] & (1 << (idx % 64)) != 0
+}
+
+fn generate() -> Vec<u64> {
+    let mut results: Vec<u8> = (0..SIZE).map(classify).collect();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for idx in 0..SIZE {
+            if results[idx] == UNKNOWN {
+                results[idx] = retrograde(&results, idx);
+                changed |= results[idx] != UNKNOWN;
+            }
+        }
+    }
+
+    let mut bits = vec![0u64; SIZE.div_ceil(64)];
+    for (idx, &result) in results.iter().enumerate() {
+        if result == WIN {
+            bits[idx / 64] |= 1 << (idx % 64);
+        }
+    }
+    bits
+}
+
+fn index(white_to_move: bool, white_king: u8, black_king: u8, pawn: u8) -> usize {
+    let pawn_idx = (pawn / 8 - 1) as usize * 4 + (pawn % 8) as usize;
+    ((usize::from(!white_to_move) * 64 + white_king as usize) * 64 + black_king as usize)
+        * PAWN_SQUARES
+        + pawn_idx
+}
+
+fn decode(idx: usize) -> (bool, u8, u8, u8) {
+    let pawn_idx = idx % PAWN_SQUARES;
+    let pawn = ((pawn_idx / 4 + 1) * 8 + pawn_idx % 4) as u8;
+    let black_king = (idx / PAWN_SQUARES % 64) as u8;
+    let white_king = (idx / PAWN_SQUARES / 64 % 64) as u8;
+    let white_to_move = idx / PAWN_SQUARES / 64 / 64 == 0;
+    (white_to_move, white_king, black_king, pawn)
+}
+
+// Results that follow from the position itself
+fn classify(idx: usize) -> u8 {
+    let (white_to_move, white_king, black_king, pawn) = decode(idx);
+
+    if white_king == black_king
+        || distance(white_king, black_king) <= 1
+        || pawn == white_king
+        || pawn == black_king
+        || white_to_move && pawn_attacks(pawn) & (1 << black_king) != 0
+    {
+        return INVALID;
+    }
+
+    if white_to_move {
+        let promotion = pawn + 8;
+        if pawn / 8 == 6
+            && promotion != white_king
+            && promotion != black_king
+            && (distance(black_king, promotion) > 1 || distance(white_king, promotion) <= 1)
+        {
+            return WIN;
+        }
+    } else {
+        let black_moves =
+            king_attacks(black_king) & !king_attacks(white_king) & !pawn_attacks(pawn);
+        if black_moves == 0 {
+            return if pawn_attacks(pawn) & (1 << black_king) != 0 {
+                WIN
+            } else {
+                DRAW
+            };
+        }
+        if black_moves & (1 << pawn) != 0 {
+            return DRAW;
+        }
+    }
+
+    UNKNOWN
+}
+
+// Combines the results of all moves, UNKNOWN while some of them are undecided
+fn retrograde(results: &[u8], idx: usize) -> u8 {
+    let (white_to_move, white_king, black_king, pawn) = decode(idx);
+    let mut successors = Vec::with_capacity(10);
+
+    if white_to_move {
+        for to in squares(king_attacks(white_king)) {
+            if to != pawn && distance(to, black_king) > 1 {
+                successors.push(results[index(false, to, black_king, pawn)]);
+            }
+        }
+        let push = pawn + 8;
+        if push != white_king && push != black_king {
+            if pawn / 8 == 6 {
+                // Not classified as a win, so the new Queen is lost
+                successors.push(DRAW);
+            } else {
+                successors.push(results[index(false, white_king, black_king, push)]);
+                let double = pawn + 16;
+                if pawn / 8 == 1 && double != white_king && double != black_king {
+                    successors.push(results[index(false, white_king, black_king, double)]);
+                }
+            }
+        }
+    } else {
+        let targets = king_attacks(black_king) & !king_attacks(white_king) & !pawn_attacks(pawn);
+        for to in squares(targets) {
+            successors.push(results[index(true, white_king, to, pawn)]);
+        }
+    }
+
+    let (good, bad) = if white_to_move {
+        (WIN, DRAW)
+    } else {
+        (DRAW, WIN)
+    };
+    if successors.contains(&good) {
+        good
+    } else if successors
+        .iter()
+        .all(|&result| result == bad || result == INVALID)
+    {
+        bad
+    } else {
+        UNKNOWN
+    }
+}
+
+// Squares attacked by a White pawn
+fn pawn_attacks(pawn: u8) -> u64 {
+    let bit = 1u64 << pawn;
+    ((bit << 7) & !0x8080_8080_8080_8080) | ((bit << 9) & !0x0101_0101_0101_0101)
+}
+
+fn squares(mut bits: u64) -> impl Iterator<Item = u8> {
+    std::iter::from_fn(move || {
+        if bits == 0 {
+            return None;
+        }
+        let sq = bits.trailing_zeros() as u8;
+        bits &= bits - 1;
+        Some(sq)
+    })
+}
+
+fn distance(a: u8, b: u8) -> u8 {
+    (a % 8).abs_diff(b % 8).max((a / 8).abs_diff(b / 8))
+}
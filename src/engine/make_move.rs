@@ -7,12 +7,15 @@
 //!
 //! ## Core Responsibilities
 //!
-//! ### 1. Threat Detection ([`is_square_attacked`])
+//! ### 1. Threat Detection ([`is_square_attacked`], [`attackers_to`])
 //! Acts as the engine's "Security System." It determines if a specific square is
 //! currently under fire by the opponent.
-//! * **Efficiency:** It utilizes a high-speed King-distance heuristic (Chebyshev distance)
-//!   before falling back to standard move generation for other piece types.
-//! * **Logic:** $distance = \max(|sq_{rank} - king_{rank}|, |sq_{file} - king_{file}|)$.
+//! * **Attack Sets:** Every piece type has a helper returning the squares it attacks
+//!   as a bitboard. Sliding pieces take an explicit occupancy, so callers can
+//!   "lift" pieces off the board and uncover x-ray attackers behind them.
+//! * **Attackers:** [`attackers_to`] collects every piece of both colors hitting a
+//!   square; it is the building block for check detection and Static Exchange
+//!   Evaluation (see the `see` module).
 //!
 //! ### 2. State Mutation ([`apply_move`])
 //! The primary state machine. It surgically alters bitboards to reflect a move's
@@ -25,7 +28,7 @@
 //! * **Persistent State:** Updates castling rights whenever a King or Rook moves
 //!   (or is captured), ensuring rules are strictly followed throughout the game.
 //!
-//! ### 3. Legality Filtering ([`generate_legal_moves`])
+//! ### 3. Legality Filtering ([`generate_legal_moves`], [`is_legal`])
 //! The bridge between "maybe" and "yes." It converts pseudo-legal moves into
 //! strictly legal ones.
 //! * **The Trial-and-Error Method:** For every generated move, the engine
@@ -33,6 +36,9 @@
 //!   of check.
 //! * **The Filter:** If the King is safe, the move is validated and added to the
 //!   final move list.
+//! * **Single Moves:** Moves coming from outside the generator (user input, the
+//!   opening book, a hash table) are checked one by one with [`is_pseudo_legal`]
+//!   and [`is_legal`], without generating the whole move list.
 //!
 //! ---
 //!
@@ -53,6 +59,148 @@ use crate::board::{Bitboards, Color, PieceType};
 use crate::engine::board;
 use crate::movegen::Move;
 
+/// Knight jump offsets as (file, rank) deltas.
+const KNIGHT_DELTAS: [(i8, i8); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+
+/// King step offsets as (file, rank) deltas.
+const KING_DELTAS: [(i8, i8); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+/// Diagonal ray directions used by Bishops and Queens.
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (-1, 1), (1, -1), (-1, -1)];
+
+/// Orthogonal ray directions used by Rooks and Queens.
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+/// Returns the square reached by stepping `(df, dr)` from `sq`, or `None`
+/// if the step leaves the board.
+fn offset_square(sq: u8, df: i8, dr: i8) -> Option<u8> {
+    let file = (sq % 8) as i8 + df;
+    let rank = (sq / 8) as i8 + dr;
+    if (0..8).contains(&file) && (0..8).contains(&rank) {
+        Some((rank * 8 + file) as u8)
+    } else {
+        None
+    }
+}
+
+/// Builds the attack set of a "leaper" (Knight or King) from its step offsets.
+fn leaper_attacks(sq: u8, deltas: &[(i8, i8)]) -> u64 {
+    deltas
+        .iter()
+        .filter_map(|&(df, dr)| offset_square(sq, df, dr))
+        .fold(0u64, |acc, target| acc | (1u64 << target))
+}
+
+/// Casts rays from `sq` in every direction, stopping at (and including) the
+/// first occupied square.
+fn sliding_attacks(sq: u8, occupancy: u64, directions: &[(i8, i8)]) -> u64 {
+    let mut attacks = 0u64;
+    for &(df, dr) in directions {
+        let mut current = sq;
+        while let Some(next) = offset_square(current, df, dr) {
+            attacks |= 1u64 << next;
+            if occupancy & (1u64 << next) != 0 {
+                break;
+            }
+            current = next;
+        }
+    }
+    attacks
+}
+
+/// Returns the squares a pawn of `color` standing on `sq` attacks (captures only,
+/// pushes are not attacks).
+pub fn pawn_attacks(sq: u8, color: Color) -> u64 {
+    let dr = match color {
+        Color::White => 1,
+        Color::Black => -1,
+    };
+    leaper_attacks(sq, &[(-1, dr), (1, dr)])
+}
+
+/// Returns the squares a Knight on `sq` attacks.
+pub fn knight_attacks(sq: u8) -> u64 {
+    leaper_attacks(sq, &KNIGHT_DELTAS)
+}
+
+/// Returns the squares a King on `sq` attacks.
+pub fn king_attacks(sq: u8) -> u64 {
+    leaper_attacks(sq, &KING_DELTAS)
+}
+
+/// Returns the diagonal squares attacked from `sq` given the board `occupancy`.
+pub fn bishop_attacks(sq: u8, occupancy: u64) -> u64 {
+    sliding_attacks(sq, occupancy, &BISHOP_DIRECTIONS)
+}
+
+/// Returns the orthogonal squares attacked from `sq` given the board `occupancy`.
+pub fn rook_attacks(sq: u8, occupancy: u64) -> u64 {
+    sliding_attacks(sq, occupancy, &ROOK_DIRECTIONS)
+}
+
+/// Collects every piece, of **both** colors, that attacks a square.
+///
+/// # Arguments
+///
+/// * `board` - A reference to the current [`Bitboards`] state.
+/// * `sq` - The bit index (0-63) of the target square.
+/// * `occupancy` - The occupancy used for sliding pieces. Passing the real board
+///   occupancy gives the ordinary attackers; removing pieces from it reveals
+///   the x-ray attackers standing behind them.
+///
+/// # Returns
+///
+/// A bitboard of attacker squares. Only pieces still present in `occupancy`
+/// are reported, so pieces that were "lifted" during an exchange drop out.
+///
+/// # Implementation Details
+///
+/// Attacks are symmetric: a White pawn attacks `sq` exactly when a Black pawn
+/// standing on `sq` would attack the White pawn's square (and vice versa), and
+/// the same holds for every other piece type. The function therefore
+/// generates each attack pattern *from* the target square and intersects it
+/// with the matching piece bitboards.
+pub fn attackers_to(board: &Bitboards, sq: u8, occupancy: u64) -> u64 {
+    let white = &board.boards[Color::White as usize];
+    let black = &board.boards[Color::Black as usize];
+    let pawn = PieceType::Pawn as usize;
+    let knight = PieceType::Knight as usize;
+    let bishop = PieceType::Bishop as usize;
+    let rook = PieceType::Rook as usize;
+    let queen = PieceType::Queen as usize;
+    let king = PieceType::King as usize;
+
+    let diagonal = white[bishop] | white[queen] | black[bishop] | black[queen];
+    let orthogonal = white[rook] | white[queen] | black[rook] | black[queen];
+
+    let attackers = (pawn_attacks(sq, Color::Black) & white[pawn])
+        | (pawn_attacks(sq, Color::White) & black[pawn])
+        | (knight_attacks(sq) & (white[knight] | black[knight]))
+        | (king_attacks(sq) & (white[king] | black[king]))
+        | (bishop_attacks(sq, occupancy) & diagonal)
+        | (rook_attacks(sq, occupancy) & orthogonal);
+
+    attackers & occupancy
+}
+
 /// Determines if a specific square is under attack by a given side.
 ///
 /// This is a fundamental utility for move validation, particularly for:
@@ -74,56 +222,16 @@ use crate::movegen::Move;
 ///
 /// # Implementation Details
 ///
-/// 1. **Enemy Identification:** Swaps the input `color` to find the attacking side.
-/// 2. **Bitboard Iteration:** Loops through all enemy piece types (excluding the King).
-///    For each piece found via bit-scanning (`trailing_zeros`), it generates
-///    pseudo-legal moves and checks if the `to` square matches `sq`.
-/// 3. **King Heuristic:** Since using a full move generator for the King can be
-///    heavy, it uses a manual distance check. If the enemy King is exactly one
-///    square away (calculated via Chebyshev distance), the square is attacked.
+/// Delegates to [`attackers_to`] with the current board occupancy and keeps
+/// only the attackers belonging to the enemy side.
 pub fn is_square_attacked(board: &Bitboards, sq: u8, color: Color) -> bool {
     let enemy_color = match color {
         Color::White => Color::Black,
         Color::Black => Color::White,
     };
-    let piece_types: [PieceType; 5] = [
-        PieceType::Pawn,
-        PieceType::Knight,
-        PieceType::Bishop,
-        PieceType::Rook,
-        PieceType::Queen,
-    ];
-
-    for &piece_type in &piece_types {
-        let bitboard = board.boards[enemy_color as usize][piece_type as usize];
-        let mut pieces = bitboard;
-        while pieces != 0 {
-            let from = pieces.trailing_zeros() as u8;
-            pieces &= pieces - 1;
-            let moves = Move::generate_moves_for_piece(from, piece_type, enemy_color, board);
-            for mv in moves {
-                if mv.to == sq {
-                    return true;
-                }
-            }
-        }
-    }
+    let occupancy = Bitboards::occupancy(board);
 
-    // Checking kings seperatly to make castling logic work
-    let king_bitboard = board.boards[enemy_color as usize][PieceType::King as usize];
-    if king_bitboard != 0 {
-        let king_sq = king_bitboard.trailing_zeros() as u8;
-        let distance = (sq as i8 - king_sq as i8)
-            .abs()
-            .max((sq % 8) as i8 - (king_sq % 8) as i8)
-            .abs();
-        if distance == 1 {
-            // King attacks 1 square away
-            return true;
-        }
-    }
-
-    false
+    attackers_to(board, sq, occupancy) & Bitboards::color_occupancy(board, enemy_color) != 0
 }
 
 /// Validates that a square index falls within the legal 0-63 range.
@@ -181,14 +289,14 @@ pub fn is_check(board: &Bitboards, color: Color) -> bool {
             return false;
         }
         let king = king_squares[0];
-        return is_square_attacked(board, king, color);
+        is_square_attacked(board, king, color)
     } else {
         let king_squares = board::Bitboards::return_squares(board.boards[1][5]);
         if king_squares.is_empty() {
             return false;
         }
         let king = king_squares[0];
-        return is_square_attacked(board, king, color);
+        is_square_attacked(board, king, color)
     }
 }
 
@@ -222,6 +330,8 @@ pub fn apply_move(board: &mut Bitboards, mv: &Move, color: Color) {
     if !is_valid_square(mv.from) || !is_valid_square(mv.to) {
         panic!("Invalid move: {:?}", mv);
     }
+    #[cfg(feature = "nnue")]
+    let before = board.boards;
     // opponents color
     let enemy_color = match color {
         Color::White => Color::Black,
@@ -230,19 +340,18 @@ pub fn apply_move(board: &mut Bitboards, mv: &Move, color: Color) {
 
     // Handle en passant capture
     let mut is_en_passant = false;
-    if mv.piece == PieceType::Pawn {
-        if let Some(ep_sq) = board.en_passant_square {
-            if mv.to == ep_sq {
-                is_en_passant = true;
-                let captured_pawn_sq = if color == Color::White {
-                    ep_sq - 8
-                } else {
-                    ep_sq + 8
-                };
-                let captured_mask = 1u64 << captured_pawn_sq;
-                board.boards[enemy_color as usize][PieceType::Pawn as usize] &= !captured_mask;
-            }
-        }
+    if mv.piece == PieceType::Pawn
+        && let Some(ep_sq) = board.en_passant_square
+        && mv.to == ep_sq
+    {
+        is_en_passant = true;
+        let captured_pawn_sq = if color == Color::White {
+            ep_sq - 8
+        } else {
+            ep_sq + 8
+        };
+        let captured_mask = 1u64 << captured_pawn_sq;
+        board.boards[enemy_color as usize][PieceType::Pawn as usize] &= !captured_mask;
     }
 
     // Reset en passant square
@@ -330,6 +439,9 @@ pub fn apply_move(board: &mut Bitboards, mv: &Move, color: Color) {
     if mv.from == 63 || mv.to == 63 {
         board.black_kingside = false;
     }
+
+    #[cfg(feature = "nnue")]
+    crate::engine::nnue::update(board, &before);
 }
 
 /// Filters pseudo-legal moves to return a vector of strictly legal moves.
@@ -369,3 +481,152 @@ pub fn generate_legal_moves(board: &Bitboards, color: Color) -> Vec<Move> {
 
     legal_moves
 }
+
+/// Checks whether all squares in `mask` are empty.
+fn squares_empty(occupancy: u64, mask: u64) -> bool {
+    occupancy & mask == 0
+}
+
+/// Validates a castling move against rights, empty squares and attacked squares.
+///
+/// Mirrors the rules enforced by the move generator: the King may not castle
+/// out of, through, or into check, and every square between King and Rook must
+/// be empty.
+fn is_castling_pseudo_legal(board: &Bitboards, mv: &Move, color: Color) -> bool {
+    let occupancy = Bitboards::occupancy(board);
+    let (right, between, king_path): (bool, u64, [u8; 3]) = match (color, mv.from, mv.to) {
+        (Color::White, 4, 6) => (board.white_kingside, (1 << 5) | (1 << 6), [4, 5, 6]),
+        (Color::White, 4, 2) => (
+            board.white_queenside,
+            (1 << 1) | (1 << 2) | (1 << 3),
+            [4, 3, 2],
+        ),
+        (Color::Black, 60, 62) => (board.black_kingside, (1 << 61) | (1 << 62), [60, 61, 62]),
+        (Color::Black, 60, 58) => (
+            board.black_queenside,
+            (1 << 57) | (1 << 58) | (1 << 59),
+            [60, 59, 58],
+        ),
+        _ => return false,
+    };
+
+    right
+        && squares_empty(occupancy, between)
+        && king_path
+            .iter()
+            .all(|&sq| !is_square_attacked(board, sq, color))
+}
+
+/// Checks whether a move is pseudo-legal in the given position.
+///
+/// A pseudo-legal move follows the movement rules of its piece but may still
+/// leave the own King in check. Unlike [`generate_legal_moves`], this check
+/// looks at a **single** move, which makes it cheap enough to validate moves
+/// from external sources (the user, the opening book, UCI or a hash table)
+/// before they ever reach [`apply_move`].
+///
+/// # Arguments
+///
+/// * `board` - A reference to the current [`Bitboards`] state.
+/// * `mv` - The move to validate.
+/// * `color` - The side making the move.
+///
+/// # Returns
+///
+/// Returns `true` only if the move is exactly what the move generator would
+/// produce, including its `is_capture`, `is_castling` and `promotion_rights`
+/// flags. A move with the right squares but wrong flags is rejected, because
+/// [`apply_move`] trusts those flags.
+pub fn is_pseudo_legal(board: &Bitboards, mv: &Move, color: Color) -> bool {
+    if !is_valid_square(mv.from) || !is_valid_square(mv.to) || mv.from == mv.to {
+        return false;
+    }
+
+    let enemy_color = match color {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    };
+    let from_mask = 1u64 << mv.from;
+    let to_mask = 1u64 << mv.to;
+    let own_pieces = Bitboards::color_occupancy(board, color);
+    let enemy_pieces = Bitboards::color_occupancy(board, enemy_color);
+    let occupancy = own_pieces | enemy_pieces;
+
+    // The piece must be ours and of the stated type, and cannot land on a friend
+    if board.boards[color as usize][mv.piece as usize] & from_mask == 0 {
+        return false;
+    }
+    if own_pieces & to_mask != 0 {
+        return false;
+    }
+
+    // Flags must describe the move the same way the generator does
+    let en_passant = mv.piece == PieceType::Pawn && board.en_passant_square == Some(mv.to);
+    if mv.is_capture != (enemy_pieces & to_mask != 0 || en_passant) {
+        return false;
+    }
+    let promotion_rank = match color {
+        Color::White => 6,
+        Color::Black => 1,
+    };
+    let promotes = mv.piece == PieceType::Pawn && mv.from / 8 == promotion_rank;
+    if mv.promotion_rights != promotes {
+        return false;
+    }
+    if mv.is_castling {
+        return mv.piece == PieceType::King && is_castling_pseudo_legal(board, mv, color);
+    }
+
+    let targets = match mv.piece {
+        PieceType::Pawn => {
+            let (push, start_rank) = match color {
+                Color::White => (8i8, 1),
+                Color::Black => (-8i8, 6),
+            };
+            let single = mv.from as i8 + push;
+            let mut targets = 0u64;
+            if (0..64).contains(&single) && squares_empty(occupancy, 1u64 << single) {
+                targets |= 1u64 << single;
+                let double = single + push;
+                if mv.from / 8 == start_rank && squares_empty(occupancy, 1u64 << double) {
+                    targets |= 1u64 << double;
+                }
+            }
+            let mut capturable = enemy_pieces;
+            if let Some(ep_sq) = board.en_passant_square {
+                capturable |= 1u64 << ep_sq;
+            }
+            targets | (pawn_attacks(mv.from, color) & capturable)
+        }
+        PieceType::Knight => knight_attacks(mv.from),
+        PieceType::Bishop => bishop_attacks(mv.from, occupancy),
+        PieceType::Rook => rook_attacks(mv.from, occupancy),
+        PieceType::Queen => bishop_attacks(mv.from, occupancy) | rook_attacks(mv.from, occupancy),
+        PieceType::King => king_attacks(mv.from),
+    };
+
+    targets & to_mask != 0
+}
+
+/// Checks whether a move is fully legal in the given position.
+///
+/// Combines [`is_pseudo_legal`] with the same "apply and look for check" test
+/// used by [`generate_legal_moves`].
+///
+/// # Arguments
+///
+/// * `board` - A reference to the current [`Bitboards`] state.
+/// * `mv` - The move to validate.
+/// * `color` - The side making the move.
+///
+/// # Returns
+///
+/// Returns `true` if the move can be played without leaving the own King in check.
+pub fn is_legal(board: &Bitboards, mv: &Move, color: Color) -> bool {
+    if !is_pseudo_legal(board, mv, color) {
+        return false;
+    }
+    let mut clone = board.clone();
+    apply_move(&mut clone, mv, color);
+    !is_check(&clone, color)
+}
@@ -1,7 +1,22 @@
 pub(crate) mod board;
+pub(crate) mod datagen;
+pub(crate) mod dtm;
+pub(crate) mod endgame;
 pub(crate) mod evaluation;
 pub(crate) mod game_over;
+pub(crate) mod king_safety;
+pub(crate) mod kpk;
 pub(crate) mod make_move;
 pub(crate) mod movegen;
+#[cfg(feature = "nnue")]
+pub(crate) mod nnue;
+pub(crate) mod params;
 pub(crate) mod parse_fen;
+pub(crate) mod pawns;
+pub(crate) mod pgn;
+pub(crate) mod san;
 pub(crate) mod search;
+pub(crate) mod see;
+pub(crate) mod syzygy;
+pub(crate) mod tune;
+pub(crate) mod zobrist;
@@ -15,6 +15,7 @@ use crate::board::{
     Bitboards
 };
 use crate::make_move;
+use crate::engine::see;
 
 /// Bitmask representing all squares on the A-file.
 /// Used to prevent pieces from "wrapping around" to the H-file when shifting left.
@@ -94,7 +95,7 @@ impl Move {
     /// # Returns
     ///
     /// Returns an [`i32`] representing the value of the piece in **centipawns**
-    fn get_piece_value(piece: PieceType) -> i32 {
+    pub(crate) fn get_piece_value(piece: PieceType) -> i32 {
         match piece {
             PieceType::Pawn => 100,
             PieceType::Knight => 300,
@@ -155,6 +156,8 @@ impl Move {
     /// # Heuristics Used
     ///
     /// * **MVV-LVA:** Prioritizes "Most Valuable Victim - Least Valuable Attacker" captures.
+    /// * **SEE:** Captures that lose material on the exchange (see [`see::see_value`]) are
+    ///   pushed behind the quiet moves instead of being tried first.
     /// * **Promotion:** Prioritizes moves that result in a piece promotion.
     /// * **Center Control:** Rewards moves that target the central squares (d4, d5, e4, e5).
     pub fn generate_moves_for_side(color: Color, boards: &Bitboards) -> Vec<Move> {
@@ -180,14 +183,19 @@ impl Move {
             }
         }
 
-        moves.sort_by_key(|m| {
+        moves.sort_by_cached_key(|m| {
                 let mut score = 0;
                 
                 if m.is_capture {
-                    // Try to figure out what piece was captured at m.to
-                    let victim_value = Self::get_captured_piece_value(boards, m.to, oposite_color);
-                    let attacker_value = Self::get_piece_value(m.piece);
-                    score += victim_value * 10 - attacker_value; // Prefer QxP over PxQ
+                    let exchange = see::see_value(boards, m, color);
+                    if exchange >= 0 {
+                        // Try to figure out what piece was captured at m.to
+                        let victim_value = Self::get_captured_piece_value(boards, m.to, oposite_color);
+                        let attacker_value = Self::get_piece_value(m.piece);
+                        score += victim_value * 10 - attacker_value; // Prefer QxP over PxQ
+                    } else {
+                        score += exchange; // Losing captures go after quiet moves
+                    }
                 }
                 
                 if m.promotion_rights {
@@ -518,7 +526,6 @@ impl Move {
 
         let pawn = 1u64 << sq;
         let mut moves = 0u64;
-        let promotion: bool;
 
 
         if color == Color::White {
@@ -527,7 +534,7 @@ impl Move {
                 moves |= pawn << 8;  // FIXED: Left shift to move UP
                 // FIXED: White pawns start on rank 2 (squares 8-15)
                 if !Self::is_square_occupied(all_pieces_combined, sq, 16)
-                    && sq >= 8 && sq <= 15 {  // FIXED: Rank 2, not rank 8
+                    && (8..=15).contains(&sq) {  // FIXED: Rank 2, not rank 8
                     moves |= pawn << 16;  // FIXED: Left shift for two squares up
                 }
             }
@@ -558,32 +565,22 @@ impl Move {
 
 
 
-        if let Some(ep_square) = board.en_passant_square {
-            let sq_i = sq as i16;
-            let ep_i = ep_square as i16;
-
-            if (ep_i - sq_i).abs() == 1 {
-                let ep_capture_square = if color == Color::White {
-                    ep_square - 8
-                } else {
-                    ep_square + 8
-                };
-                if ep_capture_square < 64 {
-                    moves |= 1u64 << ep_capture_square;
-                }
+        // The en passant square is the one the enemy pawn skipped over,
+        // so the capture lands there, diagonally in front of our pawn
+        if let Some(ep_square) = board.en_passant_square
+            && make_move::pawn_attacks(sq, color) & (1u64 << ep_square) != 0 {
+                moves |= 1u64 << ep_square;
             }
-        }
 
 
 
-        if  (color == Color::White && sq >= 48 && sq <= 55) ||
-            (color == Color::Black && sq >= 8 && sq <= 15) {
-                promotion = true;
-            } else {
-                promotion = false;
-            }
+        let promotion = (color == Color::White && (48..=55).contains(&sq))
+            || (color == Color::Black && (8..=15).contains(&sq));
+
+        // An en passant capture lands on an empty square, but it still takes a pawn
+        let ep_target = board.en_passant_square.map_or(0, |ep| 1u64 << ep);
 
-        Self::moves_from_bitboard(sq, PieceType::Pawn, moves, promotion, false, opponent_pieces)
+        Self::moves_from_bitboard(sq, PieceType::Pawn, moves, promotion, false, opponent_pieces | ep_target)
     }
 
 
@@ -632,7 +629,7 @@ impl Move {
         // EAST
 
         let mut next_sq = sq + 1;
-        while next_sq % 8 != 0 {
+        while !next_sq.is_multiple_of(8) {
             if Self::is_square_occupied(own_pieces, next_sq, 0) {
                 break;
             }
@@ -645,7 +642,7 @@ impl Move {
 
         // WEST
         let mut next_sq = sq.wrapping_sub(1);
-        while sq % 8 != 0 && next_sq % 8 != 7 {
+        while !sq.is_multiple_of(8) && next_sq % 8 != 7 {
             if Self::is_square_occupied(own_pieces, next_sq, 0) {
                 break;
             }
@@ -708,7 +705,7 @@ impl Move {
             if Self::is_square_occupied(opponent_pieces, pos, 0) {
                 break;
             }
-            if pos % 8 == 0 { break; } // prevent edge wrapping
+            if pos.is_multiple_of(8) { break; } // prevent edge wrapping
         }
 
         // Southeast (-7)
@@ -737,7 +734,7 @@ impl Move {
             if Self::is_square_occupied(opponent_pieces, pos, 0) {
                 break;
             }
-            if pos % 8 == 0 { break; }
+            if pos.is_multiple_of(8) { break; }
             if pos < 9 { break; }
         }
 
@@ -786,11 +783,68 @@ impl Move {
     }
 
 
+    /// Builds a [`Move`] from a UCI-style string (e.g., "e2e4") using the board to fill
+    /// in the metadata (moving piece, capture, castling and promotion flags).
+    ///
+    /// The result is **not** validated: it describes what the string would mean in this
+    /// position, and must be checked with [`make_move::is_pseudo_legal`] or
+    /// [`make_move::is_legal`] before being applied.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - A string slice representing the move in UCI format (e.g., "e2e4").
+    /// * `board` - The current game state bitboards.
+    /// * `color` - The side making the move.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if the input is malformed or there is no piece of `color` on the
+    /// starting square.
+    ///
+    /// # Note on Promotions
+    ///
+    /// The engine always promotes to a Queen, so both "a7a8" and "a7a8q" are accepted,
+    /// while under-promotions (e.g., "a7a8n") are rejected instead of silently turning
+    /// into a Queen.
+    pub fn from_uci(input: &str, board: &Bitboards, color: Color) -> Option<Move> {
+        let bytes = input.as_bytes();
+        let is_square = |file: u8, rank: u8| (b'a'..=b'h').contains(&file) && (b'1'..=b'8').contains(&rank);
+        if !(bytes.len() == 4 || bytes.len() == 5)
+            || !is_square(bytes[0], bytes[1])
+            || !is_square(bytes[2], bytes[3])
+            || (bytes.len() == 5 && bytes[4] != b'q') {
+            return None;
+        }
+
+        let (from_uci, to_uci) = input[..4].split_at(2);
+        let from = Self::move_coordinates_to_bit(from_uci);
+        let to = Self::move_coordinates_to_bit(to_uci);
+
+        let piece = match Bitboards::piece_at(board, from) {
+            Some((owner, piece)) if owner == color => piece,
+            _ => return None,
+        };
+        let is_capture = matches!(Bitboards::piece_at(board, to), Some((owner, _)) if owner != color)
+            || (piece == PieceType::Pawn && board.en_passant_square == Some(to));
+        let is_castling = piece == PieceType::King && from.abs_diff(to) == 2;
+        let promotion_rights = piece == PieceType::Pawn && (to / 8 == 0 || to / 8 == 7);
+
+        Some(Move {
+            from,
+            to,
+            piece,
+            promotion_rights,
+            is_castling,
+            is_capture,
+        })
+    }
+
+
     /// Parses a UCI-style string (e.g., "e2e4") and returns the corresponding legal Move.
     ///
     /// This function acts as a safety layer: it converts coordinate strings into internal 
-    /// bit indices, generates all legal moves for the side, and verifies that the 
-    /// requested move is actually valid.
+    /// bit indices with [`Self::from_uci`] and verifies that the requested move is actually
+    /// legal with [`make_move::is_legal`], without generating the full move list.
     ///
     /// # Arguments
     ///
@@ -802,22 +856,13 @@ impl Move {
     ///
     /// Returns `Some(Move)` if the input corresponds to a valid legal move. 
     /// Returns `None` if the move is illegal or the input is malformed.
-    ///
-    /// # Note on Promotions
-    ///
-    /// Currently, this parser handles standard 4-character moves. Support for 
-    /// 5-character promotion strings (e.g., "a7a8q") is a planned UCI enhancement.
     pub fn uci_user_parser(
         input: &str,
         board: &Bitboards,
         color: Color
     ) -> Option<Move> {
-        let (from_uci, to_uci) = input.split_at(2);
-        let from = Self::move_coordinates_to_bit(from_uci);
-        let to = Self::move_coordinates_to_bit(to_uci);
-
-        let moves = Self::generate_moves_for_side(color, board);
-        moves.into_iter().find(|m| m.from == from && m.to == to)
+        let mv = Self::from_uci(input, board, color)?;
+        make_move::is_legal(board, &mv, color).then_some(mv)
     }
 
 
@@ -842,8 +887,8 @@ impl Move {
     /// * **ASCII Offset:** Uses byte literals (`b'a'`, `b'1'`) to perform efficient 
     ///   character arithmetic.
     fn bit_to_uci(bit: u8) -> String {
-        let file = (bit % 8) as u8;       // 0..7 → a..h
-        let rank = (bit / 8) as u8;       // 0..7 → 1..8
+        let file = bit % 8;       // 0..7 → a..h
+        let rank = bit / 8;       // 0..7 → 1..8
         let file_char = (b'a' + file) as char;
         let rank_char = (b'1' + rank) as char;
         format!("{}{}", file_char, rank_char)
@@ -928,3 +973,34 @@ impl Move {
         moves_vec
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn en_passant_lands_on_the_skipped_square() {
+        // White pawn on e5, Black has just played d7-d5
+        let mut board = Bitboards::new();
+        Bitboards::add_piece(&mut board, Color::White, PieceType::King, 4);
+        Bitboards::add_piece(&mut board, Color::Black, PieceType::King, 60);
+        Bitboards::add_piece(&mut board, Color::White, PieceType::Pawn, 36);
+        Bitboards::add_piece(&mut board, Color::Black, PieceType::Pawn, 35);
+        board.en_passant_square = Some(43);
+
+        let moves = Move::generate_moves_for_piece(36, PieceType::Pawn, Color::White, &board);
+        let ep = moves
+            .iter()
+            .find(|mv| Move::move_to_uci(mv) == "e5d6")
+            .expect("e5d6 should be generated");
+        assert!(ep.is_capture);
+        assert!(make_move::is_legal(&board, ep, Color::White));
+
+        make_move::apply_move(&mut board, ep, Color::White);
+        assert_eq!(board.boards[Color::Black as usize][PieceType::Pawn as usize], 0);
+        assert_eq!(
+            Bitboards::piece_at(&board, 43),
+            Some((Color::White, PieceType::Pawn))
+        );
+    }
+}
@@ -0,0 +1,246 @@
+//! # Module: `nnue`
+//!
+//! An **Efficiently Updatable Neural Network** evaluator, compiled only with the
+//! `nnue` cargo feature and used only when a network is loaded with `--nnue <FILE>`.
+//! Without both, the handcrafted [`evaluation`](crate::engine::evaluation::evaluation)
+//! stays in charge.
+//!
+//! ## Architecture: `(768 → HIDDEN) x 2 → 1`
+//!
+//! * **Inputs:** one feature per (color, piece type, square): 2 x 6 x 64 = 768.
+//!   Features are seen from a *perspective*: "own" and "enemy" pieces, with the
+//!   board flipped vertically for Black, so both sides share one set of weights.
+//! * **Accumulators:** the hidden layer is computed once per perspective. As only
+//!   a few features change per move (two for a quiet move, three for a capture,
+//!   four for castling), the accumulators are **updated incrementally**: the
+//!   weight columns of removed pieces are subtracted and those of added pieces
+//!   added, instead of recomputing 768 x `HIDDEN` products.
+//! * **Output:** the side to move's accumulator followed by the opponent's, passed
+//!   through a clipped ReLU and reduced to one number by the output layer.
+//!
+//! ## Copy-Make Integration
+//!
+//! The engine makes moves on clones of [`Bitboards`], so the accumulator lives
+//! inside the board and is copied with it. [`apply_move`](crate::engine::make_move::apply_move)
+//! compares the piece bitboards before and after the move and hands the difference
+//! to [`update`]; there is no unmake, the parent board still holds the old state.
+//! A board whose accumulator was never computed (e.g. fresh from a FEN) is
+//! refreshed from scratch by [`refresh`] or, lazily, on evaluation.
+//!
+//! ## Weights File
+//!
+//! Raw little-endian `i16` values, in order: feature weights (`768` rows of
+//! `HIDDEN`), feature biases (`HIDDEN`), output weights (`2 * HIDDEN`), output
+//! bias (`1`). Trailing padding is ignored. Feature weights and biases are
+//! quantized by `QA`, output weights by `QB` and the output bias by `QA * QB`,
+//! as produced by common trainers.
+
+use crate::board::{Bitboards, Color, PieceType};
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Width of the hidden layer (per perspective).
+pub const HIDDEN: usize = 256;
+
+const INPUTS: usize = 768;
+const QA: i32 = 255;
+const QB: i32 = 64;
+// Converts the network output into centipawns
+const SCALE: i32 = 400;
+
+struct Network {
+    feature_weights: Vec<i16>,
+    feature_bias: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i16,
+}
+
+static NETWORK: OnceLock<Network> = OnceLock::new();
+
+/// Hidden layer values for both perspectives, indexed by [`Color`].
+#[derive(Clone)]
+pub struct Accumulator {
+    values: [[i16; HIDDEN]; 2],
+    /// `false` until the values have been computed for the current position.
+    computed: bool,
+}
+
+impl Default for Accumulator {
+    fn default() -> Self {
+        Self {
+            values: [[0; HIDDEN]; 2],
+            computed: false,
+        }
+    }
+}
+
+impl fmt::Debug for Accumulator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Accumulator")
+            .field("computed", &self.computed)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Loads the network used for evaluation. Can only be done once per process.
+pub fn load(path: impl AsRef<Path>) -> io::Result<()> {
+    let bytes = std::fs::read(path)?;
+    let values: Vec<i16> = bytes
+        .chunks_exact(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+
+    let expected = INPUTS * HIDDEN + HIDDEN + 2 * HIDDEN + 1;
+    if values.len() < expected {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "network has {} weights, expected {} for a hidden layer of {}",
+                values.len(),
+                expected,
+                HIDDEN
+            ),
+        ));
+    }
+
+    let (feature_weights, rest) = values.split_at(INPUTS * HIDDEN);
+    let (feature_bias, rest) = rest.split_at(HIDDEN);
+    let (output_weights, rest) = rest.split_at(2 * HIDDEN);
+    let network = Network {
+        feature_weights: feature_weights.to_vec(),
+        feature_bias: feature_bias.to_vec(),
+        output_weights: output_weights.to_vec(),
+        output_bias: rest[0],
+    };
+
+    NETWORK
+        .set(network)
+        .map_err(|_| io::Error::other("a network is already loaded"))
+}
+
+/// Evaluates the position with the loaded network.
+///
+/// # Returns
+///
+/// The score in centipawns from the point of view of `color`, or `None` if no
+/// network is loaded.
+pub fn evaluate(board: &Bitboards, color: Color) -> Option<i32> {
+    let network = NETWORK.get()?;
+    let fresh;
+    let accumulator = if board.accumulator.computed {
+        &board.accumulator
+    } else {
+        fresh = compute(network, board);
+        &fresh
+    };
+
+    let (own, enemy) = match color {
+        Color::White => (&accumulator.values[0], &accumulator.values[1]),
+        Color::Black => (&accumulator.values[1], &accumulator.values[0]),
+    };
+    let (own_weights, enemy_weights) = network.output_weights.split_at(HIDDEN);
+
+    let mut output = 0i32;
+    for (&value, &weight) in own.iter().zip(own_weights) {
+        output += (value as i32).clamp(0, QA) * weight as i32;
+    }
+    for (&value, &weight) in enemy.iter().zip(enemy_weights) {
+        output += (value as i32).clamp(0, QA) * weight as i32;
+    }
+    output += network.output_bias as i32;
+
+    Some((output as i64 * SCALE as i64 / (QA * QB) as i64) as i32)
+}
+
+/// Recomputes the accumulator of `board` from scratch.
+///
+/// Called at the root of a search, so every position below it is updated
+/// incrementally. Does nothing if no network is loaded.
+pub fn refresh(board: &mut Bitboards) {
+    if let Some(network) = NETWORK.get() {
+        board.accumulator = compute(network, board);
+    }
+}
+
+/// Brings the accumulator of `board` up to date after a move.
+///
+/// # Arguments
+///
+/// * `board` - The board *after* the move.
+/// * `before` - The piece bitboards *before* the move.
+pub fn update(board: &mut Bitboards, before: &[[u64; 6]; 2]) {
+    let Some(network) = NETWORK.get() else {
+        return;
+    };
+    if !board.accumulator.computed {
+        return;
+    }
+
+    for color in [Color::White, Color::Black] {
+        for piece in PieceType::pieces() {
+            let old = before[color as usize][piece as usize];
+            let new = board.boards[color as usize][piece as usize];
+            for sq in Bitboards::return_squares(old & !new) {
+                for perspective in [Color::White, Color::Black] {
+                    let column = weight_column(network, perspective, color, piece, sq);
+                    let values = &mut board.accumulator.values[perspective as usize];
+                    values
+                        .iter_mut()
+                        .zip(column)
+                        .for_each(|(v, w)| *v = v.wrapping_sub(*w));
+                }
+            }
+            for sq in Bitboards::return_squares(new & !old) {
+                for perspective in [Color::White, Color::Black] {
+                    let column = weight_column(network, perspective, color, piece, sq);
+                    let values = &mut board.accumulator.values[perspective as usize];
+                    values
+                        .iter_mut()
+                        .zip(column)
+                        .for_each(|(v, w)| *v = v.wrapping_add(*w));
+                }
+            }
+        }
+    }
+}
+
+fn compute(network: &Network, board: &Bitboards) -> Accumulator {
+    let mut accumulator = Accumulator {
+        values: [[0; HIDDEN]; 2],
+        computed: true,
+    };
+    for perspective in [Color::White, Color::Black] {
+        let values = &mut accumulator.values[perspective as usize];
+        values.copy_from_slice(&network.feature_bias);
+        for color in [Color::White, Color::Black] {
+            for piece in PieceType::pieces() {
+                for sq in Bitboards::return_squares(board.boards[color as usize][piece as usize]) {
+                    let column = weight_column(network, perspective, color, piece, sq);
+                    values
+                        .iter_mut()
+                        .zip(column)
+                        .for_each(|(v, w)| *v = v.wrapping_add(*w));
+                }
+            }
+        }
+    }
+    accumulator
+}
+
+// Hidden layer weights of one input feature, seen from `perspective`
+fn weight_column(
+    network: &Network,
+    perspective: Color,
+    color: Color,
+    piece: PieceType,
+    sq: u8,
+) -> &[i16] {
+    let (side, square) = match perspective {
+        Color::White => (color as usize, sq as usize),
+        Color::Black => (1 - color as usize, (sq ^ 56) as usize),
+    };
+    let feature = side * 384 + piece as usize * 64 + square;
+    &network.feature_weights[feature * HIDDEN..(feature + 1) * HIDDEN]
+}
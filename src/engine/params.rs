@@ -0,0 +1,486 @@
+//! # Module: `params`
+//!
+//! Every weight used by the evaluation, gathered in one [`EvalParams`] value so it
+//! can be changed **without recompiling**.
+//!
+//! ## Loading and Saving
+//!
+//! Parameters are stored as JSON through `serde`. [`EvalParams::default`] holds the
+//! built-in values; a file only needs the fields it changes, everything missing
+//! falls back to the default:
+//!
+//! ```json
+//! { "material": [{ "mg": 100, "eg": 130 }, ...], "pieces": { "bishop_pair": { "mg": 40, "eg": 60 } } }
+//! ```
+//!
+//! ## Active Parameters
+//!
+//! The search calls [`evaluation`](crate::engine::evaluation::evaluation), which reads
+//! the process-wide **active** parameters ([`active`]). They start as the defaults
+//! and are replaced with [`set_active`], e.g. when `--eval-params` is given.
+//! Tools that compare several parameter sets (tuning) call
+//! [`evaluate_with`](crate::engine::evaluation::evaluate_with) directly instead.
+//!
+//! ## Piece-Square Tables
+//!
+//! Tables are stored once, from White's point of view, as eight ranks starting with
+//! rank 8 (the way a board is printed). Black's tables are never written down: they
+//! are derived by mirroring the rank, see [`PieceSquareTable::score`].
+
+use crate::board::{Color, PieceType};
+use crate::engine::evaluation::Score;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, LazyLock, RwLock};
+
+/// All evaluation weights.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EvalParams {
+    /// Piece values: pawn, knight, bishop, rook, queen, king.
+    pub material: [Score; 6],
+    /// Multiplier giving the piece-square tables a proper weight in the final eval.
+    pub pst_weight: i32,
+    pub pst: PieceSquareTables,
+    /// Bonus per pseudo-legal move: pawn, knight, bishop, rook, queen, king.
+    pub mobility: [Score; 6],
+    pub development: DevelopmentParams,
+    pub pieces: PieceParams,
+    pub pawns: PawnParams,
+    pub king_safety: KingSafetyParams,
+}
+
+/// A middlegame and an endgame table for one piece type.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PieceSquareTable {
+    pub mg: [[i32; 8]; 8],
+    pub eg: [[i32; 8]; 8],
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PieceSquareTables {
+    pub pawn: PieceSquareTable,
+    pub knight: PieceSquareTable,
+    pub bishop: PieceSquareTable,
+    pub rook: PieceSquareTable,
+    pub queen: PieceSquareTable,
+    pub king: PieceSquareTable,
+}
+
+/// Opening heuristics.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DevelopmentParams {
+    /// Pawn on d4, e4, d5 or e5.
+    pub center_pawn: Score,
+    /// Pawn on the 4th or 5th rank.
+    pub advanced_pawn: Score,
+    /// g- or h-pawn pushed in the own half of the board.
+    pub early_flank_pawn: Score,
+    pub knight_on_rim: Score,
+    pub knight_off_rim: Score,
+    /// King on g1/g8 or c1/c8.
+    pub castled_kingside: Score,
+    pub castled_queenside: Score,
+}
+
+/// Piece coordination terms.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PieceParams {
+    pub bishop_pair: Score,
+    /// Rooks and Queens on files without any pawns (open) or without friendly pawns (semi-open).
+    pub rook_open_file: Score,
+    pub rook_semi_open_file: Score,
+    pub queen_open_file: Score,
+    pub queen_semi_open_file: Score,
+    /// Heavy pieces on the 7th rank, cutting off the King or attacking pawns.
+    pub rook_on_seventh: Score,
+    pub queen_on_seventh: Score,
+    /// Minor pieces on the 4th-6th rank, defended by a pawn and out of reach of enemy pawns.
+    pub knight_outpost: Score,
+    pub bishop_outpost: Score,
+    /// Per friendly pawn on the bishop's square color, counted twice for blocked central pawns.
+    pub bad_bishop_pawn: Score,
+    /// Bishop on a7/h7 shut in by a pawn on b6/g6.
+    pub trapped_bishop: Score,
+    /// Rook locked in the corner by its own uncastled King.
+    pub trapped_rook: Score,
+}
+
+/// Pawn structure terms, see the `pawns` module.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PawnParams {
+    pub doubled: Score,
+    pub isolated: Score,
+    pub backward: Score,
+    /// Indexed by relative rank (0 = own back rank, 7 = promotion rank).
+    pub connected: [Score; 8],
+    pub passed: [Score; 8],
+    /// Endgame bonus for a passed pawn whose way to the promotion square is empty.
+    pub passed_free_path: [i32; 8],
+    /// Endgame weights for King distances to the square in front of a passed pawn,
+    /// multiplied by how far the pawn has advanced.
+    pub passed_enemy_king_distance: i32,
+    pub passed_own_king_distance: i32,
+}
+
+/// King safety terms, see the `king_safety` module.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KingSafetyParams {
+    /// Shield bonus by the distance (in ranks) between the King and its nearest
+    /// friendly pawn in front of it on a file.
+    pub shield: [i32; 4],
+    pub shield_missing: i32,
+    /// Storm penalty by the distance between the King and the nearest enemy pawn
+    /// in front of it on a file. At distance 1 the pawn is usually blocked already.
+    pub storm: [i32; 5],
+    pub storm_blocked: i32,
+    pub semi_open_file: i32,
+    pub open_file: i32,
+    /// Attack units per King zone square hit: pawn, knight, bishop, rook, queen, king.
+    pub attack_weights: [i32; 6],
+    /// Danger in centipawns by number of attack units. Units beyond the end of the
+    /// table use the last entry.
+    pub safety_table: Vec<i32>,
+}
+
+impl PieceSquareTable {
+    /// Looks up the value of a piece of `color` standing on `sq` (a1 = 0).
+    ///
+    /// Tables start with rank 8, so White flips the rank of the square while
+    /// Black, looking at the board from the other side, reads it as is.
+    pub fn score(&self, sq: u8, color: Color) -> Score {
+        let row = match color {
+            Color::White => 7 - (sq / 8) as usize,
+            Color::Black => (sq / 8) as usize,
+        };
+        let file = (sq % 8) as usize;
+        Score::new(self.mg[row][file], self.eg[row][file])
+    }
+}
+
+impl PieceSquareTables {
+    pub fn get(&self, piece: PieceType) -> &PieceSquareTable {
+        match piece {
+            PieceType::Pawn => &self.pawn,
+            PieceType::Knight => &self.knight,
+            PieceType::Bishop => &self.bishop,
+            PieceType::Rook => &self.rook,
+            PieceType::Queen => &self.queen,
+            PieceType::King => &self.king,
+        }
+    }
+}
+
+impl EvalParams {
+    /// Reads parameters from a JSON file. Missing fields keep their default value.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Writes all parameters to a JSON file.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let text = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, text)
+    }
+}
+
+static ACTIVE: LazyLock<RwLock<Arc<EvalParams>>> =
+    LazyLock::new(|| RwLock::new(Arc::new(EvalParams::default())));
+
+/// Returns the parameters used by [`evaluation`](crate::engine::evaluation::evaluation).
+pub fn active() -> Arc<EvalParams> {
+    ACTIVE.read().unwrap().clone()
+}
+
+/// Replaces the parameters used by [`evaluation`](crate::engine::evaluation::evaluation).
+pub fn set_active(params: EvalParams) {
+    *ACTIVE.write().unwrap() = Arc::new(params);
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        Self {
+            // pawn, knight, bishop, rook, queen, king
+            material: [
+                Score::new(100, 120),
+                Score::new(320, 300),
+                Score::new(330, 320),
+                Score::new(500, 530),
+                Score::new(900, 950),
+                Score::new(0, 0),
+            ],
+            pst_weight: 5,
+            pst: PieceSquareTables::default(),
+            // pawn, knight, bishop, rook, queen, king
+            mobility: [
+                Score::new(1, 2),
+                Score::new(4, 4),
+                Score::new(4, 5),
+                Score::new(5, 7),
+                Score::new(9, 6),
+                Score::new(0, 0),
+            ],
+            development: DevelopmentParams::default(),
+            pieces: PieceParams::default(),
+            pawns: PawnParams::default(),
+            king_safety: KingSafetyParams::default(),
+        }
+    }
+}
+
+impl Default for PieceSquareTables {
+    fn default() -> Self {
+        let table = |mg: [[i32; 8]; 8], eg: [[i32; 8]; 8]| PieceSquareTable { mg, eg };
+        Self {
+            pawn: table(PAWN_PST_MG, PAWN_PST_EG),
+            knight: table(KNIGHT_PST_MG, KNIGHT_PST_EG),
+            bishop: table(BISHOP_PST_MG, BISHOP_PST_EG),
+            rook: table(ROOK_PST_MG, ROOK_PST_EG),
+            queen: table(QUEEN_PST_MG, QUEEN_PST_EG),
+            king: table(KING_PST_MG, KING_PST_EG),
+        }
+    }
+}
+
+impl Default for DevelopmentParams {
+    fn default() -> Self {
+        Self {
+            center_pawn: Score::new(40, 10),
+            advanced_pawn: Score::new(40, 0),
+            early_flank_pawn: Score::new(-15, 0),
+            knight_on_rim: Score::new(-20, -10),
+            knight_off_rim: Score::new(5, 5),
+            // Castling only matters while there are pieces around to attack the King
+            castled_kingside: Score::new(100, 0),
+            castled_queenside: Score::new(80, 0),
+        }
+    }
+}
+
+impl Default for PieceParams {
+    fn default() -> Self {
+        Self {
+            bishop_pair: Score::new(30, 50),
+            rook_open_file: Score::new(25, 10),
+            rook_semi_open_file: Score::new(10, 5),
+            queen_open_file: Score::new(5, 5),
+            queen_semi_open_file: Score::new(3, 3),
+            rook_on_seventh: Score::new(20, 30),
+            queen_on_seventh: Score::new(10, 15),
+            knight_outpost: Score::new(25, 15),
+            bishop_outpost: Score::new(15, 8),
+            bad_bishop_pawn: Score::new(-3, -5),
+            trapped_bishop: Score::new(-100, -100),
+            trapped_rook: Score::new(-40, -10),
+        }
+    }
+}
+
+impl Default for PawnParams {
+    fn default() -> Self {
+        Self {
+            doubled: Score::new(-10, -25),
+            isolated: Score::new(-10, -15),
+            backward: Score::new(-8, -10),
+            connected: [
+                Score::new(0, 0),
+                Score::new(3, 2),
+                Score::new(5, 4),
+                Score::new(8, 6),
+                Score::new(14, 12),
+                Score::new(25, 25),
+                Score::new(40, 40),
+                Score::new(0, 0),
+            ],
+            passed: [
+                Score::new(0, 0),
+                Score::new(5, 10),
+                Score::new(5, 15),
+                Score::new(10, 25),
+                Score::new(20, 45),
+                Score::new(35, 75),
+                Score::new(60, 120),
+                Score::new(0, 0),
+            ],
+            passed_free_path: [0, 0, 0, 5, 10, 20, 35, 0],
+            passed_enemy_king_distance: 5,
+            passed_own_king_distance: 2,
+        }
+    }
+}
+
+impl Default for KingSafetyParams {
+    fn default() -> Self {
+        Self {
+            shield: [0, 25, 15, 5],
+            shield_missing: -20,
+            storm: [0, -5, -30, -15, -5],
+            storm_blocked: -5,
+            semi_open_file: -15,
+            open_file: -15,
+            // Pawns and the King never lead an attack, so they count 0
+            attack_weights: [0, 2, 2, 3, 5, 0],
+            safety_table: SAFETY_TABLE.to_vec(),
+        }
+    }
+}
+
+#[rustfmt::skip]
+const SAFETY_TABLE: [i32; 100] = [
+    0, 0, 1, 2, 3, 5, 7, 9, 12, 15,
+    18, 22, 26, 30, 35, 39, 44, 50, 56, 62,
+    68, 75, 82, 85, 89, 97, 105, 113, 122, 131,
+    140, 150, 169, 180, 191, 202, 213, 225, 237, 248,
+    260, 272, 283, 295, 307, 319, 330, 342, 354, 366,
+    377, 389, 401, 412, 424, 436, 448, 459, 471, 483,
+    494, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+];
+
+// All tables below are written from White's point of view, index 0 = a8
+// (the way the board is printed), one row per rank.
+
+// Pawn PST (Encourages advancement and slight center control)
+const PAWN_PST_MG: [[i32; 8]; 8] = [
+    [0, 0, 0, 0, 0, 0, 0, 0],         // Rank 8 (Promotion handled elsewhere)
+    [50, 50, 50, 50, 50, 50, 50, 50], // Rank 7 (Almost promoted!)
+    [10, 10, 20, 30, 30, 20, 10, 10], // Rank 6
+    [5, 5, 10, 25, 25, 10, 5, 5],     // Rank 5
+    [0, 0, 0, 20, 20, 0, 0, 0],       // Rank 4
+    [5, -5, -10, 0, 0, -10, -5, 5],   // Rank 3
+    [5, 10, 10, -20, -20, 10, 10, 5], // Rank 2 (Defending the King)
+    [0, 0, 0, 0, 0, 0, 0, 0],         // Rank 1
+];
+
+// In the endgame every step towards promotion counts, wherever the pawn stands
+const PAWN_PST_EG: [[i32; 8]; 8] = [
+    [0, 0, 0, 0, 0, 0, 0, 0],         // Rank 8
+    [40, 40, 40, 40, 40, 40, 40, 40], // Rank 7
+    [25, 25, 25, 25, 25, 25, 25, 25], // Rank 6
+    [15, 15, 15, 15, 15, 15, 15, 15], // Rank 5
+    [8, 8, 8, 8, 8, 8, 8, 8],         // Rank 4
+    [3, 3, 3, 3, 3, 3, 3, 3],         // Rank 3
+    [0, 0, 0, 0, 0, 0, 0, 0],         // Rank 2
+    [0, 0, 0, 0, 0, 0, 0, 0],         // Rank 1
+];
+
+const KNIGHT_PST_MG: [[i32; 8]; 8] = [
+    [-30, -15, -10, -10, -10, -10, -15, -30], // Rank 8
+    [-15, 0, 0, 5, 5, 0, 0, -15],             // Rank 7
+    [-10, 5, 10, 15, 15, 10, 5, -10],         // Rank 6
+    [-10, 10, 15, 20, 20, 15, 10, -10],       // Rank 5
+    [-10, 5, 15, 20, 20, 15, 5, -10],         // Rank 4
+    [-10, 0, 10, 15, 15, 10, 0, -10],         // Rank 3
+    [-15, -5, 0, 0, 0, 0, -5, -15],           // Rank 2
+    [-30, -15, -10, -10, -10, -10, -15, -30], // Rank 1
+];
+
+const KNIGHT_PST_EG: [[i32; 8]; 8] = [
+    [-25, -15, -10, -10, -10, -10, -15, -25], // Rank 8
+    [-15, -5, 0, 0, 0, 0, -5, -15],           // Rank 7
+    [-10, 0, 5, 10, 10, 5, 0, -10],           // Rank 6
+    [-10, 0, 10, 15, 15, 10, 0, -10],         // Rank 5
+    [-10, 0, 10, 15, 15, 10, 0, -10],         // Rank 4
+    [-10, 0, 5, 10, 10, 5, 0, -10],           // Rank 3
+    [-15, -5, 0, 0, 0, 0, -5, -15],           // Rank 2
+    [-25, -15, -10, -10, -10, -10, -15, -25], // Rank 1
+];
+
+const BISHOP_PST_MG: [[i32; 8]; 8] = [
+    [-10, -10, -10, -10, -10, -10, -10, -10], // Rank 8
+    [-10, 5, 0, 0, 0, 0, 5, -10],             // Rank 7
+    [-10, 0, 5, 10, 10, 5, 0, -10],           // Rank 6
+    [-10, 5, 10, 15, 15, 10, 5, -10],         // Rank 5
+    [-10, 0, 10, 15, 15, 10, 0, -10],         // Rank 4
+    [-10, 5, 5, 10, 10, 5, 5, -10],           // Rank 3
+    [-10, 0, 0, 0, 0, 0, 0, -10],             // Rank 2
+    [-10, -10, -10, -10, -10, -10, -10, -10], // Rank 1
+];
+
+const BISHOP_PST_EG: [[i32; 8]; 8] = [
+    [-10, -5, -5, -5, -5, -5, -5, -10], // Rank 8
+    [-5, 0, 0, 0, 0, 0, 0, -5],         // Rank 7
+    [-5, 0, 5, 5, 5, 5, 0, -5],         // Rank 6
+    [-5, 0, 5, 10, 10, 5, 0, -5],       // Rank 5
+    [-5, 0, 5, 10, 10, 5, 0, -5],       // Rank 4
+    [-5, 0, 5, 5, 5, 5, 0, -5],         // Rank 3
+    [-5, 0, 0, 0, 0, 0, 0, -5],         // Rank 2
+    [-10, -5, -5, -5, -5, -5, -5, -10], // Rank 1
+];
+
+const ROOK_PST_MG: [[i32; 8]; 8] = [
+    [0, 0, 0, 5, 5, 0, 0, 0],         // Rank 8
+    [10, 15, 15, 15, 15, 15, 15, 10], // Rank 7 (Reward for 7th rank!)
+    [-5, 0, 0, 0, 0, 0, 0, -5],       // Rank 6
+    [-5, 0, 0, 0, 0, 0, 0, -5],       // Rank 5
+    [-5, 0, 0, 0, 0, 0, 0, -5],       // Rank 4
+    [-5, 0, 0, 0, 0, 0, 0, -5],       // Rank 3
+    [-5, 0, 0, 0, 0, 0, 0, -5],       // Rank 2
+    [0, 0, 0, 5, 5, 0, 0, 0],         // Rank 1 (Centralize on start)
+];
+
+const ROOK_PST_EG: [[i32; 8]; 8] = [
+    [5, 5, 5, 5, 5, 5, 5, 5],         // Rank 8
+    [10, 10, 10, 10, 10, 10, 10, 10], // Rank 7
+    [0, 0, 0, 0, 0, 0, 0, 0],         // Rank 6
+    [0, 0, 0, 0, 0, 0, 0, 0],         // Rank 5
+    [0, 0, 0, 0, 0, 0, 0, 0],         // Rank 4
+    [0, 0, 0, 0, 0, 0, 0, 0],         // Rank 3
+    [0, 0, 0, 0, 0, 0, 0, 0],         // Rank 2
+    [0, 0, 0, 0, 0, 0, 0, 0],         // Rank 1
+];
+
+const QUEEN_PST_MG: [[i32; 8]; 8] = [
+    [-20, -10, -10, -5, -5, -10, -10, -20], // Rank 8
+    [-10, 0, 5, 0, 0, 0, 0, -10],           // Rank 7
+    [-10, 5, 5, 5, 5, 5, 0, -10],           // Rank 6
+    [-5, 0, 5, 5, 5, 5, 0, -5],             // Rank 5
+    [0, 0, 5, 5, 5, 5, 0, -5],              // Rank 4
+    [-10, 0, 5, 5, 5, 5, 0, -10],           // Rank 3
+    [-10, 0, 0, 0, 0, 0, 0, -10],           // Rank 2
+    [-20, -10, -10, -5, -5, -10, -10, -20], // Rank 1
+];
+
+const QUEEN_PST_EG: [[i32; 8]; 8] = [
+    [-20, -10, -10, -5, -5, -10, -10, -20], // Rank 8
+    [-10, 0, 0, 0, 0, 0, 0, -10],           // Rank 7
+    [-10, 0, 5, 5, 5, 5, 0, -10],           // Rank 6
+    [-5, 0, 5, 10, 10, 5, 0, -5],           // Rank 5
+    [-5, 0, 5, 10, 10, 5, 0, -5],           // Rank 4
+    [-10, 0, 5, 5, 5, 5, 0, -10],           // Rank 3
+    [-10, 0, 0, 0, 0, 0, 0, -10],           // Rank 2
+    [-20, -10, -10, -5, -5, -10, -10, -20], // Rank 1
+];
+
+// Middlegame: stay behind the pawn shield
+const KING_PST_MG: [[i32; 8]; 8] = [
+    [-30, -40, -40, -50, -50, -40, -40, -30], // Rank 8
+    [-30, -40, -40, -50, -50, -40, -40, -30], // Rank 7
+    [-30, -40, -40, -50, -50, -40, -40, -30], // Rank 6
+    [-30, -40, -40, -50, -50, -40, -40, -30], // Rank 5
+    [-20, -30, -30, -40, -40, -30, -30, -20], // Rank 4
+    [-10, -20, -20, -20, -20, -20, -20, -10], // Rank 3
+    [10, 10, -5, -10, -10, -5, 10, 10],       // Rank 2
+    [20, 30, 10, 0, 0, 10, 30, 20],           // Rank 1 (Values are lower, max 30)
+];
+
+// Endgame: walk to the center and join the fight
+const KING_PST_EG: [[i32; 8]; 8] = [
+    [-50, -40, -30, -20, -20, -30, -40, -50], // Rank 8
+    [-30, -20, -10, 0, 0, -10, -20, -30],     // Rank 7
+    [-30, -10, 20, 30, 30, 20, -10, -30],     // Rank 6
+    [-30, -10, 30, 40, 40, 30, -10, -30],     // Rank 5
+    [-30, -10, 30, 40, 40, 30, -10, -30],     // Rank 4
+    [-30, -10, 20, 30, 30, 20, -10, -30],     // Rank 3
+    [-30, -30, 0, 0, 0, 0, -30, -30],         // Rank 2
+    [-50, -30, -30, -30, -30, -30, -30, -50], // Rank 1
+];
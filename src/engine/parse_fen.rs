@@ -27,10 +27,11 @@
 //!
 //!
 //!
-//! ### 4. Surgical Updates ([`update_fen`])
-//! A high-level utility for performing "surgical strikes" on a board state. By expanding
-//! a FEN into a 2D grid, moving a piece, and re-compressing it, this function allows
-//! for board manipulation without touching low-level bitboard logic.
+//! ### 4. Board Construction ([`board_from_fen`])
+//! Builds a complete [`Bitboards`] state from a FEN string: pieces, castling rights
+//! and the en passant square.
+//!
+//! The inverse, [`board_to_fen`], writes a full FEN string for a board state.
 //!
 //! ---
 //!
@@ -39,7 +40,7 @@
 //! Rust’s functional pipelines (`.split()`, `.map()`, `.rev()`), the code remains
 //! immutable, easy to test, and resistant to "off-by-one" string parsing errors.
 
-use crate::engine::board::Color;
+use crate::engine::board::{Bitboards, Color, PieceType};
 
 /// Transforms a FEN piece-placement string into a flattened 64-character board map.
 ///
@@ -86,8 +87,7 @@ pub fn parse_fen(fen: &str) -> String {
         .rev()
         .collect();
 
-    let board_representation = ranks.concat();
-    board_representation
+    ranks.concat()
 }
 
 /// Converts a 64-character flat board string into a FEN piece-placement string.
@@ -176,79 +176,137 @@ pub fn side_to_move(fen: &str) -> Option<Color> {
     }
 }
 
-/// Updates the piece-placement portion of a FEN string based on a move.
-///
-/// This function performs a "lightweight" move update by expanding the FEN
-/// into a 2D grid, moving the character from the source square to the
-/// destination, and re-compressing the grid into FEN format.
+/// Builds a [`Bitboards`] state from a full FEN string.
 ///
 /// # Arguments
 ///
-/// * `from` - The starting UCI square (e.g., "e2").
-/// * `to` - The destination UCI square (e.g., "e4").
-/// * `fen` - The current full FEN string.
+/// * `fen` - A FEN string. The piece placement is required; the castling and
+///   en passant fields are read when present.
 ///
 /// # Returns
 ///
-/// Returns a [`String`] containing the updated **piece-placement** part of the FEN.
+/// * `Some(Bitboards)` with pieces, castling rights and en passant square set.
+/// * `None` if the piece placement does not describe 64 squares or contains an
+///   unknown piece letter.
 ///
 /// # Note
 ///
-/// This function currently only updates the board layout. It does not update
-/// side-to-move, castling rights, en passant squares, or move counters.
-pub fn update_fen(from: &str, to: &str, fen: &str) -> String {
-    let board_part = fen.split_whitespace().next().unwrap();
+/// A missing castling field means no castling rights. The side to move is not
+/// part of [`Bitboards`]; use [`side_to_move`] for it.
+pub fn board_from_fen(fen: &str) -> Option<Bitboards> {
+    let flat_board = parse_fen(fen);
+    if flat_board.chars().count() != 64 {
+        return None;
+    }
 
-    let mut board: Vec<Vec<char>> = board_part
-        .split('/')
-        .map(|rank| {
-            let mut row = Vec::new();
-            for c in rank.chars() {
-                if c.is_digit(10) {
-                    for _ in 0..c.to_digit(10).unwrap() {
-                        row.push('.');
-                    }
+    let mut board = Bitboards::new();
+    for (square, c) in flat_board.chars().enumerate() {
+        if c == '.' {
+            continue;
+        }
+        let color = if c.is_lowercase() {
+            Color::Black
+        } else {
+            Color::White
+        };
+        let piece_type = match c.to_ascii_lowercase() {
+            'p' => PieceType::Pawn,
+            'n' => PieceType::Knight,
+            'b' => PieceType::Bishop,
+            'r' => PieceType::Rook,
+            'q' => PieceType::Queen,
+            'k' => PieceType::King,
+            _ => return None,
+        };
+        Bitboards::add_piece(&mut board, color, piece_type, square as u8);
+    }
+
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    let castling = fields.get(2).copied().unwrap_or("-");
+    board.white_kingside = castling.contains('K');
+    board.white_queenside = castling.contains('Q');
+    board.black_kingside = castling.contains('k');
+    board.black_queenside = castling.contains('q');
+
+    board.en_passant_square = fields.get(3).and_then(|field| match field.as_bytes() {
+        [file @ b'a'..=b'h', rank @ b'1'..=b'8'] => Some((rank - b'1') * 8 + (file - b'a')),
+        _ => None,
+    });
+
+    Some(board)
+}
+
+/// Writes a full FEN string for a board state.
+///
+/// # Arguments
+///
+/// * `board` - The [`Bitboards`] state to describe.
+/// * `color` - The side to move.
+/// * `halfmove_clock` - Half-moves since the last capture or pawn move.
+/// * `fullmove_number` - The move number, starting at 1 and incremented after Black moves.
+///
+/// # Returns
+///
+/// A six-field FEN string that [`board_from_fen`] reads back into the same state.
+pub fn board_to_fen(
+    board: &Bitboards,
+    color: Color,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+) -> String {
+    let flat_board: String = (0..64u8)
+        .map(|sq| match Bitboards::piece_at(board, sq) {
+            Some((owner, piece)) => {
+                let c = match piece {
+                    PieceType::Pawn => 'p',
+                    PieceType::Knight => 'n',
+                    PieceType::Bishop => 'b',
+                    PieceType::Rook => 'r',
+                    PieceType::Queen => 'q',
+                    PieceType::King => 'k',
+                };
+                if owner == Color::White {
+                    c.to_ascii_uppercase()
                 } else {
-                    row.push(c);
+                    c
                 }
             }
-            row
+            None => '.',
         })
         .collect();
 
-    let square_to_idx = |sq: &str| -> (usize, usize) {
-        let file = (sq.chars().nth(0).unwrap() as u8 - b'a') as usize;
-        let rank = (sq.chars().nth(1).unwrap().to_digit(10).unwrap() - 1) as usize;
-        (7 - rank, file)
+    let side = match color {
+        Color::White => "w",
+        Color::Black => "b",
     };
 
-    let (from_row, from_col) = square_to_idx(from);
-    let (to_row, to_col) = square_to_idx(to);
-
-    let piece = board[from_row][from_col];
-    board[from_row][from_col] = '.';
-    board[to_row][to_col] = piece;
-
-    let mut new_fen_parts = Vec::new();
-    for row in board {
-        let mut row_str = String::new();
-        let mut empty_count = 0;
-        for c in row {
-            if c == '.' {
-                empty_count += 1;
-            } else {
-                if empty_count > 0 {
-                    row_str.push_str(&empty_count.to_string());
-                    empty_count = 0;
-                }
-                row_str.push(c);
-            }
-        }
-        if empty_count > 0 {
-            row_str.push_str(&empty_count.to_string());
+    let mut castling = String::new();
+    for (allowed, symbol) in [
+        (board.white_kingside, 'K'),
+        (board.white_queenside, 'Q'),
+        (board.black_kingside, 'k'),
+        (board.black_queenside, 'q'),
+    ] {
+        if allowed {
+            castling.push(symbol);
         }
-        new_fen_parts.push(row_str);
     }
+    if castling.is_empty() {
+        castling.push('-');
+    }
+
+    let en_passant = match board.en_passant_square {
+        Some(sq) => format!("{}{}", (b'a' + sq % 8) as char, sq / 8 + 1),
+        None => "-".to_string(),
+    };
 
-    new_fen_parts.join("/")
+    format!(
+        "{} {} {} {} {} {}",
+        flat_board_to_fen(&flat_board),
+        side,
+        castling,
+        en_passant,
+        halfmove_clock,
+        fullmove_number
+    )
 }
@@ -0,0 +1,253 @@
+//! # Module: `pawns`
+//!
+//! Pawn structure evaluation. Pawns are the "skeleton" of a position: they move
+//! slowly, never move backwards, and decide where the pieces can go. This module
+//! scores the classic structural features for each side:
+//!
+//! * **Doubled Pawns:** Two pawns on one file block each other.
+//! * **Isolated Pawns:** No friendly pawns on the neighbouring files to defend them.
+//! * **Backward Pawns:** Left behind by their neighbours, with the square in front
+//!   controlled by an enemy pawn, so they cannot safely advance.
+//! * **Connected Pawns:** Defended by a friendly pawn, or standing side by side
+//!   with one (a "phalanx"). Worth more the further they have advanced.
+//! * **Passed Pawns:** No enemy pawn can stop them on their way to promotion.
+//!   The bonus grows with the rank, and in the endgame it is adjusted by how
+//!   close both Kings are to the pawn and whether its path is free.
+//!
+//! ## Pawn Hash Table
+//!
+//! Everything except the passed pawn King/path adjustments depends on the pawns
+//! alone, which change in only a small fraction of the moves searched. Those terms
+//! are cached in a small per-thread hash table keyed by [`zobrist::pawn_key`], so
+//! the cost is paid once per pawn configuration.
+//!
+//! The table stores *which* pawns are doubled, isolated, passed... as bitboards,
+//! not their scores. Weights are applied on every probe, so entries stay valid
+//! when the evaluation parameters change.
+
+use crate::board::{Bitboards, Color, PieceType};
+use crate::engine::evaluation::Score;
+use crate::engine::make_move::pawn_attacks;
+use crate::engine::params::PawnParams;
+use crate::engine::zobrist;
+use std::cell::RefCell;
+
+/// Bitmask representing all squares on the A-file.
+const FILE_A: u64 = 0x0101010101010101;
+
+/// Number of entries in the pawn hash table (must be a power of two).
+const PAWN_TABLE_SIZE: usize = 1 << 14;
+
+/// Cached pawn features of one pawn configuration, indexed by color.
+#[derive(Clone, Copy, Default)]
+struct PawnEntry {
+    key: u64,
+    doubled: [u64; 2],
+    isolated: [u64; 2],
+    backward: [u64; 2],
+    connected: [u64; 2],
+    passed: [u64; 2],
+}
+
+struct PawnTable {
+    entries: Vec<PawnEntry>,
+}
+
+impl PawnTable {
+    fn new() -> Self {
+        // An empty entry (key 0, no features) is exactly the result for a
+        // board without pawns, whose key is also 0, so no "valid" flag is needed.
+        Self {
+            entries: vec![PawnEntry::default(); PAWN_TABLE_SIZE],
+        }
+    }
+
+    fn probe(&mut self, board: &Bitboards) -> PawnEntry {
+        let key = zobrist::pawn_key(board);
+        let slot = &mut self.entries[key as usize & (PAWN_TABLE_SIZE - 1)];
+        if slot.key != key {
+            *slot = evaluate_pawns(board, key);
+        }
+        *slot
+    }
+}
+
+thread_local! {
+    static PAWN_TABLE: RefCell<PawnTable> = RefCell::new(PawnTable::new());
+}
+
+/// Scores the pawn structure of one side, from that side's point of view.
+///
+/// # Arguments
+///
+/// * `board` - A reference to the current [`Bitboards`] state.
+/// * `color` - The side whose pawns are evaluated.
+/// * `weights` - The pawn structure weights.
+///
+/// # Returns
+///
+/// A middlegame/endgame [`Score`] made of the cached structure terms plus the
+/// passed pawn adjustments that depend on the Kings and the other pieces.
+pub fn pawn_structure(board: &Bitboards, color: Color, weights: &PawnParams) -> Score {
+    let entry = PAWN_TABLE.with(|table| table.borrow_mut().probe(board));
+    let side = color as usize;
+
+    let mut score = weights.doubled * Bitboards::count_pieces(entry.doubled[side])
+        + weights.isolated * Bitboards::count_pieces(entry.isolated[side])
+        + weights.backward * Bitboards::count_pieces(entry.backward[side]);
+    for sq in Bitboards::return_squares(entry.connected[side]) {
+        score += weights.connected[relative_rank(sq, color)];
+    }
+    for sq in Bitboards::return_squares(entry.passed[side]) {
+        score += weights.passed[relative_rank(sq, color)];
+    }
+
+    score + passed_pawn_dynamics(board, color, entry.passed[side], weights)
+}
+
+pub(crate) fn file_mask(file: u8) -> u64 {
+    FILE_A << file
+}
+
+pub(crate) fn adjacent_files(file: u8) -> u64 {
+    let mut mask = 0u64;
+    if file > 0 {
+        mask |= file_mask(file - 1);
+    }
+    if file < 7 {
+        mask |= file_mask(file + 1);
+    }
+    mask
+}
+
+// All squares on ranks strictly in front of `rank`, seen from `color`
+pub(crate) fn ranks_in_front(rank: u8, color: Color) -> u64 {
+    match color {
+        Color::White if rank < 7 => u64::MAX << ((rank + 1) * 8),
+        Color::Black if rank > 0 => u64::MAX >> ((8 - rank) * 8),
+        _ => 0,
+    }
+}
+
+pub(crate) fn relative_rank(sq: u8, color: Color) -> usize {
+    match color {
+        Color::White => (sq / 8) as usize,
+        Color::Black => (7 - sq / 8) as usize,
+    }
+}
+
+fn chebyshev_distance(a: u8, b: u8) -> i32 {
+    let file_distance = ((a % 8) as i32 - (b % 8) as i32).abs();
+    let rank_distance = ((a / 8) as i32 - (b / 8) as i32).abs();
+    file_distance.max(rank_distance)
+}
+
+// Finds the cacheable, pawn-only features for both sides
+fn evaluate_pawns(board: &Bitboards, key: u64) -> PawnEntry {
+    let mut entry = PawnEntry {
+        key,
+        ..PawnEntry::default()
+    };
+
+    for color in [Color::White, Color::Black] {
+        let enemy_color = match color {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        };
+        let own_pawns = board.boards[color as usize][PieceType::Pawn as usize];
+        let enemy_pawns = board.boards[enemy_color as usize][PieceType::Pawn as usize];
+        let side = color as usize;
+
+        for sq in Bitboards::return_squares(own_pawns) {
+            let bit = 1u64 << sq;
+            let file = sq % 8;
+            let rank = sq / 8;
+            let relative = relative_rank(sq, color);
+            let in_front = ranks_in_front(rank, color);
+            let neighbours = own_pawns & adjacent_files(file);
+
+            // Doubled: every pawn with a friendly pawn in front of it on its file
+            if own_pawns & file_mask(file) & in_front != 0 {
+                entry.doubled[side] |= bit;
+            }
+
+            if neighbours == 0 {
+                entry.isolated[side] |= bit;
+            } else if relative < 7 {
+                // Backward: every neighbour is further advanced and the stop square
+                // is controlled by an enemy pawn
+                let stop = match color {
+                    Color::White => sq + 8,
+                    Color::Black => sq - 8,
+                };
+                let left_behind = neighbours & !in_front == 0;
+                let stop_attacked = pawn_attacks(stop, color) & enemy_pawns != 0;
+                if left_behind && stop_attacked {
+                    entry.backward[side] |= bit;
+                }
+            }
+
+            // Connected: defended by a pawn, or standing next to one
+            let supported = pawn_attacks(sq, enemy_color) & own_pawns != 0;
+            let phalanx = neighbours & (0xFFu64 << (rank * 8)) != 0;
+            if supported || phalanx {
+                entry.connected[side] |= bit;
+            }
+
+            // Passed: no enemy pawn in front on this or the neighbouring files, and
+            // not blocked by a friendly pawn (only the front one of doubled pawns counts)
+            let front_span = (file_mask(file) | adjacent_files(file)) & in_front;
+            let blocked_by_own = own_pawns & file_mask(file) & in_front != 0;
+            if enemy_pawns & front_span == 0 && !blocked_by_own {
+                entry.passed[side] |= bit;
+            }
+        }
+    }
+
+    entry
+}
+
+// Passed pawn terms that depend on the Kings and the other pieces, so cannot be cached
+fn passed_pawn_dynamics(
+    board: &Bitboards,
+    color: Color,
+    passed: u64,
+    weights: &PawnParams,
+) -> Score {
+    let enemy_color = match color {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    };
+    let own_king = board.boards[color as usize][PieceType::King as usize];
+    let enemy_king = board.boards[enemy_color as usize][PieceType::King as usize];
+    if own_king == 0 || enemy_king == 0 {
+        return Score::ZERO;
+    }
+    let own_king = own_king.trailing_zeros() as u8;
+    let enemy_king = enemy_king.trailing_zeros() as u8;
+    let occupancy = Bitboards::occupancy(board);
+
+    let mut eg = 0;
+    for sq in Bitboards::return_squares(passed) {
+        let relative = relative_rank(sq, color);
+        if !(3..7).contains(&relative) {
+            continue;
+        }
+        let weight = relative as i32 - 2;
+        let stop = match color {
+            Color::White => sq + 8,
+            Color::Black => sq - 8,
+        };
+
+        // The defending King wants to stand in front of the pawn, ours wants to escort it
+        eg += chebyshev_distance(stop, enemy_king) * weights.passed_enemy_king_distance * weight;
+        eg -= chebyshev_distance(stop, own_king) * weights.passed_own_king_distance * weight;
+
+        let path = file_mask(sq % 8) & ranks_in_front(sq / 8, color);
+        if occupancy & path == 0 {
+            eg += weights.passed_free_path[relative];
+        }
+    }
+
+    Score::new(0, eg)
+}
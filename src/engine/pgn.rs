@@ -0,0 +1,660 @@
+//! # Module: `pgn`
+//!
+//! Reading, replaying and writing games in **Portable Game Notation**.
+//!
+//! A PGN file holds any number of games, each made of tag pairs
+//! (`[White "Tal, Mikhail"]`) followed by the movetext: move numbers, moves in
+//! [SAN](crate::engine::san) and the result.
+//!
+//! ## Game Tree
+//!
+//! [`read_games`] keeps the whole movetext as a tree: the main line is a list of
+//! [`PgnMove`]s, and a variation `(...)` is stored with the move it replaces, as
+//! another list that may hold variations of its own. Every move keeps its
+//! comments (`{...}` and `;` to the end of the line) and numeric annotation
+//! glyphs (`$1`); suffix annotations such as `!?` become their glyphs. Lines
+//! starting with `%` are skipped.
+//!
+//! The tree is only text. [`PgnGame::replay`] plays the main line from the
+//! starting position through [`apply_move`](crate::make_move::apply_move),
+//! turning every SAN move into a legal [`Move`]; [`PgnGame::walk`] does the same
+//! for the variations.
+//!
+//! ## Export Format
+//!
+//! [`PgnGame::to_pgn`] writes the Seven Tag Roster first, in its fixed order
+//! and with `?` for unknown values, then any other tags. Movetext lines stay
+//! below 80 columns, and a Black move following a comment or variation repeats
+//! its move number (`12...`). Clock and evaluation data go in comments as the
+//! commands `[%emt 0:00:12]` (time spent on the move) and `[%eval 0.35]` (from
+//! White's point of view).
+
+use crate::board::{Bitboards, Color};
+use crate::engine::parse_fen;
+use crate::engine::san::parse_san;
+use crate::engine::search::{MATE_BOUND, MATE_SCORE, opposite};
+use crate::make_move;
+use crate::movegen::Move;
+use std::time::{Duration, SystemTime};
+
+const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// One game: its tags, its moves and result.
+#[derive(Debug, Default)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    /// The main line.
+    pub moves: Vec<PgnMove>,
+    pub result: String,
+}
+
+/// One move of the game tree, with its annotations.
+#[derive(Clone, Debug, Default)]
+pub struct PgnMove {
+    /// The move as written, without suffix annotations.
+    pub san: String,
+    /// Numeric annotation glyphs, e.g. `1` for `!`.
+    pub nags: Vec<u8>,
+    /// Comment before the move; only found at the start of a line.
+    pub comment_before: String,
+    /// Comment after the move.
+    pub comment: String,
+    /// Lines played instead of this move.
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+impl PgnMove {
+    /// A move without annotations.
+    pub fn new(san: &str) -> Self {
+        Self {
+            san: san.to_string(),
+            ..Self::default()
+        }
+    }
+}
+
+/// A move of a replayed game.
+#[derive(Clone, Debug)]
+pub struct Ply {
+    /// The position the move is played in.
+    pub board: Bitboards,
+    pub color: Color,
+    pub mv: Move,
+}
+
+/// The main line of a game, replayed move by move.
+#[derive(Debug)]
+pub struct Replay {
+    pub plies: Vec<Ply>,
+    /// The position after the last replayed move.
+    pub board: Bitboards,
+    /// The side to move there.
+    pub color: Color,
+    /// Full move number there.
+    pub fullmove: u32,
+    /// The first move that could not be played, if any; the replay stops there.
+    pub error: Option<String>,
+}
+
+// Tags every game starts with, in this order
+const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+// Longest movetext line written
+const LINE_WIDTH: usize = 79;
+
+// Suffix annotations and their glyphs
+const SUFFIXES: [(&str, u8); 6] = [
+    ("!!", 3),
+    ("??", 4),
+    ("!?", 5),
+    ("?!", 6),
+    ("!", 1),
+    ("?", 2),
+];
+
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+impl PgnGame {
+    /// The value of a tag, e.g. `tag("WhiteElo")`.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Sets a tag, replacing its earlier value.
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// The position the game starts from: its `FEN` tag, or the standard one.
+    ///
+    /// # Returns
+    ///
+    /// The board, side to move and full move number, or an error if the `FEN`
+    /// tag cannot be read.
+    pub fn start_position(&self) -> Result<(Bitboards, Color, u32), String> {
+        let fen = self.tag("FEN").unwrap_or(START_FEN);
+        let board =
+            parse_fen::board_from_fen(fen).ok_or_else(|| format!("invalid FEN {:?}", fen))?;
+        let color = parse_fen::side_to_move(fen).unwrap_or(Color::White);
+        let fullmove = fen
+            .split_whitespace()
+            .nth(5)
+            .and_then(|number| number.parse().ok())
+            .unwrap_or(1);
+        Ok((board, color, fullmove))
+    }
+
+    /// Replays the main line from the starting position.
+    ///
+    /// # Returns
+    ///
+    /// The replayed moves up to the first one that is not legal (see
+    /// [`Replay::error`]), or an error if the starting position cannot be read.
+    pub fn replay(&self) -> Result<Replay, String> {
+        let (board, color, fullmove) = self.start_position()?;
+        let mut replay = Replay {
+            plies: Vec::with_capacity(self.moves.len()),
+            board,
+            color,
+            fullmove,
+            error: None,
+        };
+        for pgn_move in &self.moves {
+            let Some(mv) = parse_san(&pgn_move.san, &replay.board, replay.color) else {
+                replay.error = Some(move_error(replay.fullmove, replay.color, &pgn_move.san));
+                break;
+            };
+            replay.plies.push(Ply {
+                board: replay.board.clone(),
+                color: replay.color,
+                mv: mv.clone(),
+            });
+            make_move::apply_move(&mut replay.board, &mv, replay.color);
+            if replay.color == Color::Black {
+                replay.fullmove += 1;
+            }
+            replay.color = opposite(replay.color);
+        }
+        Ok(replay)
+    }
+
+    /// Plays through every move of the game, variations included.
+    ///
+    /// # Arguments
+    ///
+    /// * `visit` - Called for every move with the position it is played in and
+    ///   its variation depth, `0` on the main line.
+    ///
+    /// # Returns
+    ///
+    /// An error naming the first move, in any line, that is not legal; the lines
+    /// that contain it are not followed further.
+    pub fn walk(&self, mut visit: impl FnMut(&Ply, usize)) -> Result<(), String> {
+        let (board, color, fullmove) = self.start_position()?;
+        let mut error = None;
+        walk_line(
+            &self.moves,
+            board,
+            color,
+            fullmove,
+            0,
+            &mut visit,
+            &mut error,
+        );
+        error.map_or(Ok(()), Err)
+    }
+
+    /// Writes the game in PGN export format.
+    ///
+    /// Move numbers follow the `FEN` tag, if any, so games from a custom
+    /// position may start with a Black move (`23...`).
+    pub fn to_pgn(&self) -> String {
+        let result = if self.result.is_empty() {
+            "*"
+        } else {
+            &self.result
+        };
+        let mut out = String::new();
+        for name in SEVEN_TAG_ROSTER {
+            let value = match name {
+                "Result" => result,
+                _ => self.tag(name).unwrap_or("?"),
+            };
+            out.push_str(&tag_pair(name, value));
+        }
+        for (name, value) in &self.tags {
+            if !SEVEN_TAG_ROSTER.contains(&name.as_str()) {
+                out.push_str(&tag_pair(name, value));
+            }
+        }
+        out.push('\n');
+
+        // Side to move and move number of the first move
+        let fen: Vec<&str> = self.tag("FEN").unwrap_or("").split_whitespace().collect();
+        let black = fen.get(1) == Some(&"b");
+        let number = fen.get(5).and_then(|n| n.parse().ok()).unwrap_or(1);
+
+        let mut tokens = Vec::new();
+        write_line(&self.moves, number, black, &mut tokens);
+        tokens.push(result.to_string());
+
+        let mut line = String::new();
+        for token in tokens {
+            let glued = line.ends_with('(') || token == ")";
+            if !line.is_empty() && !glued && line.len() + 1 + token.len() > LINE_WIDTH {
+                out.push_str(&line);
+                out.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() && !glued {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        out.push_str(&line);
+        out.push('\n');
+        out
+    }
+}
+
+// Appends the movetext tokens of a line, starting at the given move
+fn write_line(moves: &[PgnMove], mut number: u32, mut black: bool, tokens: &mut Vec<String>) {
+    let mut interrupted = true;
+    for pgn_move in moves {
+        if !pgn_move.comment_before.is_empty() {
+            tokens.push(comment(&pgn_move.comment_before));
+            interrupted = true;
+        }
+        if !black {
+            tokens.push(format!("{}.", number));
+        } else if interrupted {
+            tokens.push(format!("{}...", number));
+        }
+        tokens.push(pgn_move.san.clone());
+        tokens.extend(pgn_move.nags.iter().map(|nag| format!("${}", nag)));
+        interrupted = false;
+        if !pgn_move.comment.is_empty() {
+            tokens.push(comment(&pgn_move.comment));
+            interrupted = true;
+        }
+        for variation in &pgn_move.variations {
+            tokens.push("(".to_string());
+            write_line(variation, number, black, tokens);
+            tokens.push(")".to_string());
+            interrupted = true;
+        }
+        if black {
+            number += 1;
+        }
+        black = !black;
+    }
+}
+
+// A comment in braces; a closing brace inside would end it early
+fn comment(text: &str) -> String {
+    format!("{{{}}}", text.replace('}', ")"))
+}
+
+// Plays a line and its variations, which start from the position before the
+// move they replace
+fn walk_line(
+    moves: &[PgnMove],
+    mut board: Bitboards,
+    mut color: Color,
+    mut fullmove: u32,
+    depth: usize,
+    visit: &mut impl FnMut(&Ply, usize),
+    error: &mut Option<String>,
+) {
+    for pgn_move in moves {
+        for variation in &pgn_move.variations {
+            walk_line(
+                variation,
+                board.clone(),
+                color,
+                fullmove,
+                depth + 1,
+                visit,
+                error,
+            );
+        }
+        let Some(mv) = parse_san(&pgn_move.san, &board, color) else {
+            error.get_or_insert_with(|| move_error(fullmove, color, &pgn_move.san));
+            return;
+        };
+        let ply = Ply {
+            board: board.clone(),
+            color,
+            mv,
+        };
+        visit(&ply, depth);
+        make_move::apply_move(&mut board, &ply.mv, color);
+        if color == Color::Black {
+            fullmove += 1;
+        }
+        color = opposite(color);
+    }
+}
+
+fn move_error(fullmove: u32, color: Color, san: &str) -> String {
+    let dots = if color == Color::White { "." } else { "..." };
+    format!("{}{} {} is not a legal move", fullmove, dots, san)
+}
+
+// One tag line, escaping quotes and backslashes in the value
+fn tag_pair(name: &str, value: &str) -> String {
+    let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("[{} \"{}\"]\n", name, value)
+}
+
+/// Today's date in the format of the `Date` tag, `YYYY.MM.DD` (UTC).
+pub fn today() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    // Days since 1970-01-01 to a civil date, after Howard Hinnant's algorithm
+    let days = (seconds / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+/// The `[%emt]` comment command for the time spent on a move.
+pub fn emt_command(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+    format!(
+        "[%emt {}:{:02}:{:02}]",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// The `[%eval]` comment command for a score from White's point of view:
+/// pawns with two decimals, or `#N` / `#-N` for a mate in `N` moves.
+pub fn eval_command(white_score: i32) -> String {
+    if white_score.abs() >= MATE_BOUND {
+        let moves = (MATE_SCORE - white_score.abs() + 1) / 2;
+        let sign = if white_score < 0 { "-" } else { "" };
+        return format!("[%eval #{}{}]", sign, moves);
+    }
+    format!("[%eval {:.2}]", f64::from(white_score) / 100.0)
+}
+
+/// Splits PGN text into games.
+///
+/// # Returns
+///
+/// Every game in the text, in order. A game without a result token takes the
+/// one from its `Result` tag, or `*`. Unbalanced parentheses are tolerated: a
+/// stray `)` is ignored and unclosed variations end with the game.
+pub fn read_games(text: &str) -> Vec<PgnGame> {
+    let mut games = Vec::new();
+    let mut reader = Reader::default();
+    let mut chars = text.chars().peekable();
+    let mut line_start = true;
+
+    while let Some(c) = chars.next() {
+        let at_line_start = line_start;
+        line_start = c == '\n';
+        match c {
+            // Escape lines, for data of other programs
+            '%' if at_line_start => {
+                chars.by_ref().take_while(|&c| c != '\n').for_each(drop);
+                line_start = true;
+            }
+            '[' if reader.lines.len() == 1 => {
+                // Tags after movetext start the next game
+                if reader.has_moves() {
+                    reader.finish(&mut games);
+                }
+                // Read up to the closing bracket, which may appear inside the value
+                let mut tag = String::new();
+                let mut quoted = false;
+                let mut escaped = false;
+                for c in chars.by_ref() {
+                    match c {
+                        ']' if !quoted => break,
+                        '"' if !escaped => quoted = !quoted,
+                        _ => {}
+                    }
+                    escaped = c == '\\' && !escaped;
+                    tag.push(c);
+                }
+                if let Some(pair) = parse_tag(&tag) {
+                    reader.game.tags.push(pair);
+                }
+            }
+            '{' => {
+                let text: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                reader.comment(&text);
+            }
+            ';' => {
+                let text: String = chars.by_ref().take_while(|&c| c != '\n').collect();
+                reader.comment(&text);
+                line_start = true;
+            }
+            '(' => reader.lines.push(Line::default()),
+            ')' => reader.close_variation(),
+            c if c.is_whitespace() => {}
+            c => {
+                let mut token = String::from(c);
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || "[]{}();".contains(next) {
+                        break;
+                    }
+                    token.push(next);
+                    chars.next();
+                }
+                reader.token(&token, &mut games);
+            }
+        }
+    }
+    if reader.has_moves() || !reader.game.tags.is_empty() {
+        reader.finish(&mut games);
+    }
+    games
+}
+
+// A line being read: its moves and a comment waiting for its first move
+#[derive(Default)]
+struct Line {
+    moves: Vec<PgnMove>,
+    comment: String,
+}
+
+// The game being read, with the main line and the open variations
+struct Reader {
+    game: PgnGame,
+    lines: Vec<Line>,
+}
+
+impl Default for Reader {
+    fn default() -> Self {
+        Self {
+            game: PgnGame::default(),
+            lines: vec![Line::default()],
+        }
+    }
+}
+
+impl Reader {
+    fn has_moves(&self) -> bool {
+        !self.lines[0].moves.is_empty()
+    }
+
+    fn current(&mut self) -> &mut Line {
+        self.lines
+            .last_mut()
+            .expect("the main line is never closed")
+    }
+
+    fn comment(&mut self, text: &str) {
+        let line = self.current();
+        let target = match line.moves.last_mut() {
+            Some(last) => &mut last.comment,
+            None => &mut line.comment,
+        };
+        if !target.is_empty() {
+            target.push(' ');
+        }
+        target.push_str(text.trim());
+    }
+
+    // Attaches the innermost variation to the move it replaces
+    fn close_variation(&mut self) {
+        if self.lines.len() < 2 {
+            return;
+        }
+        let variation = self.lines.pop().expect("checked above").moves;
+        if let Some(replaced) = self.current().moves.last_mut()
+            && !variation.is_empty()
+        {
+            replaced.variations.push(variation);
+        }
+    }
+
+    fn token(&mut self, token: &str, games: &mut Vec<PgnGame>) {
+        if let Some(nag) = token.strip_prefix('$') {
+            if let (Ok(nag), Some(last)) = (nag.parse(), self.current().moves.last_mut()) {
+                last.nags.push(nag);
+            }
+            return;
+        }
+        if RESULTS.contains(&token) {
+            // Results inside variations are ignored
+            if self.lines.len() == 1 {
+                self.game.result = token.to_string();
+                self.finish(games);
+            }
+            return;
+        }
+
+        // Move numbers, also when glued to the move: "12.", "12...", "12.Nf3"
+        let mut san = strip_move_number(token);
+        let mut nag = None;
+        if let Some(&(suffix, glyph)) = SUFFIXES.iter().find(|(suffix, _)| san.ends_with(suffix)) {
+            san = &san[..san.len() - suffix.len()];
+            nag = Some(glyph);
+        }
+        let line = self.current();
+        if san.is_empty() {
+            // A suffix annotation written apart from its move
+            if let (Some(glyph), Some(last)) = (nag, line.moves.last_mut()) {
+                last.nags.push(glyph);
+            }
+            return;
+        }
+        let mut pgn_move = PgnMove::new(san);
+        pgn_move.nags.extend(nag);
+        pgn_move.comment_before = std::mem::take(&mut line.comment);
+        line.moves.push(pgn_move);
+    }
+
+    // Stores the game and starts a new one
+    fn finish(&mut self, games: &mut Vec<PgnGame>) {
+        while self.lines.len() > 1 {
+            self.close_variation();
+        }
+        let mut done = std::mem::take(&mut self.game);
+        done.moves = std::mem::take(&mut self.lines[0].moves);
+        self.lines[0].comment.clear();
+        if done.result.is_empty() {
+            done.result = done.tag("Result").unwrap_or("*").to_string();
+        }
+        games.push(done);
+    }
+}
+
+// Splits `Name "Value"`, undoing the escapes in the value
+fn parse_tag(tag: &str) -> Option<(String, String)> {
+    let (name, value) = tag.trim().split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    Some((
+        name.to_string(),
+        value.replace("\\\"", "\"").replace("\\\\", "\\"),
+    ))
+}
+
+// Drops a leading move number (`12.`, `12...`) from a token. Digits without a
+// period are kept, so castling written with zeros (`0-0`) stays intact.
+fn strip_move_number(token: &str) -> &str {
+    let digits = token.len() - token.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    match token[digits..].trim_start_matches('.') {
+        rest if digits > 0 && rest.len() < token.len() - digits => rest,
+        _ => token,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_one(text: &str) -> PgnGame {
+        let mut games = read_games(text);
+        assert_eq!(games.len(), 1);
+        games.remove(0)
+    }
+
+    #[test]
+    fn castling_with_zeros() {
+        let game = read_one("1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. 0-0 Nf6 5.d3 0-0-0 *");
+        assert_eq!(game.moves[6].san, "0-0");
+        let replay = game.replay().unwrap();
+        assert_eq!(replay.plies.len(), 9);
+        assert_eq!(
+            replay.error.as_deref(),
+            Some("5... 0-0-0 is not a legal move")
+        );
+    }
+
+    #[test]
+    fn comments_nags_and_variations() {
+        let game = read_one(
+            "[Event \"Test\"]\n\n{Opening} 1. e4 {best by test} e5 $2 (1... c5 2. Nf3 (2. c3)) 2. Nf3!? 1-0",
+        );
+        assert_eq!(game.tag("Event"), Some("Test"));
+        assert_eq!(game.result, "1-0");
+
+        let moves = &game.moves;
+        assert_eq!(moves.len(), 3);
+        assert_eq!(moves[0].comment_before, "Opening");
+        assert_eq!(moves[0].comment, "best by test");
+        assert_eq!(moves[1].nags, [2]);
+        assert_eq!(moves[2].san, "Nf3");
+        assert_eq!(moves[2].nags, [5]);
+
+        let variation = &moves[1].variations[0];
+        assert_eq!(variation.len(), 2);
+        assert_eq!(variation[0].san, "c5");
+        assert_eq!(variation[1].variations[0][0].san, "c3");
+
+        let mut plies = 0;
+        game.walk(|_, _| plies += 1).unwrap();
+        assert_eq!(plies, 6);
+    }
+
+    #[test]
+    fn written_games_read_back_the_same() {
+        let text = "1. e4 {best by test} e5 $2 (1... c5 2. Nf3) 2. Nf3 $5 *";
+        let game = read_one(text);
+        assert_eq!(read_one(&game.to_pgn()).to_pgn(), game.to_pgn());
+    }
+}
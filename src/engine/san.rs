@@ -0,0 +1,250 @@
+//! # Module: `san`
+//!
+//! **Standard Algebraic Notation**, the move format of PGN files: `e4`, `Nf3`,
+//! `exd5`, `Rad1`, `O-O`, `e8=Q+`.
+//!
+//! A SAN move only names the moving piece and its destination, plus as much of
+//! the origin square as is needed to tell two candidate pieces apart. It can
+//! therefore only be read against a position: [`parse_san`] matches it with the
+//! legal moves and accepts it if exactly one fits, and [`to_san`] adds only as
+//! much of the origin as the other legal moves require.
+
+use crate::board::{Bitboards, Color, PieceType};
+use crate::engine::search::opposite;
+use crate::make_move;
+use crate::movegen::Move;
+
+/// Finds the legal move a SAN string describes.
+///
+/// Check and mate markers (`+`, `#`), annotations (`!`, `?`) and a trailing
+/// `e.p.` are ignored, castling may be written with letters or zeros (`O-O`,
+/// `0-0-0`) and the promotion piece in either case (`e8=Q`, `e8q`).
+///
+/// # Arguments
+///
+/// * `san` - The move, e.g. `Nbd7` or `exd8=Q#`.
+/// * `board` - A reference to the current [`Bitboards`] state.
+/// * `color` - The side making the move.
+///
+/// # Returns
+///
+/// The move, or `None` if the text is malformed, matches no legal move or more
+/// than one, or is an underpromotion (the engine always promotes to a Queen).
+pub fn parse_san(san: &str, board: &Bitboards, color: Color) -> Option<Move> {
+    let san = san.trim();
+    let san = san.strip_suffix("e.p.").unwrap_or(san).trim_end();
+    let san = san.trim_end_matches(['+', '#', '!', '?']);
+    let legal = make_move::generate_legal_moves(board, color);
+
+    if matches!(san, "O-O" | "0-0" | "O-O-O" | "0-0-0") {
+        let kingside = san.len() == 3;
+        return legal
+            .into_iter()
+            .find(|mv| mv.is_castling && (mv.to % 8 == 6) == kingside);
+    }
+
+    let (piece, rest) = match san.chars().next()? {
+        'N' => (PieceType::Knight, &san[1..]),
+        'B' => (PieceType::Bishop, &san[1..]),
+        'R' => (PieceType::Rook, &san[1..]),
+        'Q' => (PieceType::Queen, &san[1..]),
+        'K' => (PieceType::King, &san[1..]),
+        _ => (PieceType::Pawn, san),
+    };
+
+    // Promotion suffix: "e8=Q", also seen without the '=' or in lower case
+    let (rest, promotion) = match rest.char_indices().last()? {
+        (i, letter @ ('Q' | 'R' | 'B' | 'N' | 'q' | 'r' | 'b' | 'n')) => {
+            if !letter.eq_ignore_ascii_case(&'q') {
+                return None;
+            }
+            (rest[..i].trim_end_matches('='), true)
+        }
+        _ => (rest, false),
+    };
+
+    if rest.len() < 2 || !rest.is_ascii() {
+        return None;
+    }
+    let (origin, to) = rest.split_at(rest.len() - 2);
+    let to = square(to)?;
+    let origin = origin.trim_end_matches('x');
+    let mut from_file = None;
+    let mut from_rank = None;
+    for c in origin.chars() {
+        match c {
+            'a'..='h' => from_file = Some(c as u8 - b'a'),
+            '1'..='8' => from_rank = Some(c as u8 - b'1'),
+            _ => return None,
+        }
+    }
+
+    let mut candidates = legal.into_iter().filter(|mv| {
+        mv.piece == piece
+            && mv.to == to
+            && !mv.is_castling
+            && from_file.is_none_or(|file| mv.from % 8 == file)
+            && from_rank.is_none_or(|rank| mv.from / 8 == rank)
+            && (!promotion || mv.promotion_rights)
+    });
+    let mv = candidates.next()?;
+    candidates.next().is_none().then_some(mv)
+}
+
+/// Writes a legal move in SAN, e.g. `Nbd7`, `exd6` or `e8=Q#`.
+///
+/// # Arguments
+///
+/// * `mv` - The move, legal in `board`.
+/// * `board` - A reference to the current [`Bitboards`] state.
+/// * `color` - The side making the move.
+///
+/// # Returns
+///
+/// The move with the origin file, rank or square where another piece of the
+/// same kind could also reach the destination, and a `+` or `#` suffix when it
+/// gives check or mate.
+pub fn to_san(mv: &Move, board: &Bitboards, color: Color) -> String {
+    let mut san = if mv.is_castling {
+        if mv.to % 8 == 6 { "O-O" } else { "O-O-O" }.to_string()
+    } else {
+        let mut san = String::new();
+        if mv.piece == PieceType::Pawn {
+            if mv.is_capture {
+                san.push(file_name(mv.from));
+            }
+        } else {
+            san.push(piece_letter(mv.piece));
+            let rivals: Vec<Move> = make_move::generate_legal_moves(board, color)
+                .into_iter()
+                .filter(|other| {
+                    other.piece == mv.piece && other.to == mv.to && other.from != mv.from
+                })
+                .collect();
+            if !rivals.is_empty() {
+                if rivals.iter().all(|other| other.from % 8 != mv.from % 8) {
+                    san.push(file_name(mv.from));
+                } else if rivals.iter().all(|other| other.from / 8 != mv.from / 8) {
+                    san.push(rank_name(mv.from));
+                } else {
+                    san.push(file_name(mv.from));
+                    san.push(rank_name(mv.from));
+                }
+            }
+        }
+        if mv.is_capture {
+            san.push('x');
+        }
+        san.push(file_name(mv.to));
+        san.push(rank_name(mv.to));
+        if mv.promotion_rights {
+            san.push_str("=Q");
+        }
+        san
+    };
+
+    let mut after = board.clone();
+    make_move::apply_move(&mut after, mv, color);
+    let opponent = opposite(color);
+    if make_move::is_check(&after, opponent) {
+        let mated = make_move::generate_legal_moves(&after, opponent).is_empty();
+        san.push(if mated { '#' } else { '+' });
+    }
+    san
+}
+
+fn piece_letter(piece: PieceType) -> char {
+    match piece {
+        PieceType::Pawn => 'P',
+        PieceType::Knight => 'N',
+        PieceType::Bishop => 'B',
+        PieceType::Rook => 'R',
+        PieceType::Queen => 'Q',
+        PieceType::King => 'K',
+    }
+}
+
+fn file_name(square: u8) -> char {
+    (b'a' + square % 8) as char
+}
+
+fn rank_name(square: u8) -> char {
+    (b'1' + square / 8) as char
+}
+
+// Index of a square written like "e4"
+fn square(name: &str) -> Option<u8> {
+    let bytes = name.as_bytes();
+    let file = bytes.first()?.checked_sub(b'a').filter(|&f| f < 8)?;
+    let rank = bytes.get(1)?.checked_sub(b'1').filter(|&r| r < 8)?;
+    Some(rank * 8 + file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::parse_fen;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    fn position(fen: &str) -> (Bitboards, Color) {
+        let board = parse_fen::board_from_fen(fen).expect("valid FEN");
+        (board, parse_fen::side_to_move(fen).expect("valid FEN"))
+    }
+
+    // The SAN of the legal move from `from` to `to`, both in UCI
+    fn san_of(fen: &str, uci: &str) -> String {
+        let (board, color) = position(fen);
+        let mv = make_move::generate_legal_moves(&board, color)
+            .into_iter()
+            .find(|mv| Move::move_to_uci(mv) == uci)
+            .expect("legal move");
+        to_san(&mv, &board, color)
+    }
+
+    #[test]
+    fn every_legal_move_round_trips() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            KIWIPETE,
+            "r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 0 2",
+        ] {
+            let (board, color) = position(fen);
+            for mv in make_move::generate_legal_moves(&board, color) {
+                let san = to_san(&mv, &board, color);
+                let parsed = parse_san(&san, &board, color).expect(&san);
+                assert_eq!(
+                    Move::move_to_uci(&parsed),
+                    Move::move_to_uci(&mv),
+                    "{}",
+                    san
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn disambiguates_by_file_rank_or_square() {
+        assert_eq!(san_of("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1", "b1d2"), "Nbd2");
+        assert_eq!(san_of("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", "a1a3"), "R1a3");
+        assert_eq!(
+            san_of("k7/8/8/8/8/2Q1Q3/8/2Q1K3 w - - 0 1", "c3d2"),
+            "Qc3d2"
+        );
+        assert_eq!(san_of(KIWIPETE, "e2a6"), "Bxa6");
+    }
+
+    #[test]
+    fn castling_and_en_passant() {
+        let fen = "r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 0 2";
+        assert_eq!(san_of(fen, "e1g1"), "O-O");
+        assert_eq!(san_of(fen, "e1c1"), "O-O-O");
+        assert_eq!(san_of(fen, "e5d6"), "exd6");
+
+        let (board, color) = position(fen);
+        for (san, uci) in [("0-0", "e1g1"), ("0-0-0", "e1c1"), ("exd6 e.p.", "e5d6")] {
+            let mv = parse_san(san, &board, color).expect(san);
+            assert_eq!(Move::move_to_uci(&mv), uci);
+        }
+    }
+}
@@ -1,26 +1,137 @@
 use crate::board::{Bitboards, Color};
 use crate::book;
+use crate::engine::dtm;
+use crate::engine::endgame;
 use crate::engine::game_over;
+use crate::engine::san;
+use crate::engine::see;
+use crate::engine::syzygy;
 use crate::evaluation::evaluation;
 use crate::make_move;
 use crate::movegen::Move;
 use indicatif::{ProgressBar, ProgressStyle};
-use std::collections::HashMap;
+
+/// Score of a checkmate, from the point of view of the side giving mate.
+/// Search scores a mate `N` plies from the root as `MATE_SCORE - N`.
+pub const MATE_SCORE: i32 = 10_000;
+
+// Deepest iteration of `search_nodes`, in practice the node limit stops it first
+const MAX_DEPTH: u32 = 64;
+
+/// Scores beyond this bound are mates, found by search or read from a DTM table
+/// probed anywhere in the tree.
+pub const MATE_BOUND: i32 = MATE_SCORE - dtm::MAX_PLIES as i32 - MAX_DEPTH as i32;
+
+/// Node budget of one search. Interactive play does not limit nodes.
+struct SearchContext {
+    nodes: u64,
+    node_limit: u64,
+}
+
+impl SearchContext {
+    fn unlimited() -> Self {
+        Self::with_node_limit(u64::MAX)
+    }
+
+    fn with_node_limit(node_limit: u64) -> Self {
+        Self {
+            nodes: 0,
+            node_limit,
+        }
+    }
+
+    // Counts a visited node and reports whether the budget is spent
+    fn visit(&mut self) -> bool {
+        self.nodes += 1;
+        self.aborted()
+    }
+
+    fn aborted(&self) -> bool {
+        self.nodes > self.node_limit
+    }
+}
 
 pub fn opposite(color: Color) -> Color {
-    let opposite = match color {
+    match color {
         Color::White => Color::Black,
         Color::Black => Color::White,
-    };
-    opposite
+    }
 }
-fn negamax(board: &Bitboards, depth: u32, color: Color, mut alpha: i32, beta: i32) -> i32 {
+/// Resolves captures at the leaves so that the static evaluation is never taken
+/// in the middle of an exchange.
+///
+/// Captures whose Static Exchange Evaluation is negative are skipped: they cannot
+/// raise the score above the stand-pat value, and they make up most of the tree.
+fn quiescence(
+    ctx: &mut SearchContext,
+    board: &Bitboards,
+    color: Color,
+    mut alpha: i32,
+    beta: i32,
+) -> i32 {
+    if ctx.visit() {
+        return 0;
+    }
+    let stand_pat = evaluation(board, color);
+    if stand_pat >= beta {
+        return stand_pat;
+    }
+    alpha = alpha.max(stand_pat);
+
+    let mut best = stand_pat;
+    for mv in Move::generate_moves_for_side(color, board) {
+        if !mv.is_capture && !mv.promotion_rights {
+            continue;
+        }
+        if !see::see(board, &mv, color, 0) {
+            continue;
+        }
+        let mut clone = board.clone();
+        make_move::apply_move(&mut clone, &mv, color);
+        if make_move::is_check(&clone, color) {
+            continue;
+        }
+
+        let score = -quiescence(ctx, &clone, opposite(color), -beta, -alpha);
+
+        best = best.max(score);
+        alpha = alpha.max(score);
+
+        if alpha >= beta {
+            break;
+        }
+    }
+
+    best
+}
+
+fn negamax(
+    ctx: &mut SearchContext,
+    board: &Bitboards,
+    depth: u32,
+    ply: u32,
+    color: Color,
+    mut alpha: i32,
+    beta: i32,
+) -> i32 {
     if depth == 0 {
-        let eval = evaluation(board, color);
-        return eval;
+        return quiescence(ctx, board, color, alpha, beta);
+    }
+    if ctx.visit() {
+        return 0;
+    }
+    // The tables know the result, searching further cannot improve on it
+    if let Some(dtm) = dtm::probe(board, color) {
+        return dtm.score(ply);
+    }
+    if let Some(score) = endgame::kpk_score(board, color) {
+        return score;
+    }
+    if let Some(wdl) = syzygy::probe_wdl(board, color) {
+        return wdl.score();
     }
     if game_over::checkmate(board, color) {
-        return -10_000;
+        return -(MATE_SCORE - ply as i32);
     }
 
     let moves = make_move::generate_legal_moves(board, color);
@@ -34,7 +145,15 @@ fn negamax(board: &Bitboards, depth: u32, color: Color, mut alpha: i32, beta: i3
         make_move::apply_move(&mut clone, &mv, color);
 
         // Flip alpha & beta, and flip color
-        let score = -negamax(&clone, depth - 1, opposite(color), -beta, -alpha);
+        let score = -negamax(
+            ctx,
+            &clone,
+            depth - 1,
+            ply + 1,
+            opposite(color),
+            -beta,
+            -alpha,
+        );
 
         best = best.max(score);
         alpha = alpha.max(score);
@@ -47,50 +166,90 @@ fn negamax(board: &Bitboards, depth: u32, color: Color, mut alpha: i32, beta: i3
     best
 }
 
+/// Where a move chosen by [`best_move`] came from.
+#[derive(Clone, Copy, Debug)]
+pub enum MoveSource {
+    Book,
+    Tablebase,
+    /// The search, with its score for the side to move.
+    Search(i32),
+}
+
 pub fn best_move(
     board: &mut Bitboards,
     depth: u32,
     color: Color,
-    fen: &str,
-    book: &HashMap<String, HashMap<String, book::MoveEntry>>,
+    book: &book::Book,
+    book_options: &book::BookOptions,
     move_count: u8,
-) -> Option<Move> {
+) -> Option<(Move, MoveSource)> {
     let moves = Move::generate_moves_for_side(color, board);
     if moves.is_empty() {
         return None;
     }
 
-    if move_count < 11 {
-        println!("{}", fen);
-        if let Some(opening_move) = book::opening(book, fen) {
-            let mut board_clone = board.clone();
-            make_move::apply_move(&mut board_clone, &opening_move, color);
-            println!("Board before move:");
-            Bitboards::render_board(&board);
-            println!("After move:");
-            Bitboards::render_board(&board_clone);
-            return Some(opening_move);
-        }
+    if u32::from(move_count) <= book_options.max_depth
+        && let Some(opening_move) = book::opening(book, board, color, book_options)
+    {
+        println!("Book move: {}", san::to_san(&opening_move, board, color));
+        let mut board_clone = board.clone();
+        make_move::apply_move(&mut board_clone, &opening_move, color);
+        println!("Board before move:");
+        Bitboards::render_board(board);
+        println!("After move:");
+        Bitboards::render_board(&board_clone);
+        return Some((opening_move, MoveSource::Book));
+    }
+
+    // Tablebase path: play the move that mates fastest, or converts fastest
+    if let Some((mv, dtm)) = dtm::root_move(board, color) {
+        println!(
+            "DTM table move ({:?}): {}",
+            dtm,
+            san::to_san(&mv, board, color)
+        );
+        return Some((mv, MoveSource::Tablebase));
+    }
+    if let Some((mv, wdl)) = syzygy::root_move(board, color) {
+        println!(
+            "Tablebase move ({:?}): {}",
+            wdl,
+            san::to_san(&mv, board, color)
+        );
+        return Some((mv, MoveSource::Tablebase));
     }
 
     // Search path
+    #[cfg(feature = "nnue")]
+    crate::engine::nnue::refresh(board);
+
     let pb = ProgressBar::new_spinner();
     pb.set_style(ProgressStyle::with_template("{spinner} {msg}").unwrap());
     pb.enable_steady_tick(std::time::Duration::from_millis(100));
     pb.set_message("Evaluating position...");
 
+    let mut ctx = SearchContext::unlimited();
     let mut best_score = i32::MIN;
     let mut best_move_search = None;
 
     for mv in moves {
         let mut clone = board.clone();
         make_move::apply_move(&mut clone, &mv, color);
-        let score = -negamax(&clone, depth - 1, opposite(color), i32::MIN + 1, i32::MAX);
+        let score = -negamax(
+            &mut ctx,
+            &clone,
+            depth - 1,
+            1,
+            opposite(color),
+            i32::MIN + 1,
+            i32::MAX,
+        );
         if score > best_score {
             best_score = score;
             best_move_search = Some(mv);
         }
-        if score == 10_000 {
+        // Mate in one, nothing can be faster
+        if score == MATE_SCORE - 1 {
             break;
         }
         pb.inc(1);
@@ -99,11 +258,78 @@ pub fn best_move(
     // print board preview for chosen move
     let mut board_clone = board.clone();
     if let Some(ref mv) = best_move_search {
+        let san = san::to_san(mv, board, color);
         make_move::apply_move(&mut board_clone, mv, color);
         println!("Board before move:");
         Bitboards::render_board(&board_clone);
-        println!("Engine's choice:");
-        println!("{:?}", mv)
+        println!("Engine's choice: {}", san);
     }
-    best_move_search
+    best_move_search.map(|mv| (mv, MoveSource::Search(best_score)))
+}
+
+/// Searches without book, progress bar or output, deepening one ply at a time
+/// until `node_limit` nodes have been visited.
+///
+/// # Arguments
+///
+/// * `board` - The position to search.
+/// * `color` - The side to move.
+/// * `node_limit` - Number of nodes after which the search stops.
+///
+/// # Returns
+///
+/// The best move of the deepest fully searched depth and its score from the
+/// point of view of `color`, or `None` if `color` has no legal move. If not even
+/// depth 1 could be completed, the first legal move is returned with the static
+/// evaluation.
+pub fn search_nodes(board: &Bitboards, color: Color, node_limit: u64) -> Option<(Move, i32)> {
+    let mut moves = make_move::generate_legal_moves(board, color);
+    if moves.is_empty() {
+        return None;
+    }
+
+    // Positions below the root update the network incrementally
+    #[cfg(feature = "nnue")]
+    let board = &{
+        let mut root = board.clone();
+        crate::engine::nnue::refresh(&mut root);
+        root
+    };
+
+    let mut ctx = SearchContext::with_node_limit(node_limit);
+    let mut best = (moves[0].clone(), evaluation(board, color));
+
+    for depth in 1..=MAX_DEPTH {
+        let mut alpha = i32::MIN + 1;
+        let mut best_index = 0;
+        for (i, mv) in moves.iter().enumerate() {
+            let mut clone = board.clone();
+            make_move::apply_move(&mut clone, mv, color);
+            let score = -negamax(
+                &mut ctx,
+                &clone,
+                depth - 1,
+                1,
+                opposite(color),
+                i32::MIN + 1,
+                -alpha,
+            );
+            if ctx.aborted() {
+                return Some(best);
+            }
+            if score > alpha {
+                alpha = score;
+                best_index = i;
+            }
+        }
+
+        // The best move is searched first in the next iteration
+        moves.swap(0, best_index);
+        best = (moves[0].clone(), alpha);
+        if alpha.abs() >= MATE_BOUND {
+            break;
+        }
+    }
+
+    Some(best)
 }
@@ -0,0 +1,217 @@
+//! # Module: `see`
+//!
+//! Static Exchange Evaluation (SEE) answers a simple question: *if both sides keep
+//! capturing on one square, always with their cheapest piece, who comes out ahead?*
+//!
+//! MVV-LVA ordering only looks at the first capture, so `PxQ` and `QxP` are easy to
+//! rank, but it cannot tell a free pawn from a pawn defended twice. SEE plays the
+//! whole exchange out on bitboards, without touching the board state.
+//!
+//! ## How it works
+//!
+//! 1. The moving piece is lifted from the occupancy and the captured value is
+//!    written into a "swap list".
+//! 2. The sides alternate, each time picking the **least valuable attacker** of
+//!    the target square. Every lifted piece may uncover an **x-ray** attacker
+//!    (a Rook behind a Rook, a Bishop behind a Queen…), which is picked up by
+//!    recomputing [`attackers_to`] with the reduced occupancy.
+//! 3. The swap list is folded backwards: at every step a side may decline to
+//!    recapture, so each entry becomes the best of "stop here" and "continue".
+//!
+//! ## Known Simplifications
+//!
+//! * Pins are ignored, as in most engines.
+//! * A King only joins the exchange when the opponent has no attackers left.
+//! * Pawns reaching the last rank, first mover or recapturer, always become Queens.
+
+use crate::board::{Bitboards, Color, PieceType};
+use crate::engine::make_move::attackers_to;
+use crate::movegen::Move;
+use crate::search::opposite;
+
+/// Finds the cheapest piece of `color` attacking `sq` with the given occupancy.
+///
+/// # Returns
+///
+/// The square and type of that attacker, or `None` if `color` has no attackers.
+pub fn least_valuable_attacker(
+    board: &Bitboards,
+    sq: u8,
+    color: Color,
+    occupancy: u64,
+) -> Option<(u8, PieceType)> {
+    let attackers = attackers_to(board, sq, occupancy);
+    for piece in PieceType::pieces() {
+        let candidates = attackers & board.boards[color as usize][piece as usize];
+        if candidates != 0 {
+            return Some((candidates.trailing_zeros() as u8, piece));
+        }
+    }
+    None
+}
+
+/// Computes the material outcome of the capture sequence started by `mv`.
+///
+/// # Arguments
+///
+/// * `board` - The position *before* `mv` is played.
+/// * `mv` - The move starting the exchange (usually a capture).
+/// * `color` - The side playing `mv`.
+///
+/// # Returns
+///
+/// The expected material gain for `color` in centipawns. Positive values mean
+/// the exchange wins material, negative values mean it loses material.
+pub fn see_value(board: &Bitboards, mv: &Move, color: Color) -> i32 {
+    let pawn_value = Move::get_piece_value(PieceType::Pawn);
+    let queen_value = Move::get_piece_value(PieceType::Queen);
+
+    let mut occupancy = Bitboards::occupancy(board) & !(1u64 << mv.from);
+    let mut gain = [0i32; 32];
+
+    gain[0] = match Bitboards::piece_at(board, mv.to) {
+        Some((owner, piece)) if owner != color => Move::get_piece_value(piece),
+        Some(_) => 0,
+        None if mv.piece == PieceType::Pawn && board.en_passant_square == Some(mv.to) => {
+            // The captured pawn stands behind the target square
+            let captured_sq = if color == Color::White {
+                mv.to - 8
+            } else {
+                mv.to + 8
+            };
+            occupancy &= !(1u64 << captured_sq);
+            pawn_value
+        }
+        None => 0,
+    };
+
+    // Value of the piece currently standing on the target square
+    let mut on_square = if mv.promotion_rights {
+        gain[0] += queen_value - pawn_value;
+        queen_value
+    } else {
+        Move::get_piece_value(mv.piece)
+    };
+
+    let mut side = opposite(color);
+    let mut depth = 0;
+
+    while depth + 1 < gain.len() {
+        let Some((sq, piece)) = least_valuable_attacker(board, mv.to, side, occupancy) else {
+            break;
+        };
+        let lifted = occupancy & !(1u64 << sq);
+
+        // A King may only recapture if the square is no longer defended
+        if piece == PieceType::King
+            && least_valuable_attacker(board, mv.to, opposite(side), lifted).is_some()
+        {
+            break;
+        }
+
+        depth += 1;
+        gain[depth] = on_square - gain[depth - 1];
+        // A pawn recapturing onto the last rank promotes on the spot
+        on_square = if piece == PieceType::Pawn && (mv.to / 8 == 0 || mv.to / 8 == 7) {
+            gain[depth] += queen_value - pawn_value;
+            queen_value
+        } else {
+            Move::get_piece_value(piece)
+        };
+        occupancy = lifted;
+        side = opposite(side);
+    }
+
+    // Each side may stop capturing whenever continuing would lose material
+    while depth > 0 {
+        gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+        depth -= 1;
+    }
+
+    gain[0]
+}
+
+/// Returns `true` if the exchange started by `mv` gains at least `threshold`
+/// centipawns for `color`.
+///
+/// `see(board, mv, color, 0)` is the usual "is this capture safe?" test used by
+/// move ordering and quiescence pruning.
+pub fn see(board: &Bitboards, mv: &Move, color: Color, threshold: i32) -> bool {
+    see_value(board, mv, color) >= threshold
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a position from `(color, piece, square)` triples, with both Kings
+    /// tucked away on g1 and h8.
+    fn position(pieces: &[(Color, PieceType, u8)]) -> Bitboards {
+        let mut board = Bitboards::new();
+        Bitboards::add_piece(&mut board, Color::White, PieceType::King, 6);
+        Bitboards::add_piece(&mut board, Color::Black, PieceType::King, 63);
+        for &(color, piece, square) in pieces {
+            Bitboards::add_piece(&mut board, color, piece, square);
+        }
+        board
+    }
+
+    fn capture(from: u8, to: u8, piece: PieceType) -> Move {
+        Move {
+            from,
+            to,
+            piece,
+            promotion_rights: false,
+            is_castling: false,
+            is_capture: true,
+        }
+    }
+
+    #[test]
+    fn rook_takes_undefended_pawn() {
+        let board = position(&[
+            (Color::White, PieceType::Rook, 4),
+            (Color::Black, PieceType::Pawn, 36),
+        ]);
+        let mv = capture(4, 36, PieceType::Rook);
+        assert_eq!(see_value(&board, &mv, Color::White), 100);
+    }
+
+    #[test]
+    fn rook_takes_defended_pawn() {
+        let board = position(&[
+            (Color::White, PieceType::Rook, 4),
+            (Color::Black, PieceType::Pawn, 36),
+            (Color::Black, PieceType::Pawn, 43),
+        ]);
+        let mv = capture(4, 36, PieceType::Rook);
+        assert_eq!(see_value(&board, &mv, Color::White), 100 - 500);
+    }
+
+    #[test]
+    fn en_passant_wins_the_pawn() {
+        let mut board = position(&[
+            (Color::White, PieceType::Pawn, 36),
+            (Color::Black, PieceType::Pawn, 35),
+        ]);
+        board.en_passant_square = Some(43);
+        let mv = capture(36, 43, PieceType::Pawn);
+        assert_eq!(see_value(&board, &mv, Color::White), 100);
+    }
+
+    #[test]
+    fn pawn_recapture_on_last_rank_promotes() {
+        // Qxc8 Rxc8 bxc8=Q: the Rook dare not recapture, so the Bishop is won
+        let board = position(&[
+            (Color::White, PieceType::Queen, 2),
+            (Color::White, PieceType::Pawn, 49),
+            (Color::Black, PieceType::Bishop, 58),
+            (Color::Black, PieceType::Rook, 59),
+        ]);
+        let mv = capture(2, 58, PieceType::Queen);
+        assert_eq!(
+            see_value(&board, &mv, Color::White),
+            Move::get_piece_value(PieceType::Bishop)
+        );
+    }
+}
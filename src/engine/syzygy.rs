@@ -0,0 +1,1384 @@
+//! # Module: `syzygy`
+//!
+//! Probing of **Syzygy endgame tablebases** stored on disk. Once few enough
+//! pieces are left, the tables replace guesswork with perfect knowledge:
+//!
+//! * **WDL tables** (`.rtbw`) tell whether the side to move wins, draws or loses.
+//!   [`negamax`](crate::engine::search) probes them to cut off whole subtrees.
+//! * **DTZ tables** (`.rtbz`) give the distance to the next *zeroing* move (a
+//!   capture or pawn move, which resets the fifty-move counter). At the root,
+//!   [`root_move`] uses them to pick the move that makes progress fastest, so a
+//!   won ending is actually converted.
+//!
+//! ## Setup
+//!
+//! [`init`] is called once with the `SyzygyPath` option (`--syzygy-path`): one or
+//! more directories separated like the `PATH` variable. Every table found is
+//! memory mapped; the operating system only reads the parts that are probed.
+//!
+//! ## Limits
+//!
+//! * Tables do not contain positions with castling rights, those are not probed.
+//! * The engine only promotes to a Queen, so underpromotions are never
+//!   considered when resolving captures and pawn moves around a probe.
+//! * The fifty-move rule is not tracked in search: "cursed" wins and "blessed"
+//!   losses (won or lost, but not within fifty moves) score as draws.
+//!
+//! ## File Format
+//!
+//! The decoder follows the reference implementation by Ronald de Man, as found in
+//! Stockfish. A position is mapped to an index from the squares of its pieces
+//! (using board symmetries to keep the tables small), and the value at that index
+//! is decompressed from blocks of canonical Huffman codes whose symbols expand
+//! into pairs of smaller symbols ("recursive pairing").
+
+use crate::board::{Bitboards, Color, PieceType};
+use crate::engine::make_move::{self, king_attacks};
+use crate::movegen::Move;
+use crate::search::opposite;
+use memmap2::Mmap;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::ops::Neg;
+use std::path::Path;
+use std::sync::{LazyLock, OnceLock};
+
+/// Score of a tablebase win, below mate scores and above any evaluation.
+pub const TB_WIN: i32 = 5_000;
+
+// Most pieces a table can hold
+const MAX_PIECES: usize = 7;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+// Table header flags
+const HAS_PAWNS: u8 = 2;
+
+// Flags of one sub-table
+const STM: u8 = 1;
+const MAPPED: u8 = 2;
+const WIN_PLIES: u8 = 4;
+const LOSS_PLIES: u8 = 8;
+const WIDE: u8 = 16;
+const SINGLE_VALUE: u8 = 128;
+
+/// Game theoretic result from the point of view of the side to move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss = -2,
+    /// Lost, but drawn by the fifty-move rule.
+    BlessedLoss = -1,
+    Draw = 0,
+    /// Won, but drawn by the fifty-move rule.
+    CursedWin = 1,
+    Win = 2,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Self {
+        match value {
+            ..=-2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+
+    /// The search score of the result; only real wins and losses count.
+    pub fn score(self) -> i32 {
+        match self {
+            Wdl::Win => TB_WIN,
+            Wdl::Loss => -TB_WIN,
+            _ => 0,
+        }
+    }
+
+    fn signum(self) -> i32 {
+        (self as i32).signum()
+    }
+}
+
+impl Neg for Wdl {
+    type Output = Wdl;
+    fn neg(self) -> Wdl {
+        Wdl::from_value(-(self as i32))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Wdl,
+    Dtz,
+}
+
+/// All tables found in the `SyzygyPath` directories, by material (e.g. `KRvK`).
+struct Tablebases {
+    wdl: HashMap<String, Table>,
+    dtz: HashMap<String, Table>,
+    max_pieces: u32,
+}
+
+static TABLEBASES: OnceLock<Tablebases> = OnceLock::new();
+
+/// Opens every table in the given directories.
+///
+/// # Arguments
+///
+/// * `path` - One or more directories, separated by `:` (`;` on Windows).
+///
+/// # Returns
+///
+/// The number of tables found, or the error of a directory or table that could
+/// not be read.
+pub fn init(path: &str) -> io::Result<usize> {
+    let mut wdl = HashMap::new();
+    let mut dtz = HashMap::new();
+    for dir in std::env::split_paths(path) {
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let (tables, kind) = match path.extension().and_then(|ext| ext.to_str()) {
+                Some("rtbw") => (&mut wdl, Kind::Wdl),
+                Some("rtbz") => (&mut dtz, Kind::Dtz),
+                _ => continue,
+            };
+            if let Some(table) = Table::open(&path, name, kind)? {
+                tables.insert(name.to_string(), table);
+            }
+        }
+    }
+
+    let max_pieces = wdl
+        .values()
+        .map(|table| table.piece_count as u32)
+        .max()
+        .unwrap_or(0);
+    let count = wdl.len() + dtz.len();
+    TABLEBASES
+        .set(Tablebases {
+            wdl,
+            dtz,
+            max_pieces,
+        })
+        .map_err(|_| io::Error::other("tablebases are already loaded"))?;
+    Ok(count)
+}
+
+/// Probes the WDL tables.
+///
+/// # Returns
+///
+/// The result for `color` to move, or `None` if no tablebase covers the position.
+pub fn probe_wdl(board: &Bitboards, color: Color) -> Option<Wdl> {
+    let tablebases = TABLEBASES.get()?;
+    if !tablebases.covers(board) {
+        return None;
+    }
+    tablebases.search(board, color, false).map(|(wdl, _)| wdl)
+}
+
+/// Picks the root move that keeps the best result and reaches the next zeroing
+/// move fastest.
+///
+/// # Returns
+///
+/// The move and the result it keeps for `color`, or `None` if no tablebase covers
+/// the position (or there is no legal move).
+pub fn root_move(board: &Bitboards, color: Color) -> Option<(Move, Wdl)> {
+    let tablebases = TABLEBASES.get()?;
+    if !tablebases.covers(board) {
+        return None;
+    }
+
+    let mut best: Option<(Move, i32, i32)> = None;
+    for mv in make_move::generate_legal_moves(board, color) {
+        let zeroing = mv.piece == PieceType::Pawn || is_capture(board, &mv, color);
+        let mut next = board.clone();
+        make_move::apply_move(&mut next, &mv, color);
+        let enemy = opposite(color);
+
+        let mut dtz = if zeroing {
+            dtz_before_zeroing(-tablebases.search(&next, enemy, false)?.0)
+        } else {
+            let dtz = -tablebases.dtz(&next, enemy)?;
+            dtz + dtz.signum()
+        };
+        if dtz == 2 && is_mate(&next, enemy) {
+            dtz = 1;
+        }
+
+        // Quick wins first, then draws, then slow losses
+        let rank = match dtz {
+            1.. => 10_000 - dtz,
+            0 => 0,
+            _ => -10_000 - dtz,
+        };
+        if best
+            .as_ref()
+            .is_none_or(|&(_, best_rank, _)| rank > best_rank)
+        {
+            best = Some((mv, rank, dtz));
+        }
+    }
+
+    best.map(|(mv, _, dtz)| {
+        let wdl = match dtz {
+            101.. => Wdl::CursedWin,
+            1.. => Wdl::Win,
+            0 => Wdl::Draw,
+            -100..0 => Wdl::Loss,
+            _ => Wdl::BlessedLoss,
+        };
+        (mv, wdl)
+    })
+}
+
+impl Tablebases {
+    fn covers(&self, board: &Bitboards) -> bool {
+        let pieces = Bitboards::occupancy(board).count_ones();
+        pieces <= self.max_pieces
+            && !(board.white_kingside
+                || board.white_queenside
+                || board.black_kingside
+                || board.black_queenside)
+    }
+
+    // Resolves captures (and, with `zeroing`, pawn moves) before probing, since
+    // the tables do not know about en passant and may store "don't care" values
+    // where a capture is best. Also reports whether the best move is zeroing.
+    fn search(&self, board: &Bitboards, color: Color, zeroing: bool) -> Option<(Wdl, bool)> {
+        let moves = make_move::generate_legal_moves(board, color);
+        let mut searched = 0;
+        let mut best = Wdl::Loss;
+
+        for mv in &moves {
+            if !is_capture(board, mv, color) && (!zeroing || mv.piece != PieceType::Pawn) {
+                continue;
+            }
+            searched += 1;
+            let mut next = board.clone();
+            make_move::apply_move(&mut next, mv, color);
+            let value = -self.search(&next, opposite(color), false)?.0;
+            if value > best {
+                best = value;
+                if value == Wdl::Win {
+                    return Some((value, true));
+                }
+            }
+        }
+
+        let all_searched = searched > 0 && searched == moves.len();
+        let value = if all_searched {
+            best
+        } else {
+            self.probe_wdl_table(board, color)?
+        };
+
+        if best >= value {
+            Some((best, best > Wdl::Draw || all_searched))
+        } else {
+            Some((value, false))
+        }
+    }
+
+    // Distance to zeroing in plies, signed by the result for `color`
+    fn dtz(&self, board: &Bitboards, color: Color) -> Option<i32> {
+        let (wdl, zeroing_best) = self.search(board, color, true)?;
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+        if zeroing_best {
+            return Some(dtz_before_zeroing(wdl));
+        }
+
+        if let Some(dtz) = self.probe_dtz_table(board, color, wdl)? {
+            let cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+            return Some((dtz + if cursed { 100 } else { 0 }) * wdl.signum());
+        }
+
+        // The table only stores the other side to move: look one ply ahead
+        let mut min_dtz = i32::MAX;
+        for mv in make_move::generate_legal_moves(board, color) {
+            let zeroing = mv.piece == PieceType::Pawn || is_capture(board, &mv, color);
+            let mut next = board.clone();
+            make_move::apply_move(&mut next, &mv, color);
+            let enemy = opposite(color);
+
+            let mut dtz = if zeroing {
+                -dtz_before_zeroing(self.search(&next, enemy, false)?.0)
+            } else {
+                -self.dtz(&next, enemy)?
+            };
+            if dtz == 1 && is_mate(&next, enemy) {
+                min_dtz = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < min_dtz && dtz.signum() == wdl.signum() {
+                min_dtz = dtz;
+            }
+        }
+        Some(if min_dtz == i32::MAX { -1 } else { min_dtz })
+    }
+
+    fn probe_wdl_table(&self, board: &Bitboards, color: Color) -> Option<Wdl> {
+        if Bitboards::occupancy(board).count_ones() == 2 {
+            return Some(Wdl::Draw);
+        }
+        let (table, black_stronger) = lookup(&self.wdl, board)?;
+        table
+            .probe(board, color, black_stronger, Wdl::Draw)
+            .map(|value| Wdl::from_value(value - 2))
+    }
+
+    // `Some(None)` if the table stores the other side to move
+    fn probe_dtz_table(&self, board: &Bitboards, color: Color, wdl: Wdl) -> Option<Option<i32>> {
+        let (table, black_stronger) = lookup(&self.dtz, board)?;
+        Some(table.probe(board, color, black_stronger, wdl))
+    }
+}
+
+// Finds the table of the material on the board and whether its colors are swapped
+fn lookup<'a>(tables: &'a HashMap<String, Table>, board: &Bitboards) -> Option<(&'a Table, bool)> {
+    let white = side_name(board, Color::White);
+    let black = side_name(board, Color::Black);
+    if let Some(table) = tables.get(&format!("{}v{}", white, black)) {
+        return Some((table, false));
+    }
+    tables
+        .get(&format!("{}v{}", black, white))
+        .map(|table| (table, true))
+}
+
+fn side_name(board: &Bitboards, color: Color) -> String {
+    let mut name = String::from("K");
+    for (piece, letter) in [
+        (PieceType::Queen, 'Q'),
+        (PieceType::Rook, 'R'),
+        (PieceType::Bishop, 'B'),
+        (PieceType::Knight, 'N'),
+        (PieceType::Pawn, 'P'),
+    ] {
+        let count = board.boards[color as usize][piece as usize].count_ones();
+        name.extend(std::iter::repeat_n(letter, count as usize));
+    }
+    name
+}
+
+fn is_capture(board: &Bitboards, mv: &Move, color: Color) -> bool {
+    let enemy = opposite(color) as usize;
+    let occupied = board.boards[enemy]
+        .iter()
+        .any(|&bb| bb & (1u64 << mv.to) != 0);
+    occupied || mv.piece == PieceType::Pawn && board.en_passant_square == Some(mv.to)
+}
+
+fn is_mate(board: &Bitboards, color: Color) -> bool {
+    make_move::is_check(board, color) && make_move::generate_legal_moves(board, color).is_empty()
+}
+
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+        Wdl::Draw => 0,
+    }
+}
+
+/* TABLES */
+
+/// One memory mapped table file.
+struct Table {
+    data: Mmap,
+    kind: Kind,
+    /// Both sides have the same material, e.g. `KRvKR`.
+    symmetric: bool,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    /// Pawns of the leading color, then of the other one.
+    pawn_count: [u32; 2],
+    piece_count: usize,
+    /// Sub-tables by side to move and file of the leading pawn.
+    items: Vec<Vec<PairsData>>,
+    /// Offset of the DTZ value maps.
+    dtz_map: usize,
+}
+
+/// Decoding data of one sub-table.
+#[derive(Clone, Default)]
+struct PairsData {
+    flags: u8,
+    /// Piece codes in the order they are encoded.
+    pieces: [u8; MAX_PIECES],
+    group_len: [usize; MAX_PIECES + 1],
+    group_idx: [u64; MAX_PIECES + 1],
+    block_size: usize,
+    span: u64,
+    num_blocks: usize,
+    min_sym_len: u8,
+    lowest_sym: usize,
+    base64: Vec<u64>,
+    symlen: Vec<u8>,
+    btree: usize,
+    block_length: usize,
+    block_length_size: usize,
+    sparse_index: usize,
+    sparse_index_size: usize,
+    blocks: usize,
+    map_idx: [usize; 4],
+}
+
+impl Table {
+    // `None` if the file name is not a material signature
+    fn open(path: &Path, name: &str, kind: Kind) -> io::Result<Option<Table>> {
+        let Some(counts) = parse_material(name) else {
+            return Ok(None);
+        };
+        let file = File::open(path)?;
+        // SAFETY: table files are only read, and nobody is expected to modify
+        // them while the engine runs.
+        let data = unsafe { Mmap::map(&file)? };
+        let magic = match kind {
+            Kind::Wdl => WDL_MAGIC,
+            Kind::Dtz => DTZ_MAGIC,
+        };
+        if data.len() < 5 || data[..4] != magic {
+            return Err(invalid(path, "not a Syzygy table"));
+        }
+
+        let mut table = Table::new(data, counts, kind);
+        if table.piece_count > MAX_PIECES || table.has_pawns != (table.data[4] & HAS_PAWNS != 0) {
+            return Err(invalid(path, "table does not match its file name"));
+        }
+        table
+            .setup()
+            .ok_or_else(|| invalid(path, "truncated table"))?;
+        Ok(Some(table))
+    }
+
+    // A table whose sub-tables are not read yet
+    fn new(data: Mmap, counts: [[u32; 6]; 2], kind: Kind) -> Table {
+        let has_pawns = counts[0][0] + counts[1][0] > 0;
+        let leading_white = counts[1][0] == 0 || counts[0][0] > 0 && counts[1][0] >= counts[0][0];
+        let pawn_count = if leading_white {
+            [counts[0][0], counts[1][0]]
+        } else {
+            [counts[1][0], counts[0][0]]
+        };
+        Table {
+            data,
+            kind,
+            symmetric: counts[0] == counts[1],
+            has_pawns,
+            has_unique_pieces: counts.iter().any(|side| side[..5].contains(&1)),
+            pawn_count,
+            piece_count: counts.iter().flatten().sum::<u32>() as usize,
+            items: Vec::new(),
+            dtz_map: 0,
+        }
+    }
+
+    // Reads the headers of all sub-tables, `None` if the file is too short
+    fn setup(&mut self) -> Option<()> {
+        let data = &self.data[..];
+        let sides = if self.kind == Kind::Wdl && !self.symmetric {
+            2
+        } else {
+            1
+        };
+        let files = if self.has_pawns { 4 } else { 1 };
+        let both_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut items = vec![vec![PairsData::default(); files]; sides];
+        let mut pos = 5;
+
+        for file in 0..files {
+            let order = [
+                [
+                    data.get(pos)? & 0xF,
+                    if both_pawns {
+                        data.get(pos + 1)? & 0xF
+                    } else {
+                        0xF
+                    },
+                ],
+                [
+                    data.get(pos)? >> 4,
+                    if both_pawns {
+                        data.get(pos + 1)? >> 4
+                    } else {
+                        0xF
+                    },
+                ],
+            ];
+            pos += 1 + usize::from(both_pawns);
+            for k in 0..self.piece_count {
+                let byte = *data.get(pos)?;
+                for (side, item) in items.iter_mut().enumerate() {
+                    item[file].pieces[k] = if side == 1 { byte >> 4 } else { byte & 0xF };
+                }
+                pos += 1;
+            }
+            for (side, item) in items.iter_mut().enumerate() {
+                self.set_groups(&mut item[file], order[side], file);
+            }
+        }
+        pos += pos & 1;
+
+        for file in 0..files {
+            for item in items.iter_mut() {
+                pos = set_sizes(&mut item[file], data, pos)?;
+            }
+        }
+        let mut dtz_map = 0;
+        if self.kind == Kind::Dtz {
+            dtz_map = pos;
+            pos = set_dtz_map(&mut items[0], data, pos)?;
+        }
+        for file in 0..files {
+            for item in items.iter_mut() {
+                item[file].sparse_index = pos;
+                pos += item[file].sparse_index_size * 6;
+            }
+        }
+        for file in 0..files {
+            for item in items.iter_mut() {
+                item[file].block_length = pos;
+                pos += item[file].block_length_size * 2;
+            }
+        }
+        for file in 0..files {
+            for item in items.iter_mut() {
+                pos = (pos + 0x3F) & !0x3F;
+                item[file].blocks = pos;
+                pos += item[file].num_blocks * item[file].block_size;
+            }
+        }
+        if pos > data.len() {
+            return None;
+        }
+
+        self.items = items;
+        self.dtz_map = dtz_map;
+        Some(())
+    }
+
+    // Splits the pieces into groups that are encoded together, and the size of
+    // the index range of each group
+    fn set_groups(&self, d: &mut PairsData, order: [u8; 2], file: usize) {
+        let encoding = &*ENCODING;
+        let mut n = 0;
+        let mut first_len: i32 = if self.has_pawns {
+            0
+        } else if self.has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        d.group_len[0] = 1;
+        for i in 1..self.piece_count {
+            first_len -= 1;
+            if first_len > 0 || d.pieces[i] == d.pieces[i - 1] {
+                d.group_len[n] += 1;
+            } else {
+                n += 1;
+                d.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        d.group_len[n] = 0;
+
+        let both_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = if both_pawns { 2 } else { 1 };
+        let mut free_squares = 64 - d.group_len[0] - if both_pawns { d.group_len[1] } else { 0 };
+        let mut idx: u64 = 1;
+        let mut k = 0;
+        while next < n || k == order[0] as usize || k == order[1] as usize {
+            if k == order[0] as usize {
+                // Leading pawns or pieces
+                d.group_idx[0] = idx;
+                idx *= if self.has_pawns {
+                    encoding.lead_pawns_size[d.group_len[0]][file]
+                } else if self.has_unique_pieces {
+                    31_332
+                } else {
+                    462
+                };
+            } else if k == order[1] as usize {
+                // Remaining pawns
+                d.group_idx[1] = idx;
+                idx *= encoding.binomial[d.group_len[1]][48 - d.group_len[0]];
+            } else {
+                // Remaining pieces
+                d.group_idx[next] = idx;
+                idx *= encoding.binomial[d.group_len[next]][free_squares];
+                free_squares -= d.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        d.group_idx[n] = idx;
+    }
+
+    fn get(&self, stm: usize, file: usize) -> &PairsData {
+        &self.items[stm % self.items.len()][if self.has_pawns { file } else { 0 }]
+    }
+
+    /// Looks up the stored value of a position.
+    ///
+    /// Returns `None` for a DTZ table that only stores the other side to move.
+    fn probe(
+        &self,
+        board: &Bitboards,
+        color: Color,
+        black_stronger: bool,
+        wdl: Wdl,
+    ) -> Option<i32> {
+        let encoding = &*ENCODING;
+        // Tables are stored with the stronger side as White, and symmetric ones
+        // only with White to move: otherwise swap the colors
+        let flip = black_stronger || self.symmetric && color == Color::Black;
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = usize::from(flip) ^ color as usize;
+
+        let mut squares = [0u8; MAX_PIECES];
+        let mut pieces = [0u8; MAX_PIECES];
+        let mut size = 0;
+        let mut lead_pawns = 0u64;
+        let mut lead_pawns_count = 0;
+        let mut tb_file = 0;
+
+        if self.has_pawns {
+            let pawn_color = ((self.get(0, 0).pieces[0] ^ flip_color) >> 3) as usize;
+            lead_pawns = board.boards[pawn_color][PieceType::Pawn as usize];
+            for sq in Bitboards::return_squares(lead_pawns) {
+                squares[size] = sq ^ flip_squares;
+                size += 1;
+            }
+            lead_pawns_count = size;
+            // The leading pawn is the one closest to the edge, then the lowest
+            let lead = (0..lead_pawns_count)
+                .max_by_key(|&i| encoding.map_pawns[squares[i] as usize])
+                .unwrap_or(0);
+            squares.swap(0, lead);
+            let file = squares[0] % 8;
+            tb_file = file.min(7 - file) as usize;
+        }
+
+        if self.kind == Kind::Dtz {
+            let flags = self.get(stm, tb_file).flags;
+            // Symmetric tables without pawns serve both sides to move
+            let both_sides = self.symmetric && !self.has_pawns;
+            if (flags & STM) as usize != stm && !both_sides {
+                return None;
+            }
+        }
+
+        let others = Bitboards::occupancy(board) & !lead_pawns;
+        for sq in Bitboards::return_squares(others) {
+            squares[size] = sq ^ flip_squares;
+            pieces[size] = piece_code(board, sq) ^ flip_color;
+            size += 1;
+        }
+
+        let d = self.get(stm, tb_file);
+        // Same piece order as the table
+        for i in lead_pawns_count..size.saturating_sub(1) {
+            for j in i + 1..size {
+                if d.pieces[i] == pieces[j] {
+                    pieces.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        // The leading piece goes to files a-d
+        if squares[0] % 8 > 3 {
+            squares[..size].iter_mut().for_each(|sq| *sq ^= 7);
+        }
+
+        let mut idx: u64;
+        if self.has_pawns {
+            idx = encoding.lead_pawn_idx[lead_pawns_count][squares[0] as usize];
+            squares[1..lead_pawns_count].sort_by_key(|&sq| encoding.map_pawns[sq as usize]);
+            for (i, &sq) in squares.iter().enumerate().take(lead_pawns_count).skip(1) {
+                idx += encoding.binomial[i][encoding.map_pawns[sq as usize]];
+            }
+        } else {
+            // Without pawns the leading piece also goes to ranks 1-4...
+            if squares[0] / 8 > 3 {
+                squares[..size].iter_mut().for_each(|sq| *sq ^= 56);
+            }
+            // ...and below the a1-h8 diagonal
+            for i in 0..d.group_len[0] {
+                let off = off_diagonal(squares[i]);
+                if off == 0 {
+                    continue;
+                }
+                if off > 0 {
+                    for sq in &mut squares[i..size] {
+                        *sq = ((*sq >> 3) | (*sq << 3)) & 63;
+                    }
+                }
+                break;
+            }
+            idx = if self.has_unique_pieces {
+                encode_unique(&squares)
+            } else {
+                encoding.map_kk[encoding.map_a1d1d4[squares[0] as usize] as usize]
+                    [squares[1] as usize]
+            };
+        }
+
+        // Remaining groups, each in ascending square order
+        idx *= d.group_idx[0];
+        let mut start = d.group_len[0];
+        let mut remaining_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = 1;
+        while d.group_len[next] != 0 {
+            let len = d.group_len[next];
+            squares[start..start + len].sort_unstable();
+            let mut n = 0;
+            for i in 0..len {
+                let sq = squares[start + i];
+                let adjust = squares[..start].iter().filter(|&&other| sq > other).count();
+                let free = sq as usize - adjust - if remaining_pawns { 8 } else { 0 };
+                n += encoding.binomial[i + 1][free];
+            }
+            remaining_pawns = false;
+            idx += n * d.group_idx[next];
+            start += len;
+            next += 1;
+        }
+
+        let value = self.decompress(d, idx);
+        Some(match self.kind {
+            Kind::Wdl => value,
+            Kind::Dtz => self.map_dtz(d, value, wdl),
+        })
+    }
+
+    // Converts a stored DTZ value into plies
+    fn map_dtz(&self, d: &PairsData, mut value: i32, wdl: Wdl) -> i32 {
+        const WDL_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+        if d.flags & MAPPED != 0 {
+            let map_idx = d.map_idx[WDL_MAP[(wdl as i32 + 2) as usize]];
+            value = if d.flags & WIDE != 0 {
+                read_u16(&self.data, self.dtz_map + 2 * (map_idx + value as usize)) as i32
+            } else {
+                self.data[self.dtz_map + map_idx + value as usize] as i32
+            };
+        }
+
+        let in_moves = match wdl {
+            Wdl::Win => d.flags & WIN_PLIES == 0,
+            Wdl::Loss => d.flags & LOSS_PLIES == 0,
+            Wdl::CursedWin | Wdl::BlessedLoss => true,
+            Wdl::Draw => false,
+        };
+        if in_moves {
+            value *= 2;
+        }
+        value + 1
+    }
+
+    // Reads the value at `idx` from the compressed blocks
+    fn decompress(&self, d: &PairsData, idx: u64) -> i32 {
+        if d.flags & SINGLE_VALUE != 0 {
+            return d.min_sym_len as i32;
+        }
+        let data = &self.data[..];
+
+        // The sparse index points close to the block holding `idx`...
+        let k = (idx / d.span) as usize;
+        let entry = d.sparse_index + 6 * k;
+        let mut block = read_u32(data, entry) as usize;
+        let mut offset = read_u16(data, entry + 4) as i64;
+        offset += (idx % d.span) as i64 - (d.span / 2) as i64;
+
+        // ...from where we walk to the right one
+        let block_length = |block: usize| read_u16(data, d.block_length + 2 * block) as i64;
+        while offset < 0 {
+            block -= 1;
+            offset += block_length(block) + 1;
+        }
+        while offset > block_length(block) {
+            offset -= block_length(block) + 1;
+            block += 1;
+        }
+
+        // Walk the Huffman codes of the block until the symbol covering `offset`
+        let mut ptr = d.blocks + block * d.block_size;
+        let mut buf = read_u64_be(data, ptr);
+        ptr += 8;
+        let mut buf_size = 64;
+        let mut sym;
+        loop {
+            let mut len = 0;
+            while len + 1 < d.base64.len() && buf < d.base64[len] {
+                len += 1;
+            }
+            let shift = 64 - len as u32 - d.min_sym_len as u32;
+            sym = (buf - d.base64[len]).checked_shr(shift).unwrap_or(0) as u16;
+            sym = sym.wrapping_add(read_u16(data, d.lowest_sym + 2 * len));
+            if offset < d.symlen[sym as usize] as i64 + 1 {
+                break;
+            }
+            offset -= d.symlen[sym as usize] as i64 + 1;
+            let len = len + d.min_sym_len as usize;
+            buf <<= len;
+            buf_size -= len;
+            if buf_size <= 32 {
+                buf_size += 32;
+                buf |= (read_u32_be(data, ptr) as u64) << (64 - buf_size);
+                ptr += 4;
+            }
+        }
+
+        // Expand the symbol into its pairs until reaching a single value
+        while d.symlen[sym as usize] != 0 {
+            let (left, right) = pair(data, d.btree, sym);
+            if offset < d.symlen[left as usize] as i64 + 1 {
+                sym = left;
+            } else {
+                offset -= d.symlen[left as usize] as i64 + 1;
+                sym = right;
+            }
+        }
+        pair(data, d.btree, sym).0 as i32
+    }
+}
+
+// Reads the block layout and Huffman code of a sub-table
+fn set_sizes(d: &mut PairsData, data: &[u8], mut pos: usize) -> Option<usize> {
+    d.flags = *data.get(pos)?;
+    pos += 1;
+    if d.flags & SINGLE_VALUE != 0 {
+        // The only value is stored in place of the minimum symbol length
+        d.min_sym_len = *data.get(pos)?;
+        return Some(pos + 1);
+    }
+
+    let groups = d.group_len.iter().position(|&len| len == 0)?;
+    let tb_size = d.group_idx[groups];
+    d.block_size = 1 << data.get(pos)?;
+    d.span = 1 << data.get(pos + 1)?;
+    d.sparse_index_size = tb_size.div_ceil(d.span) as usize;
+    let padding = *data.get(pos + 2)? as usize;
+    d.num_blocks = read_u32(data, pos + 3) as usize;
+    d.block_length_size = d.num_blocks + padding;
+    let max_sym_len = *data.get(pos + 7)?;
+    d.min_sym_len = *data.get(pos + 8)?;
+    pos += 9;
+    d.lowest_sym = pos;
+
+    // Canonical Huffman: base64[i] is the lowest code of length min + i, padded
+    // to 64 bits, so a code's length is found by comparing against them
+    let lengths = (max_sym_len.checked_sub(d.min_sym_len)? as usize) + 1;
+    d.base64 = vec![0; lengths];
+    for i in (0..lengths - 1).rev() {
+        let lowest = read_u16(data, d.lowest_sym + 2 * i) as u64;
+        let lowest_next = read_u16(data, d.lowest_sym + 2 * (i + 1)) as u64;
+        d.base64[i] = (d.base64[i + 1] + lowest - lowest_next) / 2;
+    }
+    for (i, base) in d.base64.iter_mut().enumerate() {
+        let shift = 64 - i as u32 - d.min_sym_len as u32;
+        *base = base.checked_shl(shift).unwrap_or(0);
+    }
+    pos += 2 * lengths;
+
+    let symbols = read_u16(data, pos) as usize;
+    pos += 2;
+    d.btree = pos;
+    if pos + 3 * symbols > data.len() {
+        return None;
+    }
+    d.symlen = vec![0; symbols];
+    let mut visited = vec![false; symbols];
+    for sym in 0..symbols {
+        if !visited[sym] {
+            d.symlen[sym] = set_symlen(d, data, sym as u16, &mut visited);
+        }
+    }
+    Some(pos + 3 * symbols + (symbols & 1))
+}
+
+// Number of values a symbol expands to, minus one
+fn set_symlen(d: &mut PairsData, data: &[u8], sym: u16, visited: &mut [bool]) -> u8 {
+    visited[sym as usize] = true;
+    let (left, right) = pair(data, d.btree, sym);
+    if right == 0xFFF {
+        return 0;
+    }
+    for child in [left, right] {
+        if !visited[child as usize] {
+            d.symlen[child as usize] = set_symlen(d, data, child, visited);
+        }
+    }
+    d.symlen[left as usize]
+        .wrapping_add(d.symlen[right as usize])
+        .wrapping_add(1)
+}
+
+// Locates the maps from stored DTZ values to real ones
+fn set_dtz_map(items: &mut [PairsData], data: &[u8], mut pos: usize) -> Option<usize> {
+    let map = pos;
+    for d in items.iter_mut() {
+        if d.flags & MAPPED == 0 {
+            continue;
+        }
+        if d.flags & WIDE != 0 {
+            pos += pos & 1;
+            for i in 0..4 {
+                d.map_idx[i] = (pos - map) / 2 + 1;
+                pos += 2 * read_u16(data, pos) as usize + 2;
+            }
+        } else {
+            for i in 0..4 {
+                d.map_idx[i] = pos - map + 1;
+                pos += *data.get(pos)? as usize + 1;
+            }
+        }
+    }
+    Some(pos + (pos & 1))
+}
+
+// The two symbols a symbol stands for, 12 bits each
+fn pair(data: &[u8], btree: usize, sym: u16) -> (u16, u16) {
+    let at = btree + 3 * sym as usize;
+    let (b0, b1, b2) = (data[at] as u16, data[at + 1] as u16, data[at + 2] as u16);
+    (((b1 & 0xF) << 8) | b0, (b2 << 4) | (b1 >> 4))
+}
+
+fn read_u16(data: &[u8], pos: usize) -> u16 {
+    data.get(pos..pos + 2)
+        .map_or(0, |bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], pos: usize) -> u32 {
+    data.get(pos..pos + 4)
+        .map_or(0, |bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+// Block data is big endian and may be read slightly past the end of the file
+fn read_u32_be(data: &[u8], pos: usize) -> u32 {
+    let mut bytes = [0; 4];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = data.get(pos + i).copied().unwrap_or(0);
+    }
+    u32::from_be_bytes(bytes)
+}
+
+fn read_u64_be(data: &[u8], pos: usize) -> u64 {
+    ((read_u32_be(data, pos) as u64) << 32) | read_u32_be(data, pos + 4) as u64
+}
+
+fn invalid(path: &Path, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}: {}", path.display(), message),
+    )
+}
+
+// Piece counts of both sides from a name like "KRPvKR", indexed like `boards`
+fn parse_material(name: &str) -> Option<[[u32; 6]; 2]> {
+    let (white, black) = name.split_once('v')?;
+    let mut counts = [[0; 6]; 2];
+    for (side, pieces) in [white, black].into_iter().enumerate() {
+        if !pieces.starts_with('K') {
+            return None;
+        }
+        for letter in pieces.chars() {
+            let piece = match letter {
+                'P' => PieceType::Pawn,
+                'N' => PieceType::Knight,
+                'B' => PieceType::Bishop,
+                'R' => PieceType::Rook,
+                'Q' => PieceType::Queen,
+                'K' => PieceType::King,
+                _ => return None,
+            };
+            counts[side][piece as usize] += 1;
+        }
+    }
+    Some(counts)
+}
+
+// Piece code used in the table files: 1-6 for pawn to King, plus 8 for Black
+fn piece_code(board: &Bitboards, sq: u8) -> u8 {
+    for color in [Color::White, Color::Black] {
+        for piece in PieceType::pieces() {
+            if board.boards[color as usize][piece as usize] & (1u64 << sq) != 0 {
+                return piece as u8 + 1 + 8 * color as u8;
+            }
+        }
+    }
+    0
+}
+
+/* INDEX ENCODING */
+
+// Rank minus file: 0 on the a1-h8 diagonal, negative below it
+fn off_diagonal(sq: u8) -> i32 {
+    (sq / 8) as i32 - (sq % 8) as i32
+}
+
+// Index of three unique leading pieces, the first one in the a1-d1-d4 triangle
+fn encode_unique(squares: &[u8]) -> u64 {
+    let encoding = &*ENCODING;
+    let [s0, s1, s2] = [squares[0], squares[1], squares[2]];
+    let adjust1 = u64::from(s1 > s0);
+    let adjust2 = u64::from(s2 > s0) + u64::from(s2 > s1);
+    let rank = |sq: u8| (sq / 8) as u64;
+
+    if off_diagonal(s0) != 0 {
+        (encoding.map_a1d1d4[s0 as usize] * 63 + (s1 as u64 - adjust1)) * 62 + s2 as u64 - adjust2
+    } else if off_diagonal(s1) != 0 {
+        (6 * 63 + rank(s0) * 28 + encoding.map_b1h1h7[s1 as usize]) * 62 + s2 as u64 - adjust2
+    } else if off_diagonal(s2) != 0 {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + rank(s0) * 7 * 28
+            + (rank(s1) - adjust1) * 28
+            + encoding.map_b1h1h7[s2 as usize]
+    } else {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + 4 * 7 * 28
+            + rank(s0) * 7 * 6
+            + (rank(s1) - adjust1) * 6
+            + (rank(s2) - adjust2)
+    }
+}
+
+/// Lookup tables mapping squares to index parts.
+struct Encoding {
+    /// Squares below the a1-h8 diagonal to 0..28.
+    map_b1h1h7: [u64; 64],
+    /// The a1-d1-d4 triangle to 0..10, diagonal squares last.
+    map_a1d1d4: [u64; 64],
+    /// The 462 legal placements of two Kings, the first in the triangle.
+    map_kk: [[u64; 64]; 10],
+    binomial: [[u64; 64]; MAX_PIECES],
+    /// Pawn squares to 0..48, higher for pawns closer to the edge and lower.
+    map_pawns: [usize; 64],
+    lead_pawn_idx: [[u64; 64]; MAX_PIECES],
+    lead_pawns_size: [[u64; 4]; MAX_PIECES],
+}
+
+static ENCODING: LazyLock<Encoding> = LazyLock::new(|| {
+    let mut e = Encoding {
+        map_b1h1h7: [0; 64],
+        map_a1d1d4: [0; 64],
+        map_kk: [[0; 64]; 10],
+        binomial: [[0; 64]; MAX_PIECES],
+        map_pawns: [0; 64],
+        lead_pawn_idx: [[0; 64]; MAX_PIECES],
+        lead_pawns_size: [[0; 4]; MAX_PIECES],
+    };
+
+    let mut code = 0;
+    for sq in 0..64u8 {
+        if off_diagonal(sq) < 0 {
+            e.map_b1h1h7[sq as usize] = code;
+            code += 1;
+        }
+    }
+
+    let mut code = 0;
+    let mut diagonal = Vec::new();
+    for sq in 0..=27u8 {
+        if sq % 8 > 3 {
+            continue;
+        }
+        if off_diagonal(sq) < 0 {
+            e.map_a1d1d4[sq as usize] = code;
+            code += 1;
+        } else if off_diagonal(sq) == 0 {
+            diagonal.push(sq);
+        }
+    }
+    for sq in diagonal {
+        e.map_a1d1d4[sq as usize] = code;
+        code += 1;
+    }
+
+    let mut code = 0;
+    let mut both_on_diagonal = Vec::new();
+    for idx in 0..10 {
+        for s1 in 0..=27u8 {
+            // b1 is mapped to 0, the squares outside the triangle as well
+            if e.map_a1d1d4[s1 as usize] != idx || (idx == 0 && s1 != 1) {
+                continue;
+            }
+            for s2 in 0..64u8 {
+                if (king_attacks(s1) | 1u64 << s1) & (1u64 << s2) != 0 {
+                    continue;
+                }
+                if off_diagonal(s1) == 0 && off_diagonal(s2) > 0 {
+                    continue;
+                }
+                if off_diagonal(s1) == 0 && off_diagonal(s2) == 0 {
+                    both_on_diagonal.push((idx, s2));
+                } else {
+                    e.map_kk[idx as usize][s2 as usize] = code;
+                    code += 1;
+                }
+            }
+        }
+    }
+    for (idx, s2) in both_on_diagonal {
+        e.map_kk[idx as usize][s2 as usize] = code;
+        code += 1;
+    }
+
+    e.binomial[0][0] = 1;
+    for n in 1..64 {
+        for k in 0..MAX_PIECES.min(n + 1) {
+            e.binomial[k][n] = if k > 0 { e.binomial[k - 1][n - 1] } else { 0 }
+                + if k < n { e.binomial[k][n - 1] } else { 0 };
+        }
+    }
+
+    let mut available = 47;
+    for lead_pawns in 1..MAX_PIECES - 1 {
+        for file in 0..4u8 {
+            let mut idx = 0;
+            for rank in 1..7u8 {
+                let sq = (rank * 8 + file) as usize;
+                if lead_pawns == 1 {
+                    e.map_pawns[sq] = available;
+                    e.map_pawns[sq ^ 7] = available - 1;
+                    available = available.saturating_sub(2);
+                }
+                e.lead_pawn_idx[lead_pawns][sq] = idx;
+                idx += e.binomial[lead_pawns - 1][e.map_pawns[sq]];
+            }
+            e.lead_pawns_size[lead_pawns][file as usize] = idx;
+        }
+    }
+
+    e
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use memmap2::MmapOptions;
+    use std::collections::HashSet;
+
+    fn table(name: &str, kind: Kind) -> Table {
+        let data = MmapOptions::new()
+            .len(64)
+            .map_anon()
+            .and_then(|data| data.make_read_only())
+            .expect("anonymous map");
+        Table::new(data, parse_material(name).expect("material name"), kind)
+    }
+
+    // Writes a table file to the temporary directory and opens it
+    fn open_bytes(name: &str, bytes: &[u8]) -> io::Result<Option<Table>> {
+        let dir = std::env::temp_dir().join(format!("syzygy-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}.rtbw", name));
+        std::fs::write(&path, bytes)?;
+        let result = Table::open(&path, name, Kind::Wdl);
+        std::fs::remove_file(&path)?;
+        result
+    }
+
+    fn board(pieces: &[(Color, PieceType, u8)]) -> Bitboards {
+        let mut board = Bitboards::new();
+        board.white_kingside = false;
+        board.white_queenside = false;
+        board.black_kingside = false;
+        board.black_queenside = false;
+        for &(color, piece, square) in pieces {
+            Bitboards::add_piece(&mut board, color, piece, square);
+        }
+        board
+    }
+
+    #[test]
+    fn square_maps_cover_their_regions() {
+        let encoding = &*ENCODING;
+        let below: Vec<u64> = (0..64u8)
+            .filter(|&sq| off_diagonal(sq) < 0)
+            .map(|sq| encoding.map_b1h1h7[sq as usize])
+            .collect();
+        assert_eq!(below.iter().collect::<HashSet<_>>().len(), 28);
+        assert!(below.iter().all(|&code| code < 28));
+
+        let triangle: Vec<u8> = (0..=27u8)
+            .filter(|&sq| sq % 8 <= 3 && off_diagonal(sq) <= 0)
+            .collect();
+        let codes: HashSet<u64> = triangle
+            .iter()
+            .map(|&sq| encoding.map_a1d1d4[sq as usize])
+            .collect();
+        assert_eq!(codes, (0..10).collect());
+        // a1, b2, c3 and d4 come after the six squares below the diagonal
+        for sq in [0, 9, 18, 27] {
+            assert!(encoding.map_a1d1d4[sq] >= 6);
+        }
+    }
+
+    #[test]
+    fn king_pairs_use_462_codes() {
+        let codes: HashSet<u64> = ENCODING.map_kk.iter().flatten().copied().collect();
+        assert_eq!(codes.len(), 462);
+        assert_eq!(codes.iter().max(), Some(&461));
+    }
+
+    #[test]
+    fn binomial_coefficients() {
+        let binomial = &ENCODING.binomial;
+        assert_eq!(binomial[0][10], 1);
+        assert_eq!(binomial[1][63], 63);
+        assert_eq!(binomial[2][62], 1891);
+        assert_eq!(binomial[3][48], 17_296);
+    }
+
+    #[test]
+    fn pawn_squares_are_mapped_once() {
+        let encoding = &*ENCODING;
+        let codes: HashSet<usize> = (8..56).map(|sq| encoding.map_pawns[sq]).collect();
+        assert_eq!(codes, (0..48).collect());
+        // A single leading pawn can stand on six ranks of its file
+        assert_eq!(encoding.lead_pawns_size[1], [6; 4]);
+    }
+
+    #[test]
+    fn unique_pieces_fill_the_index_range() {
+        // Canonical placements: the first piece in the a1-d1-d4 triangle, and the
+        // first piece off the diagonal below it
+        let triangle: Vec<u8> = (0..=27u8)
+            .filter(|&sq| sq % 8 <= 3 && off_diagonal(sq) <= 0)
+            .collect();
+        let mut seen = HashSet::new();
+        for &s0 in &triangle {
+            for s1 in (0..64u8).filter(|&sq| sq != s0) {
+                if off_diagonal(s0) == 0 && off_diagonal(s1) > 0 {
+                    continue;
+                }
+                for s2 in (0..64u8).filter(|&sq| sq != s0 && sq != s1) {
+                    if off_diagonal(s0) == 0 && off_diagonal(s1) == 0 && off_diagonal(s2) > 0 {
+                        continue;
+                    }
+                    let idx = encode_unique(&[s0, s1, s2]);
+                    assert!(idx < 31_332, "{:?} -> {}", (s0, s1, s2), idx);
+                    assert!(seen.insert(idx), "{:?} -> {} twice", (s0, s1, s2), idx);
+                }
+            }
+        }
+        assert_eq!(seen.len(), 31_332);
+    }
+
+    #[test]
+    fn material_from_file_names() {
+        let counts = parse_material("KRPvKR").expect("valid name");
+        assert_eq!(counts[0], [1, 0, 0, 1, 0, 1]);
+        assert_eq!(counts[1], [0, 0, 0, 1, 0, 1]);
+        assert!(parse_material("KRK").is_none());
+        assert!(parse_material("KXvK").is_none());
+        assert!(parse_material("RvK").is_none());
+    }
+
+    #[test]
+    fn leading_color_has_fewer_pawns() {
+        assert_eq!(table("KPvKPP", Kind::Wdl).pawn_count, [1, 2]);
+        assert_eq!(table("KPPvKP", Kind::Wdl).pawn_count, [1, 2]);
+        assert_eq!(table("KvKP", Kind::Wdl).pawn_count, [1, 0]);
+        assert_eq!(table("KPvKP", Kind::Wdl).pawn_count, [1, 1]);
+        assert!(table("KPvKP", Kind::Wdl).symmetric);
+        assert!(!table("KQQvK", Kind::Wdl).has_unique_pieces);
+    }
+
+    #[test]
+    fn group_sizes() {
+        // Three unique pieces are encoded together
+        let krk = table("KRvK", Kind::Wdl);
+        let mut d = PairsData::default();
+        d.pieces[..3].copy_from_slice(&[6, 4, 14]);
+        krk.set_groups(&mut d, [0, 0xF], 0);
+        assert_eq!(d.group_len[..2], [3, 0]);
+        assert_eq!(d.group_idx[1], 31_332);
+
+        // Two Kings, then two identical Queens on the 62 remaining squares
+        let kqqk = table("KQQvK", Kind::Wdl);
+        let mut d = PairsData::default();
+        d.pieces[..4].copy_from_slice(&[6, 14, 5, 5]);
+        kqqk.set_groups(&mut d, [0, 0xF], 0);
+        assert_eq!(d.group_len[..3], [2, 2, 0]);
+        assert_eq!(d.group_idx[2], 462 * 1891);
+
+        // Leading pawn on six ranks, then each King on its own
+        let kpk = table("KPvK", Kind::Wdl);
+        let mut d = PairsData::default();
+        d.pieces[..3].copy_from_slice(&[1, 6, 14]);
+        kpk.set_groups(&mut d, [0, 0xF], 0);
+        assert_eq!(d.group_len[..4], [1, 1, 1, 0]);
+        assert_eq!(d.group_idx[3], 6 * 63 * 62);
+    }
+
+    #[test]
+    fn open_rejects_bad_files() {
+        let error = |result: io::Result<Option<Table>>| match result {
+            Err(error) => error.to_string(),
+            Ok(_) => "opened".to_string(),
+        };
+        assert!(error(open_bytes("KRvK", b"not a table")).ends_with("not a Syzygy table"));
+
+        let mut header = WDL_MAGIC.to_vec();
+        header.push(0);
+        assert!(error(open_bytes("KPvK", &header)).ends_with("table does not match its file name"));
+        assert!(error(open_bytes("KRvK", &header)).ends_with("truncated table"));
+        assert!(matches!(open_bytes("README", &header), Ok(None)));
+    }
+
+    #[test]
+    fn nothing_is_probed_without_tables() {
+        let board = board(&[
+            (Color::White, PieceType::King, 4),
+            (Color::White, PieceType::Rook, 0),
+            (Color::Black, PieceType::King, 60),
+        ]);
+        assert!(probe_wdl(&board, Color::White).is_none());
+        assert!(root_move(&board, Color::White).is_none());
+    }
+
+    #[test]
+    fn castling_rights_are_not_covered() {
+        let tablebases = Tablebases {
+            wdl: HashMap::new(),
+            dtz: HashMap::new(),
+            max_pieces: 3,
+        };
+        let mut krk = board(&[
+            (Color::White, PieceType::King, 4),
+            (Color::White, PieceType::Rook, 0),
+            (Color::Black, PieceType::King, 60),
+        ]);
+        assert!(tablebases.covers(&krk));
+
+        krk.white_queenside = true;
+        assert!(!tablebases.covers(&krk));
+
+        let krpk = board(&[
+            (Color::White, PieceType::King, 4),
+            (Color::White, PieceType::Rook, 0),
+            (Color::White, PieceType::Pawn, 12),
+            (Color::Black, PieceType::King, 60),
+        ]);
+        assert!(!tablebases.covers(&krpk));
+    }
+}
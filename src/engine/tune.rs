@@ -0,0 +1,269 @@
+//! # Module: `tune`
+//!
+//! Texel tuning of the evaluation parameters. Given a set of quiet positions, each
+//! labeled with the result of the game it was taken from, the tuner looks for the
+//! [`EvalParams`] whose evaluations *predict* those results best.
+//!
+//! ## How it works
+//!
+//! 1. An evaluation in centipawns is turned into an expected score between `0`
+//!    (Black wins) and `1` (White wins) with a sigmoid:
+//!    $sigmoid(e) = \frac{1}{1 + 10^{-K \cdot e / 400}}$
+//! 2. The **error** of a parameter set is the mean squared difference between
+//!    that expected score and the real result over all positions.
+//! 3. `K` is fitted once, for the starting parameters, so the error measures the
+//!    parameters and not the scale of the sigmoid.
+//! 4. **Local search:** every parameter is nudged by `+step`, then `-step`; a
+//!    change is kept whenever it lowers the error. Passes repeat until no
+//!    parameter improves or the iteration limit is reached.
+//!
+//! ## Parameter Vector
+//!
+//! The tuner does not know the layout of [`EvalParams`]. It serializes the
+//! parameters to JSON and treats every number in it as one entry of a flat
+//! vector, so new weights become tunable as soon as they are added to
+//! [`EvalParams`].
+//!
+//! ## Data Format
+//!
+//! One position per line, a FEN followed by the game result, in any of the usual
+//! forms: `1-0`, `0-1`, `1/2-1/2`, `[1.0]`, `[0.5]`, `[0.0]` or an EPD opcode
+//! `c9 "1-0";`. Lines that cannot be read are skipped and counted.
+
+use crate::board::{Bitboards, Color};
+use crate::engine::evaluation::evaluate_with;
+use crate::engine::params::EvalParams;
+use crate::engine::parse_fen;
+use serde_json::Value;
+use std::io;
+use std::path::Path;
+use std::thread;
+
+/// A training position with the game result from White's point of view.
+struct Sample {
+    board: Bitboards,
+    color: Color,
+    result: f64,
+}
+
+/// Tunes `start` on the labeled positions in `data` and writes the best
+/// parameters found to `out` after every pass.
+///
+/// # Arguments
+///
+/// * `data` - Path of the labeled positions.
+/// * `out` - Path the tuned parameters are written to.
+/// * `start` - The parameters to start from.
+/// * `iterations` - Maximum number of passes over all parameters.
+/// * `step` - How far a parameter is moved in each try.
+///
+/// # Returns
+///
+/// The tuned parameters, or the I/O error that stopped the tuning.
+pub fn tune(
+    data: &Path,
+    out: &Path,
+    start: &EvalParams,
+    iterations: usize,
+    step: i32,
+) -> io::Result<EvalParams> {
+    let text = std::fs::read_to_string(data)?;
+    let mut skipped = 0;
+    let samples: Vec<Sample> = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            let sample = parse_sample(line);
+            if sample.is_none() {
+                skipped += 1;
+            }
+            sample
+        })
+        .collect();
+    println!("Loaded {} positions ({} skipped)", samples.len(), skipped);
+    if samples.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "no labeled positions found",
+        ));
+    }
+
+    let k = fit_k(&samples, start);
+    let template = serde_json::to_value(start)?;
+    let mut best = flatten(&template);
+    let mut best_error = mean_squared_error(&samples, start, k);
+    println!(
+        "K = {:.3}, {} parameters, initial error {:.6}",
+        k,
+        best.len(),
+        best_error
+    );
+
+    for iteration in 1..=iterations {
+        let mut improved = false;
+        for i in 0..best.len() {
+            for delta in [step, -step] {
+                let mut candidate = best.clone();
+                candidate[i] += delta;
+                let params = unflatten(&template, &candidate)?;
+                let error = mean_squared_error(&samples, &params, k);
+                if error < best_error {
+                    best = candidate;
+                    best_error = error;
+                    improved = true;
+                    break;
+                }
+            }
+        }
+
+        let params = unflatten(&template, &best)?;
+        params.save(out)?;
+        println!("Iteration {}: error {:.6}", iteration, best_error);
+        if !improved {
+            break;
+        }
+    }
+
+    unflatten(&template, &best)
+}
+
+// Reads "<fen> <result>" in the formats listed in the module documentation
+fn parse_sample(line: &str) -> Option<Sample> {
+    let line = line.trim();
+    let (fen, result) = if let Some(start) = line.find("c9 \"") {
+        let rest = &line[start + 4..];
+        (&line[..start], &rest[..rest.find('"')?])
+    } else {
+        let split = line.rfind(char::is_whitespace)?;
+        (&line[..split], line[split..].trim())
+    };
+
+    let result = match result.trim_matches(|c| c == '[' || c == ']' || c == ';') {
+        "1-0" | "1.0" | "1" => 1.0,
+        "0-1" | "0.0" | "0" => 0.0,
+        "1/2-1/2" | "0.5" => 0.5,
+        _ => return None,
+    };
+    let board = parse_fen::board_from_fen(fen)?;
+    let color = parse_fen::side_to_move(fen)?;
+
+    Some(Sample {
+        board,
+        color,
+        result,
+    })
+}
+
+fn sigmoid(eval: f64, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * eval / 400.0))
+}
+
+// Mean squared error of the predicted results, spread over all available cores
+fn mean_squared_error(samples: &[Sample], params: &EvalParams, k: f64) -> f64 {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = samples.len().div_ceil(threads);
+
+    let total: f64 = thread::scope(|scope| {
+        let handles: Vec<_> = samples
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|sample| {
+                            let eval = white_eval(sample, params) as f64;
+                            (sample.result - sigmoid(eval, k)).powi(2)
+                        })
+                        .sum::<f64>()
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).sum()
+    });
+
+    total / samples.len() as f64
+}
+
+// The evaluation is from the side to move's point of view, results are White's
+fn white_eval(sample: &Sample, params: &EvalParams) -> i32 {
+    let eval = evaluate_with(&sample.board, sample.color, params);
+    match sample.color {
+        Color::White => eval,
+        Color::Black => -eval,
+    }
+}
+
+// Finds the sigmoid scale that fits the starting parameters best, by narrowing
+// down a coarse scan
+fn fit_k(samples: &[Sample], params: &EvalParams) -> f64 {
+    let evals: Vec<(f64, f64)> = samples
+        .iter()
+        .map(|sample| (white_eval(sample, params) as f64, sample.result))
+        .collect();
+    let error = |k: f64| -> f64 {
+        evals
+            .iter()
+            .map(|&(eval, result)| (result - sigmoid(eval, k)).powi(2))
+            .sum::<f64>()
+            / evals.len() as f64
+    };
+
+    let mut best = (1..=30)
+        .map(|k| k as f64 / 10.0)
+        .min_by(|&a, &b| error(a).total_cmp(&error(b)))
+        .unwrap_or(1.0);
+    let mut step = 0.05;
+    for _ in 0..8 {
+        let candidates = [best - step, best, best + step];
+        best = candidates
+            .into_iter()
+            .filter(|&k| k > 0.0)
+            .min_by(|&a, &b| error(a).total_cmp(&error(b)))
+            .unwrap_or(best);
+        step /= 2.0;
+    }
+    best
+}
+
+// Every integer in the serialized parameters, in document order
+fn flatten(value: &Value) -> Vec<i32> {
+    let mut values = Vec::new();
+    collect_numbers(value, &mut values);
+    values
+}
+
+fn collect_numbers(value: &Value, out: &mut Vec<i32>) {
+    match value {
+        Value::Number(n) => out.push(n.as_i64().unwrap_or(0) as i32),
+        Value::Array(items) => items.iter().for_each(|item| collect_numbers(item, out)),
+        Value::Object(fields) => fields
+            .values()
+            .for_each(|field| collect_numbers(field, out)),
+        _ => {}
+    }
+}
+
+// Writes `values` back into a copy of `template`, in the order `flatten` read them
+fn unflatten(template: &Value, values: &[i32]) -> io::Result<EvalParams> {
+    let mut value = template.clone();
+    let mut remaining = values.iter();
+    replace_numbers(&mut value, &mut remaining);
+    Ok(serde_json::from_value(value)?)
+}
+
+fn replace_numbers<'a>(value: &mut Value, values: &mut impl Iterator<Item = &'a i32>) {
+    match value {
+        Value::Number(n) => {
+            if let Some(&v) = values.next() {
+                *n = v.into();
+            }
+        }
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|item| replace_numbers(item, values)),
+        Value::Object(fields) => fields
+            .values_mut()
+            .for_each(|field| replace_numbers(field, values)),
+        _ => {}
+    }
+}
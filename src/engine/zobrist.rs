@@ -0,0 +1,119 @@
+//! # Module: `zobrist`
+//!
+//! Zobrist hashing turns a board into a (practically) unique 64-bit key by XOR-ing
+//! one random number per (color, piece, square) triple. Because XOR is its own
+//! inverse, a key can be updated by toggling only the numbers of the pieces that
+//! moved, and equal positions always produce equal keys.
+//!
+//! The side to move, each castling right and each en passant file have their own
+//! number as well, so [`hash`] tells apart positions that only differ in those.
+//!
+//! The random numbers are generated at compile time with a fixed-seed
+//! SplitMix64 generator, so keys are identical between runs and builds.
+
+use crate::board::{Bitboards, Color, PieceType};
+
+/// One step of the SplitMix64 generator, usable in `const` context.
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (state, z ^ (z >> 31))
+}
+
+/// Builds the `[color][piece][square]` key table.
+const fn piece_square_keys() -> [[[u64; 64]; 6]; 2] {
+    let mut keys = [[[0u64; 64]; 6]; 2];
+    let mut state = 0x5EED_C0FF_EE15_600D;
+    let mut color = 0;
+    while color < 2 {
+        let mut piece = 0;
+        while piece < 6 {
+            let mut sq = 0;
+            while sq < 64 {
+                let (next_state, value) = splitmix64(state);
+                state = next_state;
+                keys[color][piece][sq] = value;
+                sq += 1;
+            }
+            piece += 1;
+        }
+        color += 1;
+    }
+    keys
+}
+
+/// Builds the keys for the rest of the position: side to move, the four castling
+/// rights (White kingside, White queenside, Black kingside, Black queenside) and
+/// the eight en passant files.
+const fn state_keys() -> [u64; 13] {
+    let mut keys = [0u64; 13];
+    let mut state = 0xBADC_0FFE_E0DD_F00D;
+    let mut i = 0;
+    while i < 13 {
+        let (next_state, value) = splitmix64(state);
+        state = next_state;
+        keys[i] = value;
+        i += 1;
+    }
+    keys
+}
+
+/// Random number for every (color, piece, square) combination.
+pub const PIECE_SQUARE_KEYS: [[[u64; 64]; 6]; 2] = piece_square_keys();
+
+const STATE_KEYS: [u64; 13] = state_keys();
+const SIDE_KEY: usize = 0;
+const CASTLING_KEYS: usize = 1;
+const EN_PASSANT_KEYS: usize = 5;
+
+/// Computes the key of a full position: pieces, side to move, castling rights and
+/// en passant file.
+///
+/// Two positions with the same key are (practically) the same position, which is
+/// what repetition detection needs.
+pub fn hash(board: &Bitboards, color: Color) -> u64 {
+    let mut key = 0u64;
+    for color in [Color::White, Color::Black] {
+        for piece in PieceType::pieces() {
+            for sq in Bitboards::return_squares(board.boards[color as usize][piece as usize]) {
+                key ^= PIECE_SQUARE_KEYS[color as usize][piece as usize][sq as usize];
+            }
+        }
+    }
+    if color == Color::Black {
+        key ^= STATE_KEYS[SIDE_KEY];
+    }
+    let rights = [
+        board.white_kingside,
+        board.white_queenside,
+        board.black_kingside,
+        board.black_queenside,
+    ];
+    for (i, _) in rights.iter().enumerate().filter(|(_, allowed)| **allowed) {
+        key ^= STATE_KEYS[CASTLING_KEYS + i];
+    }
+    if let Some(ep) = board.en_passant_square {
+        key ^= STATE_KEYS[EN_PASSANT_KEYS + (ep % 8) as usize];
+    }
+    key
+}
+
+/// Computes a key from the pawns alone.
+///
+/// Used by the pawn hash table: pawn structure changes far less often than the
+/// rest of the position, so everything that depends only on pawns can be cached
+/// under this key.
+pub fn pawn_key(board: &Bitboards) -> u64 {
+    let mut key = 0u64;
+    for color in [Color::White, Color::Black] {
+        let mut pawns = board.boards[color as usize][PieceType::Pawn as usize];
+        while pawns != 0 {
+            let sq = pawns.trailing_zeros() as usize;
+            pawns &= pawns - 1;
+            key ^= PIECE_SQUARE_KEYS[color as usize][PieceType::Pawn as usize][sq];
+        }
+    }
+    key
+}
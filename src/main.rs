@@ -6,85 +6,463 @@ use crossterm::{
 use regex::Regex;
 use std::io;
 use std::io::stdout;
+use std::path::PathBuf;
+use std::time::Instant;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use engine::{
     board::{self, Bitboards},
-    evaluation, make_move, movegen, parse_fen, search,
+    datagen, dtm, evaluation, make_move, movegen,
+    params::{self, EvalParams},
+    parse_fen, search, syzygy, tune,
 };
 use indicatif::{ProgressBar, ProgressStyle};
 mod opening_book;
-use opening_book::book;
+use opening_book::learning::{GameRecord, Learning};
+use opening_book::{book, builder, convert};
 
-use std::collections::HashMap;
+use crate::engine::{make_move::apply_move, movegen::Move, pgn, san, search::MoveSource};
 
-use crate::engine::{game_over, make_move::apply_move, movegen::Move};
+const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 #[derive(Parser, Debug)]
 struct Args {
     #[arg(
         short,
         long,
-        default_value = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        global = true,
+        default_value = START_FEN
     )]
     fen: String,
     #[arg(short, long, default_value = "w")]
     color: char, //either w or b
+    /// JSON file with evaluation parameters replacing the built-in ones
+    #[arg(long, global = true)]
+    eval_params: Option<PathBuf>,
+    /// Directories with Syzygy tablebases (SyzygyPath), separated like PATH
+    #[arg(long, global = true, alias = "SyzygyPath")]
+    syzygy_path: Option<String>,
+    /// Directory with DTM tables made by the tablegen command
+    #[arg(long, global = true)]
+    dtm_path: Option<PathBuf>,
+    /// Neural network weights replacing the handcrafted evaluation
+    #[cfg(feature = "nnue")]
+    #[arg(long, global = true)]
+    nnue: Option<PathBuf>,
+    /// Opening book to use instead of the built-in one: `.bin` for Polyglot, anything else JSON
+    #[arg(long, global = true)]
+    book: Option<PathBuf>,
+    /// Play without opening book
+    #[arg(long, global = true, conflicts_with = "book")]
+    no_book: bool,
+    /// How book moves are chosen
+    #[arg(long, value_enum, default_value_t = book::Policy::Weighted)]
+    book_policy: book::Policy,
+    /// Book moves with a lower weight are never played
+    #[arg(long, default_value_t = 1)]
+    book_min_weight: u32,
+    /// Temperature of weighted book moves: below 1 favors the main lines, above 1 adds variety
+    #[arg(long, default_value_t = 1.0)]
+    book_variety: f64,
+    /// Last move number the book is used for
+    #[arg(long, default_value_t = 10)]
+    book_depth: u32,
+    /// File of learned book weights, updated after every game (no learning without it)
+    #[arg(long)]
+    book_learn: Option<PathBuf>,
+    /// Write the PGN of the game to this file when it ends, instead of printing it
+    #[arg(long)]
+    pgn: Option<PathBuf>,
+    /// Add the time spent on every move to the PGN
+    #[arg(long)]
+    pgn_clock: bool,
+    /// Add the engine's evaluation of its searched moves to the PGN
+    #[arg(long)]
+    pgn_eval: bool,
+    /// Continue a game saved as PGN, from the end of its main line (replaces --fen)
+    #[arg(long)]
+    load: Option<PathBuf>,
+    /// Which game of the --load file to continue, counting from 1 (default: the last)
+    #[arg(long, requires = "load")]
+    game: Option<usize>,
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the evaluation of the --fen position term by term
+    Eval,
+    /// Write the evaluation parameters in use (built-in or --eval-params) to a JSON file
+    DumpParams { path: PathBuf },
+    /// Tune the evaluation parameters on labeled positions (Texel tuning)
+    Tune {
+        /// Positions with game results, one "<fen> <result>" per line
+        data: PathBuf,
+        /// Where the tuned parameters are written
+        #[arg(long, default_value = "tuned_params.json")]
+        out: PathBuf,
+        /// Maximum number of passes over all parameters
+        #[arg(long, default_value_t = 50)]
+        iterations: usize,
+        /// How far a parameter is moved in each try
+        #[arg(long, default_value_t = 1)]
+        step: i32,
+    },
+    /// Generate training data from self-play games
+    Datagen {
+        /// Where the positions are written, one "<fen> | <score> | <result>" per line
+        #[arg(long, default_value = "datagen.txt")]
+        out: PathBuf,
+        /// Number of games to play
+        #[arg(long, default_value_t = 100)]
+        games: usize,
+        /// Nodes searched per move
+        #[arg(long, default_value_t = 5000)]
+        nodes: u64,
+        /// Random moves played at the start of every game
+        #[arg(long, default_value_t = 8)]
+        random_plies: u32,
+        /// Number of games played at once (default: all cores)
+        #[arg(long)]
+        threads: Option<usize>,
+    },
+    /// Generate distance-to-mate tables by retrograde analysis
+    Tablegen {
+        /// Material signatures with the side listed first as White, e.g. KQK KRK KPK KRKP
+        #[arg(required = true)]
+        signatures: Vec<String>,
+        /// Directory the tables are written to; tables already there are reused
+        #[arg(long, default_value = "tables")]
+        out: PathBuf,
+    },
+    /// Replay the games of a PGN file, variations included, and report illegal moves
+    Pgn {
+        /// PGN file to read
+        path: PathBuf,
+    },
+    /// Opening book tools
+    Book {
+        #[command(subcommand)]
+        command: BookCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum BookCommand {
+    /// Build a book from PGN files
+    Build {
+        /// PGN files to read
+        #[arg(required = true)]
+        pgn: Vec<PathBuf>,
+        /// Where the book is written: `.bin` for Polyglot, anything else for JSON
+        #[arg(short, long, default_value = "book.bin")]
+        out: PathBuf,
+        /// Plies counted from the start of every game
+        #[arg(long, default_value_t = 30)]
+        plies: u32,
+        /// Rating both players need (0 accepts every game)
+        #[arg(long, default_value_t = 0)]
+        min_elo: u32,
+        /// Results of the games to use
+        #[arg(long, value_delimiter = ',', default_value = "1-0,0-1,1/2-1/2")]
+        results: Vec<String>,
+        /// Games a move must appear in to be kept
+        #[arg(long, default_value_t = 2)]
+        min_games: u32,
+        /// Weight of a move by the side that won
+        #[arg(long, default_value_t = 2)]
+        win_weight: u32,
+        /// Weight of a move in a drawn game
+        #[arg(long, default_value_t = 1)]
+        draw_weight: u32,
+        /// Weight of a move by the side that lost
+        #[arg(long, default_value_t = 0)]
+        loss_weight: u32,
+    },
+    /// List the book moves of a position (the --book, or the default book)
+    Probe {
+        /// Position to look up; defaults to --fen
+        fen: Option<String>,
+    },
+    /// Count the positions and moves of a book
+    Stats,
+    /// Combine books, adding up the weights of shared moves
+    Merge {
+        /// Books to combine, in either format
+        #[arg(num_args = 2.., required = true)]
+        books: Vec<PathBuf>,
+        /// Where the merged book is written: `.bin` for Polyglot, anything else for JSON
+        #[arg(short, long)]
+        out: PathBuf,
+    },
+    /// Rewrite a book in the format given by the output extension
+    Convert {
+        /// Book to read
+        input: PathBuf,
+        /// Book to write: `.bin` for Polyglot, anything else for JSON
+        output: PathBuf,
+    },
+}
+
+// Runs a subcommand instead of the interactive game
+fn run_command(command: &Command, args: &Args) {
+    match command {
+        Command::Eval => {
+            let board = board_or_exit(&args.fen);
+            let color = parse_fen::side_to_move(&args.fen).unwrap_or(board::Color::White);
+            Bitboards::render_board(&board);
+            println!("{}", evaluation::evaluation_trace(&board, color));
+        }
+        Command::DumpParams { path } => {
+            params::active()
+                .save(path)
+                .expect("failed to write evaluation parameters");
+            println!("Evaluation parameters written to {}", path.display());
+        }
+        Command::Tune {
+            data,
+            out,
+            iterations,
+            step,
+        } => {
+            tune::tune(data, out, &params::active(), *iterations, *step).expect("tuning failed");
+            println!("Tuned parameters written to {}", out.display());
+        }
+        Command::Datagen {
+            out,
+            games,
+            nodes,
+            random_plies,
+            threads,
+        } => {
+            let options = datagen::DatagenOptions {
+                games: *games,
+                nodes: *nodes,
+                random_plies: *random_plies,
+                threads: threads
+                    .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get())),
+            };
+            let positions = datagen::generate(out, &options).expect("data generation failed");
+            println!("{} positions written to {}", positions, out.display());
+        }
+        Command::Tablegen { signatures, out } => {
+            let tables = dtm::generate(signatures, out).expect("table generation failed");
+            println!("{} tables written to {}", tables, out.display());
+        }
+        Command::Pgn { path } => {
+            let text = std::fs::read_to_string(path).expect("failed to read PGN file");
+            for (i, game) in pgn::read_games(&text).iter().enumerate() {
+                let mut main_line = 0;
+                let mut in_variations = 0;
+                let walked = game.walk(|_, depth| match depth {
+                    0 => main_line += 1,
+                    _ => in_variations += 1,
+                });
+                print!(
+                    "{}. {} - {} {}: {} moves, {} in variations",
+                    i + 1,
+                    game.tag("White").unwrap_or("?"),
+                    game.tag("Black").unwrap_or("?"),
+                    game.result,
+                    main_line,
+                    in_variations
+                );
+                match walked {
+                    Ok(()) => println!(),
+                    Err(err) => println!(" ({})", err),
+                }
+            }
+        }
+        Command::Book { command } => run_book_command(command, args),
+    }
+}
+
+// The book chosen with --book or --no-book, else the built-in one, with its name
+// for messages. A --book file that cannot be read is reported and replaced by
+// the built-in book.
+fn load_book(args: &Args) -> (book::Book, String) {
+    if args.no_book {
+        return (book::Book::empty(), "no book".to_string());
+    }
+    if let Some(path) = &args.book {
+        match book::Book::load(path) {
+            Ok(book) => return (book, path.display().to_string()),
+            Err(err) => println!(
+                "Warning: cannot read opening book {} ({}), using the built-in book",
+                path.display(),
+                err
+            ),
+        }
+    }
+    (book::Book::built_in(), "the built-in book".to_string())
+}
+
+fn run_book_command(command: &BookCommand, args: &Args) {
+    match command {
+        BookCommand::Build {
+            pgn,
+            out,
+            plies,
+            min_elo,
+            results,
+            min_games,
+            win_weight,
+            draw_weight,
+            loss_weight,
+        } => {
+            let options = builder::BuildOptions {
+                plies: *plies,
+                min_elo: *min_elo,
+                results: results.clone(),
+                min_games: *min_games,
+                win_weight: *win_weight,
+                draw_weight: *draw_weight,
+                loss_weight: *loss_weight,
+            };
+            let summary = builder::build(pgn, out, &options).expect("book build failed");
+            println!(
+                "{} of {} games used, {} positions with {} moves written to {}",
+                summary.games_used,
+                summary.games_read,
+                summary.positions,
+                summary.moves,
+                out.display()
+            );
+        }
+        BookCommand::Probe { fen } => {
+            let fen = fen.as_deref().unwrap_or(&args.fen);
+            let board = board_or_exit(fen);
+            let color = parse_fen::side_to_move(fen).unwrap_or(board::Color::White);
+            let (book, name) = load_book(args);
+            let moves = book.probe(&board, color);
+            if moves.is_empty() {
+                println!("No book moves for this position in {}", name);
+                return;
+            }
+            let total: u32 = moves.iter().map(|mv| mv.weight).sum();
+            for mv in &moves {
+                let share = 100.0 * f64::from(mv.weight) / f64::from(total.max(1));
+                print!("{:<6} {:>8} {:>6.2}%", mv.uci, mv.weight, share);
+                match &mv.problem {
+                    Some(problem) => println!("  skipped: {}", problem),
+                    None => println!(),
+                }
+            }
+        }
+        BookCommand::Stats => {
+            let (book, name) = load_book(args);
+            let stats = book.stats();
+            let format = match book {
+                book::Book::Json(_) => "JSON",
+                book::Book::Polyglot(_) => "Polyglot",
+            };
+            println!("{} ({})", name, format);
+            println!("Positions:    {}", stats.positions);
+            println!("Moves:        {}", stats.moves);
+            println!("Total weight: {}", stats.total_weight);
+            println!("Most moves in one position: {}", stats.widest);
+        }
+        BookCommand::Merge { books, out } => {
+            let books: Vec<book::Book> = books
+                .iter()
+                .map(|path| book::Book::load(path).expect("failed to read opening book"))
+                .collect();
+            let (merged, skipped) = convert::merge(&books, book::is_polyglot_path(out));
+            merged.save(out).expect("failed to write opening book");
+            let stats = merged.stats();
+            println!(
+                "{} positions with {} moves written to {} ({} entries skipped)",
+                stats.positions,
+                stats.moves,
+                out.display(),
+                skipped
+            );
+        }
+        BookCommand::Convert { input, output } => {
+            let book = book::Book::load(input).expect("failed to read opening book");
+            let (converted, skipped) = convert::convert(&book, book::is_polyglot_path(output));
+            converted
+                .save(output)
+                .expect("failed to write opening book");
+            let stats = converted.stats();
+            println!(
+                "{} positions with {} moves written to {} ({} entries skipped)",
+                stats.positions,
+                stats.moves,
+                output.display(),
+                skipped
+            );
+        }
+    }
+}
+
+// Parses a FEN, or reports it and exits when it does not describe a position
+fn board_or_exit(fen: &str) -> Bitboards {
+    parse_fen::board_from_fen(fen).unwrap_or_else(|| {
+        eprintln!("Invalid FEN: {}", fen);
+        std::process::exit(1);
+    })
+}
+
+#[allow(dead_code)]
 fn clear_terminal() {
     execute!(stdout(), Clear(ClearType::All)).unwrap();
 }
 
 fn main() {
+    let args = Args::parse();
+    if let Some(path) = &args.syzygy_path {
+        let tables = syzygy::init(path).expect("failed to load Syzygy tablebases");
+        println!("Loaded {} Syzygy tables", tables);
+    }
+    if let Some(path) = &args.dtm_path {
+        let tables = dtm::init(path).expect("failed to load DTM tables");
+        println!("Loaded {} DTM tables", tables);
+    }
+    if let Some(path) = &args.eval_params {
+        let loaded = EvalParams::load(path).expect("failed to read evaluation parameters");
+        params::set_active(loaded);
+    }
+    #[cfg(feature = "nnue")]
+    if let Some(path) = &args.nnue {
+        engine::nnue::load(path).expect("failed to load neural network");
+    }
+    if let Some(command) = &args.command {
+        run_command(command, &args);
+        return;
+    }
+
     let pb = ProgressBar::new_spinner();
     pb.set_style(ProgressStyle::with_template("{spinner} {msg}").unwrap());
     pb.enable_steady_tick(std::time::Duration::from_millis(100));
     pb.set_message("Loading opening book...");
 
-    let opening_book_data = std::fs::read_to_string("src/opening_book/book.json").unwrap();
-    let book: HashMap<String, HashMap<String, book::MoveEntry>> =
-        serde_json::from_str(&opening_book_data).unwrap();
+    let (book, _) = load_book(&args);
+    let book_options = book::BookOptions {
+        policy: args.book_policy,
+        min_weight: args.book_min_weight,
+        variety: args.book_variety,
+        max_depth: args.book_depth,
+        learning: match &args.book_learn {
+            Some(path) => Learning::load(path).expect("failed to read book learning file"),
+            None => Learning::default(),
+        },
+    };
+    let mut record = GameRecord::default();
 
     pb.finish_with_message("Opening book loaded successfully!");
 
-    let args = Args::parse();
-    let fen_position = parse_fen::parse_fen(&args.fen);
     let user_color = &args.color;
     let user_color = match user_color {
         'w' => board::Color::White,
         'b' => board::Color::Black,
         _ => unreachable!(),
     };
-    let mut board = Bitboards::new();
-    for (i, n) in fen_position.chars().enumerate() {
-        if n == '.' {
-            continue;
-        }
-        let square = i as u8;
-
-        let color = if n.is_lowercase() {
-            board::Color::Black
-        } else {
-            board::Color::White
-        };
-
-        let piece_type = match n.to_ascii_lowercase() {
-            'p' => board::PieceType::Pawn,
-            'r' => board::PieceType::Rook,
-            'n' => board::PieceType::Knight,
-            'b' => board::PieceType::Bishop,
-            'q' => board::PieceType::Queen,
-            'k' => board::PieceType::King,
-            _ => unreachable!(),
-        }; 
-
-        Bitboards::add_piece(&mut board, color, piece_type, square);
-    }
-    // Validate UCI move syntax (simple) input like e2e4 or b1c3 legal but e8e9, illegal
+    let mut board = board_or_exit(&args.fen);
+    // Tells UCI input like e2e4 or b1c3 apart from SAN; e8e9 is neither
     let re = Regex::new(r"^[a-h][1-8][a-h][1-8][qrbn]?$").unwrap();
     let mut color_to_move = parse_fen::side_to_move(&args.fen).unwrap();
     let mut move_count = 0;
-    let mut fen = parse_fen::flat_board_to_fen(&fen_position);
 
     let moves = Move::generate_moves_for_side(board::Color::White, &board);
 
@@ -92,118 +470,196 @@ fn main() {
         println!("Found Move: {:?}", m);
     }
 
-    if user_color == board::Color::White {
-        // Bitboards::render_board(&board);
-        while !game_over::checkmate(&board, color_to_move) {
-            if color_to_move == board::Color::Black {
-                // Bitboards::render_board(&board);
-                let mv = search::best_move(&mut board, 5, color_to_move, &fen, &book, move_count)
-                    .unwrap();
-                apply_move(&mut board, &mv, color_to_move);
-                color_to_move = search::opposite(color_to_move);
-                let uci = Move::move_to_uci(&mv);
-                let (from, to) = uci.split_at(2);
-                fen = parse_fen::update_fen(from, to, &fen);
-            } else if color_to_move == board::Color::White {
-                let mut input_const;
-
-                let mv = loop {
-                    let mut input = String::new();
-                    println!("Play your next move! Your color is {:?},", color_to_move);
-
-                    io::stdin()
-                        .read_line(&mut input)
-                        .expect("Failed to read input");
-
-                    let input = input.trim();
-
-                    if !re.is_match(input) {
-                        println!("'{}' is not a valid UCI format, please try again.", input);
-                        continue;
-                    }
-                    input_const = input.to_string().clone();
-                    match Move::uci_user_parser(input, &board, color_to_move) {
-                        Some(mv) => {
-                            println!("'{}' is a valid and legal move", input);
-                            break mv; // success, exit loop
-                        }
-                        _none => {
-                            println!(
-                                "'{}' has correct format but is not legal here, try again.",
-                                input
-                            );
-
-                            continue;
-                        }
-                    }
-                };
-                // clear_terminal();
-
-                // Bitboards::render_board(&board);
-                apply_move(&mut board, &mv, color_to_move);
-                color_to_move = search::opposite(color_to_move);
-                println!("{:?}", input_const);
-                let (from, to) = input_const.split_at(2);
-                fen = parse_fen::update_fen(from, to, &fen);
-                move_count += 1
+    let mut game = pgn::PgnGame::default();
+    let engine_name = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+    let (white, black) = match user_color {
+        board::Color::White => ("Player", engine_name),
+        board::Color::Black => (engine_name, "Player"),
+    };
+    game.set_tag("Event", "Casual game");
+    game.set_tag("Date", &pgn::today());
+    game.set_tag("Round", "-");
+    game.set_tag("White", white);
+    game.set_tag("Black", black);
+    if args.fen != START_FEN {
+        game.set_tag("SetUp", "1");
+        game.set_tag("FEN", &args.fen);
+    }
+
+    if let Some(path) = &args.load {
+        let text = std::fs::read_to_string(path).expect("failed to read PGN file");
+        let games = pgn::read_games(&text);
+        let index = args
+            .game
+            .map_or(games.len().saturating_sub(1), |n| n.saturating_sub(1));
+        let mut loaded = games
+            .into_iter()
+            .nth(index)
+            .expect("no such game in the PGN file");
+        let replay = loaded.replay().expect("invalid FEN tag in the PGN file");
+        if let Some(err) = &replay.error {
+            println!("Warning: {}, continuing from the move before", err);
+        }
+        println!(
+            "Loaded {} moves from {}",
+            replay.plies.len(),
+            path.display()
+        );
+
+        loaded.moves.truncate(replay.plies.len());
+        loaded.result.clear();
+        move_count = u8::try_from(replay.plies.len() / 2).unwrap_or(u8::MAX);
+        board = replay.board;
+        color_to_move = replay.color;
+        game = loaded;
+    }
+
+    let win = |color| match color {
+        board::Color::White => "1-0",
+        board::Color::Black => "0-1",
+    };
+    // The game ends in mate or stalemate, or when the user resigns or quits
+    let result = loop {
+        if make_move::generate_legal_moves(&board, color_to_move).is_empty() {
+            if make_move::is_check(&board, color_to_move) {
+                println!("checkmate");
+                break win(search::opposite(color_to_move));
             }
+            println!("stalemate");
+            break "1/2-1/2";
         }
-    } else if user_color == board::Color::Black {
-        while !game_over::checkmate(&board, color_to_move) {
-            if color_to_move == board::Color::White {
-                // Bitboards::render_board(&board);
-                let mv = search::best_move(&mut board, 5, color_to_move, &fen, &book, move_count)
-                    .unwrap();
-                apply_move(&mut board, &mv, color_to_move);
-                color_to_move = search::opposite(color_to_move);
-                let uci = Move::move_to_uci(&mv);
-                let (from, to) = uci.split_at(2);
-                fen = parse_fen::update_fen(from, to, &fen);
-            } else if color_to_move == board::Color::Black {
-                let mut input_const;
-
-                let mv = loop {
-                    let mut input = String::new();
-                    println!("Play your next move! Your color is {:?},", color_to_move);
-
-                    io::stdin()
-                        .read_line(&mut input)
-                        .expect("Failed to read input");
-
-                    let input = input.trim();
-
-                    if !re.is_match(input) {
-                        println!("'{}' is not a valid UCI format, please try again.", input);
-                        continue;
-                    }
-                    input_const = input.to_string().clone();
-                    match Move::uci_user_parser(input, &board, color_to_move) {
-                        Some(mv) => {
-                            println!("'{}' is a valid and legal move", input);
-                            break mv; // success, exit loop
-                        }
-                        _none => {
-                            println!(
-                                "'{}' has correct format but is not legal here, try again.",
-                                input
-                            );
-
-                            continue;
-                        }
-                    }
-                };
-                // clear_terminal();
-
-                // Bitboards::render_board(&board);
-                apply_move(&mut board, &mv, color_to_move);
-                color_to_move = search::opposite(color_to_move);
-                println!("{:?}", input_const);
-                let (from, to) = input_const.split_at(2);
-                fen = parse_fen::update_fen(from, to, &fen);
-                move_count += 1
+
+        let started = Instant::now();
+        let (mv, score) = if color_to_move == user_color {
+            match read_user_move(&board, color_to_move, &re) {
+                UserInput::Move(mv) => (mv, None),
+                UserInput::Resign => break win(search::opposite(user_color)),
+                UserInput::Quit => break "*",
             }
+        } else {
+            let (mv, source) = search::best_move(
+                &mut board,
+                5,
+                color_to_move,
+                &book,
+                &book_options,
+                move_count,
+            )
+            .expect("a legal move exists");
+            record.record(&board, color_to_move, &mv, source);
+            let score = match source {
+                MoveSource::Search(score) => Some(score),
+                _ => None,
+            };
+            (mv, score)
+        };
+
+        let mut comment = Vec::new();
+        if args.pgn_eval
+            && let Some(score) = score
+        {
+            let white_score = match color_to_move {
+                board::Color::White => score,
+                board::Color::Black => -score,
+            };
+            comment.push(pgn::eval_command(white_score));
+        }
+        if args.pgn_clock {
+            comment.push(pgn::emt_command(started.elapsed()));
         }
+        let mut pgn_move = pgn::PgnMove::new(&san::to_san(&mv, &board, color_to_move));
+        pgn_move.comment = comment.join(" ");
+        game.moves.push(pgn_move);
+
+        apply_move(&mut board, &mv, color_to_move);
+        if color_to_move == user_color {
+            move_count += 1;
+        }
+        color_to_move = search::opposite(color_to_move);
+    };
+
+    game.result = result.to_string();
+    let text = game.to_pgn();
+    match &args.pgn {
+        Some(path) => match std::fs::write(path, &text) {
+            Ok(()) => println!("Game written to {}", path.display()),
+            Err(err) => println!("Cannot write {} ({}):\n\n{}", path.display(), err, text),
+        },
+        None => println!("\n{}", text),
+    }
+
+    let engine_result = match result {
+        "*" => None,
+        "1/2-1/2" => Some(0.0),
+        _ if result == win(user_color) => Some(-1.0),
+        _ => Some(1.0),
+    };
+    if let Some(path) = &args.book_learn
+        && let Some(engine_result) = engine_result
+        && record.used_book()
+    {
+        let mut learning = book_options.learning;
+        let signal = learning.learn(&record, engine_result);
+        learning
+            .save(path)
+            .expect("failed to write book learning file");
+        println!(
+            "Book learning: signal {:+.2} written to {}",
+            signal,
+            path.display()
+        );
     }
+}
+
+// What the user entered at the move prompt
+enum UserInput {
+    Move(Move),
+    Resign,
+    Quit,
+}
 
-    println!("checkmate");
+// Asks for a move until a legal one, "resign" or "quit" is entered; the end of
+// the input counts as quitting
+fn read_user_move(board: &Bitboards, color: board::Color, re: &Regex) -> UserInput {
+    loop {
+        let mut input = String::new();
+        println!(
+            "Play your next move! Your color is {:?} ('resign' or 'quit' to stop),",
+            color
+        );
+
+        let read = io::stdin()
+            .read_line(&mut input)
+            .expect("Failed to read input");
+        if read == 0 {
+            return UserInput::Quit;
+        }
+
+        let input = input.trim();
+        match input {
+            "quit" => return UserInput::Quit,
+            "resign" => return UserInput::Resign,
+            _ => {}
+        }
+        // Either UCI ("g1f3") or SAN ("Nf3")
+        let mv = if re.is_match(input) {
+            Move::uci_user_parser(input, board, color)
+        } else {
+            san::parse_san(input, board, color)
+        };
+        match mv {
+            Some(mv) => {
+                println!(
+                    "'{}' is a valid and legal move: {}",
+                    input,
+                    san::to_san(&mv, board, color)
+                );
+                return UserInput::Move(mv);
+            }
+            None => println!(
+                "'{}' is not a legal move here, in UCI (g1f3) or SAN (Nf3), try again.",
+                input
+            ),
+        }
+    }
 }
@@ -1,83 +1,357 @@
-use crate::board::PieceType;
+use super::learning::Learning;
+use super::polyglot::{self, PolyglotBook};
+use crate::board::{Bitboards, Color, PieceType};
+use crate::engine::parse_fen;
+use crate::make_move;
 use crate::movegen::Move;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::io;
+use std::path::Path;
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct MoveEntry {
     pub count: u32,
     pub piece: String, // We'll convert this to PieceType
 }
 
-fn piece_from_str(s: &str) -> PieceType {
-    match s {
-        "P" | "p" => PieceType::Pawn,
-        "N" | "n" => PieceType::Knight,
-        "B" | "b" => PieceType::Bishop,
-        "R" | "r" => PieceType::Rook,
-        "Q" | "q" => PieceType::Queen,
-        "K" | "k" => PieceType::King,
-        _ => panic!("Invalid piece symbol: {}", s),
-    }
+/// An opening book in one of the supported formats.
+#[derive(Clone)]
+pub enum Book {
+    /// Our own format: UCI moves with counts, keyed by FEN.
+    Json(HashMap<String, HashMap<String, MoveEntry>>),
+    /// A standard Polyglot book, keyed by position hash.
+    Polyglot(PolyglotBook),
 }
 
-// I know there is probably better way, but i like the simplest
-// This is used for calculating length of kings move (either one normal move, or two, castle)
-fn absolute_subtruct(a: u8, b: u8) -> u8 {
-    if a > b {
-        return a - b;
+// Main lines of the common openings, built from default.pgn with
+// `book build src/opening_book/default.pgn -o src/opening_book/default.bin --min-games 1 --plies 16`
+const BUILT_IN: &[u8] = include_bytes!("default.bin");
+
+impl Book {
+    /// The small book compiled into the binary, used when no other is given.
+    pub fn built_in() -> Self {
+        Book::Polyglot(PolyglotBook::from_bytes(BUILT_IN).expect("built-in book is valid"))
+    }
+
+    /// A book without any moves, for playing without book.
+    pub fn empty() -> Self {
+        Book::Json(HashMap::new())
+    }
+
+    /// Loads a book from disk; `.bin` files are read as Polyglot, anything else
+    /// as JSON.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        if is_polyglot_path(path) {
+            return PolyglotBook::load(path).map(Book::Polyglot);
+        }
+        let data = std::fs::read_to_string(path)?;
+        serde_json::from_str(&data)
+            .map(Book::Json)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
     }
-    b - a
-}
-
-// Select a move from the opening book randomly, weighted by count
-pub fn get_uci_move(
-    book: &HashMap<String, HashMap<String, MoveEntry>>,
-    fen: &str,
-) -> Option<String> {
-    if let Some(moves) = book.get(fen) {
-        let total: u32 = moves.values().map(|entry| entry.count).sum();
-        let mut rng = rand::rng();
-        let mut r = rand::Rng::random_range(&mut rng, 0..total);
-
-        for (mv, entry) in moves {
-            if r < entry.count {
-                return Some(mv.clone());
+
+    /// Writes the book in its own format.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        match self {
+            Book::Json(book) => {
+                let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+                serde_json::to_writer(file, book).map_err(io::Error::other)
             }
-            r -= entry.count;
+            Book::Polyglot(book) => book.save(path),
         }
     }
-    None
+
+    /// Lists every book move of the position, heaviest first, including the
+    /// ones the engine would skip.
+    pub fn probe(&self, board: &Bitboards, color: Color) -> Vec<ProbedMove> {
+        let mut moves: Vec<ProbedMove> = match self {
+            Book::Json(book) => book
+                .get(&placement(board))
+                .into_iter()
+                .flatten()
+                .map(|(uci, entry)| ProbedMove {
+                    uci: uci.clone(),
+                    weight: entry.count,
+                    problem: json_move(uci, entry, board, color).err(),
+                })
+                .collect(),
+            Book::Polyglot(book) => book
+                .entries(polyglot::key(board, color))
+                .iter()
+                .map(|entry| ProbedMove {
+                    uci: polyglot::move_uci(entry.raw_move, board, color),
+                    weight: u32::from(entry.weight),
+                    problem: polyglot::decode_move(entry.raw_move, board, color)
+                        .is_none()
+                        .then(|| "not playable by the engine".to_string()),
+                })
+                .collect(),
+        };
+        moves.sort_by(|a, b| b.weight.cmp(&a.weight).then_with(|| a.uci.cmp(&b.uci)));
+        moves
+    }
+
+    /// Counts the positions and moves of the book.
+    pub fn stats(&self) -> BookStats {
+        let mut stats = BookStats::default();
+        match self {
+            Book::Json(book) => {
+                for moves in book.values() {
+                    stats.add_position(moves.values().map(|entry| entry.count));
+                }
+            }
+            Book::Polyglot(book) => {
+                for position in book.all_entries().chunk_by(|a, b| a.key == b.key) {
+                    stats.add_position(position.iter().map(|entry| u32::from(entry.weight)));
+                }
+            }
+        }
+        stats
+    }
 }
 
-// Convert UCI string from opening book into a Move struct
-pub fn opening(book: &HashMap<String, HashMap<String, MoveEntry>>, fen: &str) -> Option<Move> {
-    let uci = get_uci_move(book, fen)?;
+/// One move found by [`Book::probe`].
+pub struct ProbedMove {
+    pub uci: String,
+    pub weight: u32,
+    /// Why the engine would skip the move, if it would.
+    pub problem: Option<String>,
+}
 
-    assert!(uci.len() >= 4, "UCI move string too short");
-    let (from_uci, to_uci) = uci.split_at(2);
+/// Size of a book, as reported by [`Book::stats`].
+#[derive(Default)]
+pub struct BookStats {
+    pub positions: usize,
+    pub moves: usize,
+    pub total_weight: u64,
+    /// Most moves stored for a single position.
+    pub widest: usize,
+}
 
-    let from_file = (from_uci.chars().nth(0).unwrap() as u8) - b'a';
-    let from_rank = (from_uci.chars().nth(1).unwrap() as u8) - b'1';
-    let to_file = (to_uci.chars().nth(0).unwrap() as u8) - b'a';
-    let to_rank = (to_uci.chars().nth(1).unwrap() as u8) - b'1';
+impl BookStats {
+    fn add_position(&mut self, weights: impl Iterator<Item = u32>) {
+        let mut moves = 0;
+        for weight in weights {
+            moves += 1;
+            self.total_weight += u64::from(weight);
+        }
+        self.positions += 1;
+        self.moves += moves;
+        self.widest = self.widest.max(moves);
+    }
+}
+
+/// Whether a book path names a Polyglot book (`.bin`) rather than a JSON one.
+pub fn is_polyglot_path(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()) == Some("bin")
+}
+
+/// The piece placement field of the FEN, the key of JSON books.
+pub fn placement(board: &Bitboards) -> String {
+    let fen = parse_fen::board_to_fen(board, Color::White, 0, 1);
+    fen.split(' ').next().unwrap_or_default().to_string()
+}
+
+/// The FEN letter of a piece, the `piece` field of JSON entries.
+pub fn piece_letter(piece: PieceType, color: Color) -> char {
+    let letter = match piece {
+        PieceType::Pawn => 'p',
+        PieceType::Knight => 'n',
+        PieceType::Bishop => 'b',
+        PieceType::Rook => 'r',
+        PieceType::Queen => 'q',
+        PieceType::King => 'k',
+    };
+    match color {
+        Color::White => letter.to_ascii_uppercase(),
+        Color::Black => letter,
+    }
+}
+
+fn piece_from_str(s: &str) -> Option<PieceType> {
+    match s {
+        "P" | "p" => Some(PieceType::Pawn),
+        "N" | "n" => Some(PieceType::Knight),
+        "B" | "b" => Some(PieceType::Bishop),
+        "R" | "r" => Some(PieceType::Rook),
+        "Q" | "q" => Some(PieceType::Queen),
+        "K" | "k" => Some(PieceType::King),
+        _ => None,
+    }
+}
+
+/// Matches a JSON entry with the legal moves of the position.
+///
+/// The move is taken from the generated list rather than built from the text,
+/// so its capture and castling flags are right.
+///
+/// # Returns
+///
+/// The move, or the reason the entry is unusable: it names no legal move, or a
+/// different piece than the one on its origin square.
+pub fn json_move(
+    uci: &str,
+    entry: &MoveEntry,
+    board: &Bitboards,
+    color: Color,
+) -> Result<Move, String> {
+    let piece = piece_from_str(&entry.piece)
+        .ok_or_else(|| format!("unknown piece symbol {:?}", entry.piece))?;
+    let parsed = Move::from_uci(uci, board, color).ok_or("not a move in this position")?;
+    let mv = make_move::generate_legal_moves(board, color)
+        .into_iter()
+        .find(|mv| mv.from == parsed.from && mv.to == parsed.to)
+        .ok_or("illegal in this position")?;
+    if mv.piece != piece {
+        return Err(format!("{:?} listed, {:?} on the square", piece, mv.piece));
+    }
+    Ok(mv)
+}
+
+/// How a move is chosen among the book moves of a position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Policy {
+    /// Always the move with the highest weight.
+    Best,
+    /// A random move, more likely the higher its weight.
+    Weighted,
+}
+
+/// Settings for playing from the book.
+#[derive(Clone, Debug)]
+pub struct BookOptions {
+    pub policy: Policy,
+    /// Moves with a lower weight are never played.
+    pub min_weight: u32,
+    /// Temperature of weighted selection: `1` plays moves in proportion to their
+    /// weights, lower values favor the heavy moves and higher values even the
+    /// choice out.
+    pub variety: f64,
+    /// Last move number the book is consulted for.
+    pub max_depth: u32,
+    /// Weight factors learned from earlier games.
+    pub learning: Learning,
+}
+
+// Picks one of the weighted book moves according to the options, scaling the
+// weights by the learned factors
+fn choose(
+    candidates: Vec<(Move, u32)>,
+    board: &Bitboards,
+    color: Color,
+    options: &BookOptions,
+) -> Option<Move> {
+    let candidates = candidates
+        .into_iter()
+        .filter(|&(_, weight)| weight >= options.min_weight.max(1))
+        .map(|(mv, weight)| {
+            let factor = options.learning.factor(board, color, &mv);
+            (mv, f64::from(weight) * factor)
+        });
+
+    match options.policy {
+        Policy::Best => candidates
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(candidate, _)| candidate),
+        Policy::Weighted => {
+            let exponent = 1.0 / options.variety.max(0.01);
+            let candidates: Vec<(Move, f64)> = candidates.collect();
+            let heaviest = candidates
+                .iter()
+                .map(|&(_, weight)| weight)
+                .fold(0.0, f64::max);
+            if heaviest <= 0.0 {
+                return None;
+            }
+            // Relative to the heaviest move, so a high exponent cannot overflow
+            let candidates: Vec<(Move, f64)> = candidates
+                .into_iter()
+                .map(|(candidate, weight)| (candidate, (weight / heaviest).powf(exponent)))
+                .collect();
+            let total: f64 = candidates.iter().map(|&(_, weight)| weight).sum();
+            let mut rng = rand::rng();
+            let mut r = rand::Rng::random_range(&mut rng, 0.0..total);
+
+            let last = candidates.len() - 1;
+            for (i, (candidate, weight)) in candidates.into_iter().enumerate() {
+                if r < weight || i == last {
+                    return Some(candidate);
+                }
+                r -= weight;
+            }
+            None
+        }
+    }
+}
+
+/// Picks a book move for the position.
+///
+/// JSON books are looked up by the piece placement of `board` (see [`placement`]),
+/// Polyglot books by the position itself. Only entries that match a legal move
+/// are considered; the others are reported and skipped, so a broken book can
+/// never feed an illegal move to [`make_move::apply_move`].
+///
+/// # Returns
+///
+/// The chosen move, or `None` when the position has no valid book move left
+/// and the caller should search instead.
+pub fn opening(
+    book: &Book,
+    board: &Bitboards,
+    color: Color,
+    options: &BookOptions,
+) -> Option<Move> {
+    let book = match book {
+        Book::Json(book) => book,
+        Book::Polyglot(book) => {
+            let moves: Vec<(Move, u32)> = book
+                .moves(board, color)
+                .into_iter()
+                .map(|(mv, weight)| (mv, u32::from(weight)))
+                .collect();
+            // Underpromotions and entries that match no legal move
+            let skipped = book.entries(polyglot::key(board, color)).len() - moves.len();
+            if skipped > 0 {
+                println!("Skipped {} book moves that cannot be played here", skipped);
+            }
+            return choose(moves, board, color, options);
+        }
+    };
+    let entries = book.get(&placement(board))?;
+    let mut candidates = Vec::with_capacity(entries.len());
+    for (uci, entry) in entries {
+        match json_move(uci, entry, board, color) {
+            Ok(mv) => candidates.push((mv, entry.count)),
+            Err(reason) => println!("Skipped book move {} ({}): {}", uci, entry.piece, reason),
+        }
+    }
+    choose(candidates, board, color, options)
+}
 
-    let from = from_rank * 8 + from_file;
-    let to = to_rank * 8 + to_file;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // Get the piece type directly from the MoveEntry
-    let piece_entry = &book.get(fen).unwrap()[&uci];
-    let piece = piece_from_str(&piece_entry.piece);
-    let mut castling: bool = false;
-    if piece == PieceType::King && absolute_subtruct(from, to) == 2 {
-        castling = true;
+    #[test]
+    fn low_variety_still_picks_a_move() {
+        let board =
+            parse_fen::board_from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
+                .unwrap();
+        let options = BookOptions {
+            policy: Policy::Weighted,
+            min_weight: 1,
+            variety: 0.01,
+            max_depth: 20,
+            learning: Learning::default(),
+        };
+        let candidates = ["e2e4", "d2d4"]
+            .into_iter()
+            .zip([65_535, 30_000])
+            .map(|(uci, weight)| (Move::from_uci(uci, &board, Color::White).unwrap(), weight))
+            .collect();
+        // 65535^100 is far beyond f64::MAX
+        let mv = choose(candidates, &board, Color::White, &options).unwrap();
+        assert_eq!(Move::move_to_uci(&mv), "e2e4");
     }
-    Some(Move {
-        from,
-        to,
-        piece,
-        promotion_rights: uci.len() == 5, // if promotion indicated, e.g. "e7e8q"
-        is_castling: castling,            // can implement castling detection if needed
-        is_capture: false,                // can implement capture detection if needed
-    })
 }
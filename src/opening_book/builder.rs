@@ -0,0 +1,210 @@
+//! # Module: `builder`
+//!
+//! Builds opening books from local PGN files (`book build`), so a book no longer
+//! needs the Python script and its download from Lichess.
+//!
+//! ## Game Selection
+//!
+//! * Only games with one of the requested results are used; unfinished games
+//!   (`*`) never are.
+//! * With a minimum Elo, both players must be rated at least that high. Games
+//!   without ratings are then skipped.
+//! * Games starting from a custom position (a `FEN` tag) are skipped.
+//!
+//! ## Weights
+//!
+//! The first `plies` moves of every game's main line are replayed with
+//! [`PgnGame::replay`]; a game stops counting at the first move that cannot be
+//! played. Each move adds a weight to its position
+//! depending on how the game ended for the side that played it, by default `2`
+//! for a win, `1` for a draw and `0` for a loss. Moves played in fewer than
+//! `min_games` games, or without any weight, are dropped.
+//!
+//! ## Output
+//!
+//! The file extension picks the format, like [`Book::load`]: `.bin` writes a
+//! Polyglot book, anything else our JSON format keyed by piece placement.
+
+use super::book::{self, Book, MoveEntry};
+use super::polyglot::{self, Entry, PolyglotBook};
+use crate::board::Color;
+use crate::engine::pgn::{self, PgnGame, Ply};
+use crate::movegen::Move;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Settings of a book build.
+pub struct BuildOptions {
+    /// Number of plies counted from the start of every game.
+    pub plies: u32,
+    /// Rating both players need; `0` accepts every game.
+    pub min_elo: u32,
+    /// Results of the games to use, e.g. `1-0`.
+    pub results: Vec<String>,
+    /// Number of games a move must appear in to be kept.
+    pub min_games: u32,
+    /// Weight of a move by the side that went on to win.
+    pub win_weight: u32,
+    /// Weight of a move in a drawn game.
+    pub draw_weight: u32,
+    /// Weight of a move by the side that went on to lose.
+    pub loss_weight: u32,
+}
+
+/// What a book build read and wrote.
+pub struct BuildSummary {
+    pub games_read: usize,
+    pub games_used: usize,
+    pub positions: usize,
+    pub moves: usize,
+}
+
+// Counts of one move in one position
+struct Tally {
+    mv: Move,
+    color: Color,
+    games: u32,
+    weight: u32,
+}
+
+// One position by its Polyglot key, with its piece placement for JSON books
+struct Position {
+    placement: String,
+    moves: HashMap<u16, Tally>,
+}
+
+/// Reads the games in `pgn_paths` and writes the book to `out`.
+///
+/// # Returns
+///
+/// A summary of the build, or the error of a file that could not be read or
+/// written.
+pub fn build(
+    pgn_paths: &[PathBuf],
+    out: &Path,
+    options: &BuildOptions,
+) -> io::Result<BuildSummary> {
+    let mut positions: HashMap<u64, Position> = HashMap::new();
+    let mut games_read = 0;
+    let mut games_used = 0;
+
+    for path in pgn_paths {
+        let text = std::fs::read_to_string(path)?;
+        for game in pgn::read_games(&text) {
+            games_read += 1;
+            if accepts(&game, options) {
+                games_used += 1;
+                count_game(&game, options, &mut positions);
+            }
+        }
+    }
+
+    // Drop rare and weightless moves, then positions left without moves
+    for position in positions.values_mut() {
+        position
+            .moves
+            .retain(|_, tally| tally.games >= options.min_games && tally.weight > 0);
+    }
+    positions.retain(|_, position| !position.moves.is_empty());
+
+    let moves = positions
+        .values()
+        .map(|position| position.moves.len())
+        .sum();
+    let summary = BuildSummary {
+        games_read,
+        games_used,
+        positions: positions.len(),
+        moves,
+    };
+
+    let book = if book::is_polyglot_path(out) {
+        polyglot_book(&positions)
+    } else {
+        json_book(&positions)
+    };
+    book.save(out)?;
+    Ok(summary)
+}
+
+fn accepts(game: &PgnGame, options: &BuildOptions) -> bool {
+    if game.result == "*" || !options.results.contains(&game.result) {
+        return false;
+    }
+    if game.tag("FEN").is_some() {
+        return false;
+    }
+    if options.min_elo > 0 {
+        let elo = |tag: &str| game.tag(tag).and_then(|elo| elo.parse::<u32>().ok());
+        let (Some(white), Some(black)) = (elo("WhiteElo"), elo("BlackElo")) else {
+            return false;
+        };
+        return white.min(black) >= options.min_elo;
+    }
+    true
+}
+
+fn count_game(game: &PgnGame, options: &BuildOptions, positions: &mut HashMap<u64, Position>) {
+    let Ok(replay) = game.replay() else {
+        return;
+    };
+
+    for Ply { board, color, mv } in replay.plies.iter().take(options.plies as usize) {
+        let color = *color;
+        let weight = match (game.result.as_str(), color) {
+            ("1-0", Color::White) | ("0-1", Color::Black) => options.win_weight,
+            ("1/2-1/2", _) => options.draw_weight,
+            _ => options.loss_weight,
+        };
+
+        let position = positions
+            .entry(polyglot::key(board, color))
+            .or_insert_with(|| Position {
+                placement: book::placement(board),
+                moves: HashMap::new(),
+            });
+        let tally = position
+            .moves
+            .entry(polyglot::encode_move(mv))
+            .or_insert_with(|| Tally {
+                mv: mv.clone(),
+                color,
+                games: 0,
+                weight: 0,
+            });
+        tally.games += 1;
+        tally.weight += weight;
+    }
+}
+
+fn polyglot_book(positions: &HashMap<u64, Position>) -> Book {
+    let entries = positions
+        .iter()
+        .flat_map(|(&key, position)| {
+            position.moves.iter().map(move |(&raw_move, tally)| Entry {
+                key,
+                raw_move,
+                weight: tally.weight.min(u32::from(u16::MAX)) as u16,
+            })
+        })
+        .collect();
+    Book::Polyglot(PolyglotBook::from_entries(entries))
+}
+
+fn json_book(positions: &HashMap<u64, Position>) -> Book {
+    let mut book: HashMap<String, HashMap<String, MoveEntry>> = HashMap::new();
+    for position in positions.values() {
+        let moves = book.entry(position.placement.clone()).or_default();
+        for tally in position.moves.values() {
+            let entry = moves
+                .entry(Move::move_to_uci(&tally.mv))
+                .or_insert_with(|| MoveEntry {
+                    count: 0,
+                    piece: book::piece_letter(tally.mv.piece, tally.color).to_string(),
+                });
+            entry.count += tally.weight;
+        }
+    }
+    Book::Json(book)
+}
@@ -0,0 +1,190 @@
+//! # Module: `convert`
+//!
+//! Conversion between the two book formats, and merging of books, for the
+//! `book convert` and `book merge` subcommands.
+//!
+//! ## JSON to Polyglot
+//!
+//! JSON books are keyed by piece placement alone, so the rest of the position
+//! has to be filled in before a Polyglot key can be computed:
+//!
+//! * the side to move is the color of the entry's `piece`;
+//! * castling rights are assumed wherever King and Rook still stand on their
+//!   starting squares;
+//! * no en passant square is set.
+//!
+//! ## Polyglot to JSON
+//!
+//! Polyglot keys are hashes and cannot be turned back into positions. The book
+//! is therefore walked from the starting position, following its own moves;
+//! entries of positions the walk never reaches are dropped.
+//!
+//! In both directions, entries that do not match a legal move are dropped and
+//! counted as skipped. Merged books add up the weights of moves found in more
+//! than one input, Polyglot weights saturating at `u16::MAX`.
+
+use super::book::{self, Book, MoveEntry};
+use super::polyglot::{self, Entry, PolyglotBook};
+use crate::board::{Bitboards, Color, PieceType};
+use crate::engine::{parse_fen, search::opposite};
+use crate::make_move;
+use crate::movegen::Move;
+use std::collections::{HashMap, HashSet};
+
+const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// Rewrites a book in the Polyglot format (`polyglot`) or the JSON format.
+///
+/// # Returns
+///
+/// The converted book and the number of entries that could not be carried
+/// over. A book already in the requested format is copied unchanged.
+pub fn convert(book: &Book, polyglot: bool) -> (Book, usize) {
+    match (book, polyglot) {
+        (Book::Json(json), true) => {
+            let (entries, skipped) = json_entries(json);
+            (Book::Polyglot(PolyglotBook::from_entries(entries)), skipped)
+        }
+        (Book::Polyglot(book), false) => {
+            let (json, skipped) = walk_polyglot(book);
+            (Book::Json(json), skipped)
+        }
+        _ => (book.clone(), 0),
+    }
+}
+
+/// Combines books into one in the Polyglot format (`polyglot`) or the JSON
+/// format.
+///
+/// # Returns
+///
+/// The merged book and the number of entries lost converting the inputs.
+pub fn merge(books: &[Book], polyglot: bool) -> (Book, usize) {
+    let mut skipped = 0;
+    let mut weights: HashMap<(u64, u16), u32> = HashMap::new();
+    let mut json: HashMap<String, HashMap<String, MoveEntry>> = HashMap::new();
+
+    for input in books {
+        let (converted, lost) = convert(input, polyglot);
+        skipped += lost;
+        match converted {
+            Book::Polyglot(book) => {
+                for entry in book.all_entries() {
+                    *weights.entry((entry.key, entry.raw_move)).or_default() +=
+                        u32::from(entry.weight);
+                }
+            }
+            Book::Json(book) => {
+                for (placement, moves) in book {
+                    let merged = json.entry(placement).or_default();
+                    for (uci, entry) in moves {
+                        merged
+                            .entry(uci)
+                            .and_modify(|merged| merged.count += entry.count)
+                            .or_insert(entry);
+                    }
+                }
+            }
+        }
+    }
+
+    if !polyglot {
+        return (Book::Json(json), skipped);
+    }
+    let entries = weights
+        .into_iter()
+        .map(|((key, raw_move), weight)| Entry {
+            key,
+            raw_move,
+            weight: weight.min(u32::from(u16::MAX)) as u16,
+        })
+        .collect();
+    (Book::Polyglot(PolyglotBook::from_entries(entries)), skipped)
+}
+
+fn json_entries(json: &HashMap<String, HashMap<String, MoveEntry>>) -> (Vec<Entry>, usize) {
+    let mut entries = Vec::new();
+    let mut skipped = 0;
+    for (placement, moves) in json {
+        for (uci, entry) in moves {
+            let color = if entry.piece.starts_with(char::is_uppercase) {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let Some(board) = position(placement) else {
+                skipped += 1;
+                continue;
+            };
+            match book::json_move(uci, entry, &board, color) {
+                Ok(mv) => entries.push(Entry {
+                    key: polyglot::key(&board, color),
+                    raw_move: polyglot::encode_move(&mv),
+                    weight: entry.count.min(u32::from(u16::MAX)) as u16,
+                }),
+                Err(_) => skipped += 1,
+            }
+        }
+    }
+    (entries, skipped)
+}
+
+// The board of a piece placement, with the castling rights its King and Rook
+// squares allow
+fn position(placement: &str) -> Option<Bitboards> {
+    let mut board = parse_fen::board_from_fen(&format!("{} w - - 0 1", placement))?;
+    let on = |color: Color, piece: PieceType, sq: u8| {
+        board.boards[color as usize][piece as usize] & (1 << sq) != 0
+    };
+    let white_king = on(Color::White, PieceType::King, 4);
+    let black_king = on(Color::Black, PieceType::King, 60);
+    let rights = [
+        white_king && on(Color::White, PieceType::Rook, 7),
+        white_king && on(Color::White, PieceType::Rook, 0),
+        black_king && on(Color::Black, PieceType::Rook, 63),
+        black_king && on(Color::Black, PieceType::Rook, 56),
+    ];
+    [
+        board.white_kingside,
+        board.white_queenside,
+        board.black_kingside,
+        board.black_queenside,
+    ] = rights;
+    Some(board)
+}
+
+// Follows the book's moves from the starting position, collecting them by
+// piece placement
+fn walk_polyglot(book: &PolyglotBook) -> (HashMap<String, HashMap<String, MoveEntry>>, usize) {
+    let mut json: HashMap<String, HashMap<String, MoveEntry>> = HashMap::new();
+    let mut visited = HashSet::new();
+    let mut converted = 0;
+
+    let start = parse_fen::board_from_fen(START_FEN).expect("valid start position");
+    let mut stack = vec![(start, Color::White)];
+    while let Some((board, color)) = stack.pop() {
+        let key = polyglot::key(&board, color);
+        if !visited.insert(key) {
+            continue;
+        }
+        for entry in book.entries(key) {
+            let Some(mv) = polyglot::decode_move(entry.raw_move, &board, color) else {
+                continue;
+            };
+            converted += 1;
+            json.entry(book::placement(&board))
+                .or_default()
+                .entry(Move::move_to_uci(&mv))
+                .or_insert_with(|| MoveEntry {
+                    count: 0,
+                    piece: book::piece_letter(mv.piece, color).to_string(),
+                })
+                .count += u32::from(entry.weight);
+
+            let mut next = board.clone();
+            make_move::apply_move(&mut next, &mv, color);
+            stack.push((next, opposite(color)));
+        }
+    }
+    (json, book.all_entries().len() - converted)
+}
@@ -0,0 +1,149 @@
+//! # Module: `learning`
+//!
+//! **Book learning**: after every game the engine adjusts how often it plays the
+//! book moves it chose, so lines that keep going badly for it are played less.
+//!
+//! ## Signal
+//!
+//! Each game yields one signal between `-1` and `1`, seen from the engine's
+//! side:
+//!
+//! * the result: `1` for a win, `0` for a draw, `-1` for a loss;
+//! * the search score of the engine's last scored move among the first
+//!   `SCORE_DELAY` moves after leaving the book, scaled by `SCORE_SCALE`
+//!   centipawns and clamped.
+//!
+//! With a score, the signal is the mean of the two; without one (a game that
+//! ended in the book, or tablebase moves only) it is the result alone.
+//!
+//! ## Factors
+//!
+//! The book itself is never changed. Instead every book move the engine played
+//! gets a factor, multiplied by `exp(LEARNING_RATE * signal)` after each game,
+//! that scales the move's weight when choosing among the book moves. Factors
+//! are stored as JSON by Polyglot key (in hex) and UCI move, so they work with
+//! either book format.
+
+use super::polyglot;
+use crate::board::{Bitboards, Color};
+use crate::engine::search::MoveSource;
+use crate::movegen::Move;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+// Engine moves after leaving the book whose search scores count
+const SCORE_DELAY: u32 = 4;
+
+// Score, in centipawns, that counts as fully won or lost
+const SCORE_SCALE: f64 = 400.0;
+
+// Change of the log factor per game at full signal
+const LEARNING_RATE: f64 = 0.2;
+
+// Bounds of the factors, so no line is ever ruled out or forced for good
+const MIN_FACTOR: f64 = 0.05;
+const MAX_FACTOR: f64 = 20.0;
+
+/// Learned weight factors, by position and move.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Learning {
+    factors: HashMap<String, HashMap<String, f64>>,
+}
+
+impl Learning {
+    /// Reads learned factors from disk.
+    ///
+    /// # Returns
+    ///
+    /// The factors, nothing learned yet if the file does not exist, or an error
+    /// if it cannot be read or parsed.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let data = match std::fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err),
+        };
+        serde_json::from_str(&data).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Writes the factors as JSON.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer_pretty(file, self).map_err(io::Error::other)
+    }
+
+    /// The factor of a book move, `1` if nothing was learned about it.
+    pub fn factor(&self, board: &Bitboards, color: Color, mv: &Move) -> f64 {
+        self.factors
+            .get(&position_key(board, color))
+            .and_then(|moves| moves.get(&Move::move_to_uci(mv)))
+            .copied()
+            .unwrap_or(1.0)
+    }
+
+    /// Updates the factors of the book moves of a finished game.
+    ///
+    /// # Arguments
+    ///
+    /// * `record` - The engine's moves of the game.
+    /// * `result` - `1` if the engine won, `0` for a draw, `-1` if it lost.
+    ///
+    /// # Returns
+    ///
+    /// The signal applied to the book moves.
+    pub fn learn(&mut self, record: &GameRecord, result: f64) -> f64 {
+        let signal = match record.score {
+            Some(score) => (result + (f64::from(score) / SCORE_SCALE).clamp(-1.0, 1.0)) / 2.0,
+            None => result,
+        };
+        let change = (LEARNING_RATE * signal).exp();
+        for (key, uci) in &record.book_moves {
+            let factor = self
+                .factors
+                .entry(key.clone())
+                .or_default()
+                .entry(uci.clone())
+                .or_insert(1.0);
+            *factor = (*factor * change).clamp(MIN_FACTOR, MAX_FACTOR);
+        }
+        signal
+    }
+}
+
+/// The engine's moves of the current game, as far as learning needs them.
+#[derive(Default)]
+pub struct GameRecord {
+    book_moves: Vec<(String, String)>,
+    moves_after_book: u32,
+    score: Option<i32>,
+}
+
+impl GameRecord {
+    /// Notes a move the engine played in `board`.
+    pub fn record(&mut self, board: &Bitboards, color: Color, mv: &Move, source: MoveSource) {
+        match source {
+            MoveSource::Book => {
+                self.book_moves
+                    .push((position_key(board, color), Move::move_to_uci(mv)));
+            }
+            _ if self.book_moves.is_empty() || self.moves_after_book >= SCORE_DELAY => {}
+            MoveSource::Tablebase => self.moves_after_book += 1,
+            MoveSource::Search(score) => {
+                self.moves_after_book += 1;
+                self.score = Some(score);
+            }
+        }
+    }
+
+    /// Whether the engine played any book move this game.
+    pub fn used_book(&self) -> bool {
+        !self.book_moves.is_empty()
+    }
+}
+
+fn position_key(board: &Bitboards, color: Color) -> String {
+    format!("{:016x}", polyglot::key(board, color))
+}
@@ -1 +1,5 @@
 pub(crate) mod book;
+pub(crate) mod builder;
+pub(crate) mod convert;
+pub(crate) mod learning;
+pub(crate) mod polyglot;